@@ -176,6 +176,63 @@ macro_rules! debug_checked_assume_ne {
     );
 }
 
+/// Declares an invariant of a struct, enum or union type that is defined in the current crate.
+/// The condition is an expression over the identifier that precedes the =>, which is bound to a
+/// reference to a value of the type. For example: invariant!(Cart, cart => cart.total <= cart.limit).
+/// This macro expands to nothing unless the program is compiled with MIRAI.
+/// When compiled with MIRAI, the invariant is assumed on entry to every public method of the type
+/// that takes &self or &mut self. It is verified on return from every such method and from every
+/// public function of the type that returns a new value of the type, and callers of all of these
+/// functions can assume it after the call.
+/// Generic types are not supported.
+#[macro_export]
+macro_rules! invariant {
+    ($type:ty, $value:ident => $condition:expr) => {
+        #[cfg(mirai)]
+        impl $type {
+            #[doc(hidden)]
+            #[allow(dead_code)]
+            pub fn mirai_invariant(&self) -> bool {
+                let $value = self;
+                $condition
+            }
+        }
+    };
+}
+
+/// Equivalent to a no op when used with an unmodified Rust compiler.
+/// When compiled with MIRAI, this declares an integer measure that shows that a loop or a
+/// recursive function terminates, for example decreases!(n - i).
+/// At the start of the body of a loop, MIRAI verifies that the measure is never negative and that
+/// every iteration of the loop makes it smaller.
+/// At the start of the body of a function, MIRAI verifies that the measure is never negative and
+/// that it is smaller for the arguments of every call the function makes to itself.
+/// Recursion via other functions is not checked, and calls to functions that can call back the
+/// function are reported as possible non-termination.
+#[macro_export]
+macro_rules! decreases {
+    ($measure:expr) => {
+        if cfg!(mirai) {
+            mirai_annotations::mirai_decreases($measure)
+        }
+    };
+}
+
+/// Equivalent to a no op when used with an unmodified Rust compiler.
+/// When compiled with MIRAI, this must be at the start of the body of a loop. MIRAI verifies that
+/// the condition holds on entry to the loop and that it holds again after every iteration of
+/// the loop, given that it held at the start of the iteration. The condition is then assumed at
+/// the start of every iteration, which makes up for facts about the variables modified by the
+/// loop that MIRAI cannot infer by itself.
+#[macro_export]
+macro_rules! loop_invariant {
+    ($condition:expr) => {
+        if cfg!(mirai) {
+            mirai_annotations::mirai_loop_invariant($condition)
+        }
+    };
+}
+
 /// Equivalent to a no op when used with an unmodified Rust compiler.
 /// When compiled with MIRAI, this causes MIRAI to verify the condition at the
 /// point where it appears in a function, but to also add it a postcondition that can
@@ -814,6 +871,78 @@ macro_rules! debug_checked_verify_ne {
     );
 }
 
+/// Equivalent to a no op when used with an unmodified Rust compiler.
+/// When compiled with MIRAI, this marks the value of the given place, and the values of all of its
+/// parts, as tainted with the given kind of taint, for example add_taint!(request, UserInput).
+/// The kind must have been declared with taint_kind!. MIRAI tracks the taint through
+/// computations, copies and calls, until it is removed with remove_taint!.
+#[macro_export]
+macro_rules! add_taint {
+    ($value:expr, $kind:ty) => {
+        if cfg!(mirai) {
+            mirai_annotations::mirai_add_taint::<_, $kind>(&$value)
+        }
+    };
+}
+
+/// Provides a way to state that a condition holds for at least one index in a range, for example
+/// postcondition!(exists!(i in 0..v.len(), v[i] == x)).
+/// MIRAI treats this as an existential quantifier, so that the condition need not be checked for
+/// every index. When not compiled with MIRAI, this evaluates the condition for the indices in the
+/// range until it holds, so it should only be used in conditions that are not checked at runtime,
+/// unless the range is small.
+#[macro_export]
+macro_rules! exists {
+    ($index:ident in $range:expr, $condition:expr) => {
+        mirai_annotations::mirai_exists($range, |$index: usize| $condition)
+    };
+}
+
+/// Provides a way to state that a condition holds for every index in a range, for example
+/// precondition!(forall!(i in 0..v.len(), v[i] != 0)).
+/// MIRAI treats this as a universal quantifier, so that the condition need not be checked for
+/// every index. When not compiled with MIRAI, this evaluates the condition for every index in the
+/// range, so it should only be used in conditions that are not checked at runtime, unless the
+/// range is small.
+#[macro_export]
+macro_rules! forall {
+    ($index:ident in $range:expr, $condition:expr) => {
+        mirai_annotations::mirai_forall($range, |$index: usize| $condition)
+    };
+}
+
+/// Provides a block of specification-only code, for example ghost! { LOCKS += 1; }.
+/// The block is compiled only when the program is compiled with MIRAI, so it can use ghost statics
+/// (see ghost_static!) and can update them, as well as contain other annotations.
+/// The block is unsafe, since ghost statics are mutable statics, but it is never executed at runtime.
+#[macro_export]
+macro_rules! ghost {
+    ($($body:tt)*) => {
+        #[cfg(mirai)]
+        #[allow(unused_unsafe)]
+        unsafe {
+            $($body)*
+        }
+    };
+}
+
+/// Declares a ghost static variable, which is specification-only state such as a count of the
+/// locks that are held, for example ghost_static! { static LOCKS: usize = 0; }
+/// This macro expands to nothing unless the program is compiled with MIRAI, so a ghost static can
+/// only be used inside ghost! blocks. When compiled with MIRAI, updates of ghost statics become
+/// side effects of the functions that make them, so that callers track them along with their own
+/// state. Since code that runs before a function can update a ghost static, MIRAI does not assume
+/// that a ghost static has its initial value on entry to a function.
+#[macro_export]
+macro_rules! ghost_static {
+    ($(#[$attr:meta])* $vis:vis static $name:ident : $type:ty = $value:expr;) => {
+        #[cfg(mirai)]
+        #[mirai::ghost]
+        $(#[$attr])*
+        $vis static mut $name: $type = $value;
+    };
+}
+
 /// Retrieves the value of the specified model field, or the given default value if the model field
 /// is not set.
 /// This function has no meaning outside of a verification
@@ -826,6 +955,60 @@ macro_rules! get_model_field {
     };
 }
 
+/// Provides a way to refer to whether the value of the given place, or the value of any of its
+/// parts, is tainted with the given kind of taint.
+/// This evaluates to false unless the program is compiled with MIRAI, so it has no meaning outside
+/// of a verification condition and should not be used with checked or debug_checked conditions.
+/// For example: postcondition!(!has_taint!(result, UserInput)).
+#[macro_export]
+macro_rules! has_taint {
+    ($value:expr, $kind:ty) => {
+        mirai_annotations::mirai_has_taint::<_, $kind>(&$value)
+    };
+}
+
+/// Equivalent to a no op when used with an unmodified Rust compiler.
+/// When compiled with MIRAI, this declares the places, rooted in parameters or statics, that the
+/// function may modify, for example modifies!(self.count, *buffer).
+/// MIRAI checks that the function does not modify any other places that are reachable from its
+/// parameters. When used in a contract function, such as a foreign contract or the default body
+/// of a trait method, callers forget what they know about the listed places, rather than relying
+/// on the (usually abstract) body of the contract to tell them what the new values are.
+#[macro_export]
+macro_rules! modifies {
+    ($($place:expr),+ $(,)?) => {
+        if cfg!(mirai) {
+            $(mirai_annotations::mirai_modifies(&$place);)+
+        }
+    };
+}
+
+/// Provides a way to refer to the value that a place had on entry to the function, from inside
+/// a postcondition. The place should be rooted in a parameter or a static, for example
+/// postcondition!(self.count == old!(self.count) + 1).
+/// When the program is not compiled with MIRAI, this evaluates to the current value of the place,
+/// so a checked postcondition that uses old! does not check the same condition at runtime.
+#[macro_export]
+macro_rules! old {
+    ($value:expr) => {
+        mirai_annotations::mirai_old(&$value)
+    };
+}
+
+/// Equivalent to a no op when used with an unmodified Rust compiler.
+/// When compiled with MIRAI, this removes the given kind of taint from the value of the given
+/// place, and from the values of all of its parts. This should be used in sanitizers, for example
+/// remove_taint!(escaped, UserInput), once the value has been checked or transformed so that it
+/// is safe to use in sinks for the kind of taint. Other kinds of taint are left in place.
+#[macro_export]
+macro_rules! remove_taint {
+    ($value:expr, $kind:ty) => {
+        if cfg!(mirai) {
+            mirai_annotations::mirai_remove_taint::<_, $kind>(&$value)
+        }
+    };
+}
+
 /// Provides a way to refer to the result value of an abstract or contract function without
 /// specifying an actual value anywhere.
 /// This macro expands to unimplemented!() unless the program is compiled with MIRAI.
@@ -856,6 +1039,102 @@ macro_rules! set_model_field {
     };
 }
 
+/// Declares a kind of taint, for example taint_kind!(pub UserInput).
+/// The kind is a type that can be used with add_taint!, has_taint!, remove_taint! and taint_sink!,
+/// so that different kinds of taint can be added, checked and removed independently of each other.
+#[macro_export]
+macro_rules! taint_kind {
+    ($(#[$attr:meta])* $vis:vis $name:ident) => {
+        $(#[$attr])*
+        $vis struct $name;
+        impl mirai_annotations::TaintKind for $name {}
+    };
+}
+
+/// Equivalent to a no op when used with an unmodified Rust compiler.
+/// When compiled with MIRAI, this causes MIRAI to verify that the value of the given place, and
+/// the values of all of its parts, are not tainted with the given kind of taint, for example
+/// taint_sink!(query, UserInput). If this depends on the values of parameters, the check becomes
+/// a precondition of the function, so that tainted data is reported where it enters the sink,
+/// along with the calls through which it got there.
+#[macro_export]
+macro_rules! taint_sink {
+    ($value:expr, $kind:ty) => {
+        if cfg!(mirai) {
+            mirai_annotations::mirai_taint_sink::<_, $kind>(&$value)
+        }
+    };
+}
+
+/// Declares a typestate protocol for a type: the states that a value of the type can be in and,
+/// for each method that the protocol constrains, the states in which the method may be called and
+/// the state that the call leads to. The first state is the state of a newly constructed value.
+/// For example:
+/// typestate!(Connection {
+///     states: Closed, Open, Finished;
+///     open: Closed -> Open,
+///     send: Open -> Open,
+///     close: Open -> Finished,
+/// });
+/// This macro expands to nothing unless the program is compiled with MIRAI.
+/// When compiled with MIRAI, calls to the constrained methods are checked against the protocol,
+/// so that calling send before open, or calling close twice, is reported at the offending call.
+/// Methods that are not listed may be called in any state and do not change the state.
+/// MIRAI does not check that a value reaches a particular state before it is dropped.
+/// Generic types are not supported.
+#[macro_export]
+macro_rules! typestate {
+    ($type:ty {
+        states: $initial:ident $(, $state:ident)* $(,)?;
+        $($method:ident : $from:ident $(| $other:ident)* -> $to:ident),+ $(,)?
+    }) => {
+        #[cfg(mirai)]
+        const _: () = {
+            #[allow(dead_code)]
+            enum MiraiState {
+                $initial,
+                $($state,)*
+            }
+            #[doc(hidden)]
+            trait MiraiTypestate {
+                $(fn $method(&self);)+
+            }
+            impl MiraiTypestate for $type {
+                $(
+                    fn $method(&self) {
+                        let state = mirai_annotations::mirai_get_model_field(
+                            self,
+                            "mirai_typestate",
+                            MiraiState::$initial as usize,
+                        );
+                        mirai_annotations::mirai_precondition_start();
+                        mirai_annotations::mirai_precondition(
+                            state == MiraiState::$from as usize
+                                $(|| state == MiraiState::$other as usize)*,
+                            concat!(
+                                "protocol violation: ",
+                                stringify!($method),
+                                " requires ",
+                                stringify!($type),
+                                " to be in state ",
+                                stringify!($from $(| $other)*)
+                            ),
+                        );
+                        mirai_annotations::mirai_set_model_field(
+                            self,
+                            "mirai_typestate",
+                            MiraiState::$to as usize,
+                        );
+                    }
+                )+
+            }
+        };
+    };
+}
+
+/// Implemented by the types that are declared as kinds of taint with the taint_kind! macro.
+pub trait TaintKind {}
+
 /// Equivalent to unreachable! when used with an unmodified Rust compiler.
 /// When compiled with MIRAI, this causes MIRAI to assume that the annotation statement cannot be reached.
 #[macro_export]
@@ -930,6 +1209,10 @@ pub fn mirai_abstract_value<T>(x: T) -> T {
     x
 }
 
+// Helper function for MIRAI. Should only be called via the add_taint! macro.
+#[doc(hidden)]
+pub fn mirai_add_taint<T: ?Sized, K: TaintKind>(_value: &T) {}
+
 // Helper function for MIRAI. Should only be called via the assume macros.
 #[doc(hidden)]
 pub fn mirai_assume(_condition: bool) {}
@@ -954,12 +1237,52 @@ pub fn mirai_precondition(_condition: bool, _message: &str) {}
 #[doc(hidden)]
 pub fn mirai_verify(_condition: bool, _message: &str) {}
 
+// Helper function for MIRAI. Should only be called via the decreases! macro.
+#[doc(hidden)]
+pub fn mirai_decreases<T: Copy + Ord>(_measure: T) {}
+
+// Helper function for MIRAI. Should only be called via the exists! macro.
+#[doc(hidden)]
+pub fn mirai_exists<F: Fn(usize) -> bool>(mut range: std::ops::Range<usize>, condition: F) -> bool {
+    range.any(condition)
+}
+
+// Helper function for MIRAI. Should only be called via the forall! macro.
+#[doc(hidden)]
+pub fn mirai_forall<F: Fn(usize) -> bool>(mut range: std::ops::Range<usize>, condition: F) -> bool {
+    range.all(condition)
+}
+
 // Helper function for MIRAI. Should only be called via the get_model_field macro.
 #[doc(hidden)]
 pub fn mirai_get_model_field<T, V>(_target: T, _field_name: &str, default_value: V) -> V {
     default_value
 }
 
+// Helper function for MIRAI. Should only be called via the has_taint! macro.
+#[doc(hidden)]
+pub fn mirai_has_taint<T: ?Sized, K: TaintKind>(_value: &T) -> bool {
+    false
+}
+
+// Helper function for MIRAI. Should only be called via the loop_invariant macro.
+#[doc(hidden)]
+pub fn mirai_loop_invariant(_condition: bool) {}
+
+// Helper function for MIRAI. Should only be called via the modifies! macro.
+#[doc(hidden)]
+pub fn mirai_modifies<T: ?Sized>(_place: &T) {}
+
+// Helper function for MIRAI. Should only be called via the old! macro.
+#[doc(hidden)]
+pub fn mirai_old<T: Copy>(value: &T) -> T {
+    *value
+}
+
+// Helper function for MIRAI. Should only be called via the remove_taint! macro.
+#[doc(hidden)]
+pub fn mirai_remove_taint<T: ?Sized, K: TaintKind>(_value: &T) {}
+
 // Helper function for MIRAI. Should only be called via the result! macro.
 #[doc(hidden)]
 pub fn mirai_result<T>() -> T {
@@ -969,3 +1292,7 @@ pub fn mirai_result<T>() -> T {
 // Helper function for MIRAI. Should only be called via the set_model_field macro.
 #[doc(hidden)]
 pub fn mirai_set_model_field<T, V>(_target: T, _field_name: &str, _value: V) {}
+
+// Helper function for MIRAI. Should only be called via the taint_sink! macro.
+#[doc(hidden)]
+pub fn mirai_taint_sink<T: ?Sized, K: TaintKind>(_value: &T) {}
@@ -7,7 +7,7 @@
 use crate::constant_domain::ConstantDomain;
 use crate::environment::Environment;
 use crate::expression::Expression::{ConditionalExpression, Join, Widen};
-use crate::expression::{Expression, ExpressionType, LayoutSource};
+use crate::expression::{Expression, ExpressionType, LayoutSource, QuantifierKind};
 use crate::interval_domain::{self, IntervalDomain};
 use crate::k_limits;
 use crate::path::PathRefinement;
@@ -199,7 +199,7 @@ impl AbstractValue {
     /// Initializes the optional domains to None.
     #[logfn_inputs(TRACE)]
     pub fn make_from(expression: Expression, expression_size: u64) -> Rc<AbstractValue> {
-        if expression_size > k_limits::MAX_EXPRESSION_SIZE {
+        if expression_size > k_limits::max_expression_size() {
             // If the expression gets too large, refining it gets expensive and composing it
             // into other expressions leads to exponential growth. We therefore need to abstract
             // (go up in the lattice). We do that by making the expression a typed variable and
@@ -228,6 +228,51 @@ impl AbstractValue {
         }
     }
 
+    /// Creates an abstract value that is true if condition is true for every (or some) value of
+    /// the bound variable in the range lower_bound..upper_bound.
+    #[logfn_inputs(TRACE)]
+    pub fn make_quantifier(
+        kind: QuantifierKind,
+        variable: Rc<Path>,
+        lower_bound: Rc<AbstractValue>,
+        upper_bound: Rc<AbstractValue>,
+        condition: Rc<AbstractValue>,
+    ) -> Rc<AbstractValue> {
+        let range_is_empty = lower_bound
+            .greater_or_equal(upper_bound.clone())
+            .as_bool_if_known();
+        match (kind, range_is_empty, condition.as_bool_if_known()) {
+            (QuantifierKind::ForAll, Some(true), _) | (QuantifierKind::ForAll, _, Some(true)) => {
+                return Rc::new(TRUE);
+            }
+            (QuantifierKind::ForAll, Some(false), Some(false)) => {
+                return Rc::new(FALSE);
+            }
+            (QuantifierKind::Exists, Some(true), _) | (QuantifierKind::Exists, _, Some(false)) => {
+                return Rc::new(FALSE);
+            }
+            (QuantifierKind::Exists, Some(false), Some(true)) => {
+                return Rc::new(TRUE);
+            }
+            _ => {}
+        }
+        let expression_size = lower_bound
+            .expression_size
+            .saturating_add(upper_bound.expression_size)
+            .saturating_add(condition.expression_size)
+            .saturating_add(1);
+        AbstractValue::make_from(
+            Expression::Quantifier {
+                kind,
+                variable,
+                lower_bound,
+                upper_bound,
+                condition,
+            },
+            expression_size,
+        )
+    }
+
     /// Creates an abstract value that is a reference to the memory named by the given path.
     #[logfn_inputs(TRACE)]
     pub fn make_reference(path: Rc<Path>) -> Rc<AbstractValue> {
@@ -259,6 +304,7 @@ pub trait AbstractValueTrait: Sized {
         widened_env: &Environment,
     ) -> Self;
     fn add_overflows(&self, other: Self, target_type: ExpressionType) -> Self;
+    fn add_taint(&self, kind: Rc<String>) -> Self;
     fn and(&self, other: Self) -> Self;
     fn as_bool_if_known(&self) -> Option<bool>;
     fn as_int_if_known(&self) -> Option<Rc<AbstractValue>>;
@@ -273,6 +319,7 @@ pub trait AbstractValueTrait: Sized {
     fn equals(&self, other: Self) -> Self;
     fn greater_or_equal(&self, other: Self) -> Self;
     fn greater_than(&self, other: Self) -> Self;
+    fn has_taint(&self, kind: &Rc<String>) -> Self;
     fn implies(&self, other: &Self) -> bool;
     fn implies_not(&self, other: &Self) -> bool;
     fn intrinsic_binary(&self, other: Self, name: KnownNames) -> Self;
@@ -296,6 +343,7 @@ pub trait AbstractValueTrait: Sized {
     fn or(&self, other: Self) -> Self;
     fn record_heap_blocks(&self, result: &mut HashSet<Rc<AbstractValue>>);
     fn remainder(&self, other: Self) -> Self;
+    fn remove_taint(&self, kind: Rc<String>) -> Self;
     fn shift_left(&self, other: Self) -> Self;
     fn shl_overflows(&self, other: Self, target_type: ExpressionType) -> Self;
     fn shr(&self, other: Self, expression_type: ExpressionType) -> Self;
@@ -432,6 +480,23 @@ impl AbstractValueTrait for Rc<AbstractValue> {
         )
     }
 
+    /// Returns an element that is self, tainted with the given kind of taint.
+    #[logfn_inputs(TRACE)]
+    fn add_taint(&self, kind: Rc<String>) -> Rc<AbstractValue> {
+        match &self.expression {
+            Expression::Bottom | Expression::Top => self.clone(),
+            Expression::Tainted { kind: k, .. } if *k == kind => self.clone(),
+            Expression::Sanitized { operand, kind: k } if *k == kind => operand.add_taint(kind),
+            _ => AbstractValue::make_from(
+                Expression::Tainted {
+                    operand: self.clone(),
+                    kind,
+                },
+                self.expression_size.saturating_add(1),
+            ),
+        }
+    }
+
     /// Returns an element that is "self && other".
     #[logfn_inputs(TRACE)]
     fn and(&self, other: Rc<AbstractValue>) -> Rc<AbstractValue> {
@@ -1255,6 +1320,96 @@ impl AbstractValueTrait for Rc<AbstractValue> {
         })
     }
 
+    /// Returns an element that is true if self has been tainted with the given kind of taint,
+    /// or has been computed from a value that has been tainted with it.
+    #[logfn_inputs(TRACE)]
+    fn has_taint(&self, kind: &Rc<String>) -> Rc<AbstractValue> {
+        match &self.expression {
+            Expression::Bottom
+            | Expression::Top
+            | Expression::CompileTimeConstant(..)
+            | Expression::HasTaint { .. }
+            | Expression::HeapBlock { .. }
+            | Expression::HeapBlockLayout { .. }
+            | Expression::Quantifier { .. }
+            | Expression::Reference(..)
+            | Expression::UnknownModelField { .. } => Rc::new(FALSE),
+            Expression::Add { left, right }
+            | Expression::AddOverflows { left, right, .. }
+            | Expression::And { left, right }
+            | Expression::BitAnd { left, right }
+            | Expression::BitOr { left, right }
+            | Expression::BitXor { left, right }
+            | Expression::Div { left, right }
+            | Expression::Equals { left, right }
+            | Expression::GreaterOrEqual { left, right }
+            | Expression::GreaterThan { left, right }
+            | Expression::IntrinsicBinary { left, right, .. }
+            | Expression::Join { left, right, .. }
+            | Expression::LessOrEqual { left, right }
+            | Expression::LessThan { left, right }
+            | Expression::Mul { left, right }
+            | Expression::MulOverflows { left, right, .. }
+            | Expression::Ne { left, right }
+            | Expression::Offset { left, right }
+            | Expression::Or { left, right }
+            | Expression::Rem { left, right }
+            | Expression::Shl { left, right }
+            | Expression::ShlOverflows { left, right, .. }
+            | Expression::Shr { left, right, .. }
+            | Expression::ShrOverflows { left, right, .. }
+            | Expression::Sub { left, right }
+            | Expression::SubOverflows { left, right, .. } => {
+                left.has_taint(kind).or(right.has_taint(kind))
+            }
+            Expression::BitNot { operand, .. }
+            | Expression::Cast { operand, .. }
+            | Expression::IntrinsicBitVectorUnary { operand, .. }
+            | Expression::IntrinsicFloatingPointUnary { operand, .. }
+            | Expression::LogicalNot { operand }
+            | Expression::Neg { operand }
+            | Expression::Widen { operand, .. } => operand.has_taint(kind),
+            Expression::ConditionalExpression {
+                condition,
+                consequent,
+                alternate,
+            } => condition
+                .conditional_expression(consequent.has_taint(kind), alternate.has_taint(kind)),
+            Expression::Sanitized { operand, kind: k } => {
+                if k == kind {
+                    Rc::new(FALSE)
+                } else {
+                    operand.has_taint(kind)
+                }
+            }
+            Expression::Tainted { operand, kind: k } => {
+                if k == kind {
+                    Rc::new(TRUE)
+                } else {
+                    operand.has_taint(kind)
+                }
+            }
+            Expression::UninterpretedCall { arguments, .. } => arguments
+                .iter()
+                .fold(Rc::new(FALSE), |acc, arg| acc.or(arg.has_taint(kind))),
+            Expression::Variable { path, .. } => {
+                if path.is_rooted_by_parameter() {
+                    // Only the caller knows if the value is tainted.
+                    AbstractValue::make_from(
+                        Expression::HasTaint {
+                            operand: self.clone(),
+                            kind: kind.clone(),
+                        },
+                        2,
+                    )
+                } else {
+                    // The buck stops here and nothing has tainted the value.
+                    Rc::new(FALSE)
+                }
+            }
+        }
+    }
+
     /// Returns true if "self => other" is known at compile time to be true.
     /// Returning false does not imply the implication is false, just that we do not know.
     ///
@@ -1915,6 +2070,33 @@ impl AbstractValueTrait for Rc<AbstractValue> {
         })
     }
 
+    /// Returns an element that is self, without any taint of the given kind.
+    #[logfn_inputs(TRACE)]
+    fn remove_taint(&self, kind: Rc<String>) -> Rc<AbstractValue> {
+        match &self.expression {
+            Expression::Bottom
+            | Expression::Top
+            | Expression::CompileTimeConstant(..)
+            | Expression::HeapBlock { .. }
+            | Expression::Reference(..) => self.clone(),
+            Expression::Sanitized { kind: k, .. } if *k == kind => self.clone(),
+            Expression::Tainted { operand, kind: k } => {
+                if *k == kind {
+                    operand.remove_taint(kind)
+                } else {
+                    operand.remove_taint(kind).add_taint(k.clone())
+                }
+            }
+            _ => AbstractValue::make_from(
+                Expression::Sanitized {
+                    operand: self.clone(),
+                    kind,
+                },
+                self.expression_size.saturating_add(1),
+            ),
+        }
+    }
+
     /// Returns an element that is "self << other".
     #[logfn_inputs(TRACE)]
     fn shift_left(&self, other: Rc<AbstractValue>) -> Rc<AbstractValue> {
@@ -2420,6 +2602,37 @@ impl AbstractValueTrait for Rc<AbstractValue> {
             } => left
                 .refine_paths(environment)
                 .sub_overflows(right.refine_paths(environment), result_type.clone()),
+            Expression::HasTaint { operand, kind } => {
+                if let Expression::Reference(path) | Expression::Variable { path, .. } =
+                    &operand.expression
+                {
+                    // The value may be an aggregate whose parts are tainted.
+                    let refined_path = path.refine_paths(environment);
+                    if let Some(has_taint) = environment.has_taint_at(&refined_path, kind) {
+                        return has_taint;
+                    }
+                }
+                operand.refine_paths(environment).has_taint(kind)
+            }
+            Expression::Sanitized { operand, kind } => {
+                operand.refine_paths(environment).remove_taint(kind.clone())
+            }
+            Expression::Tainted { operand, kind } => {
+                operand.refine_paths(environment).add_taint(kind.clone())
+            }
+            Expression::Quantifier {
+                kind,
+                variable,
+                lower_bound,
+                upper_bound,
+                condition,
+            } => AbstractValue::make_quantifier(
+                *kind,
+                variable.clone(),
+                lower_bound.refine_paths(environment),
+                upper_bound.refine_paths(environment),
+                condition.refine_paths(environment),
+            ),
             Expression::UninterpretedCall { .. } => self.clone(),
             Expression::UnknownModelField { path, default } => {
                 if let Some(val) = environment.value_at(&path) {
@@ -2607,6 +2820,37 @@ impl AbstractValueTrait for Rc<AbstractValue> {
                     }
                 }
             }
+            Expression::HasTaint { operand, kind } => {
+                // Simplification is left to refine_paths, which can look at the parts of an
+                // aggregate argument in the environment of the caller.
+                let refined_operand = operand.refine_parameters(arguments, fresh);
+                AbstractValue::make_from(
+                    Expression::HasTaint {
+                        operand: refined_operand.clone(),
+                        kind: kind.clone(),
+                    },
+                    refined_operand.expression_size.saturating_add(1),
+                )
+            }
+            Expression::Sanitized { operand, kind } => operand
+                .refine_parameters(arguments, fresh)
+                .remove_taint(kind.clone()),
+            Expression::Tainted { operand, kind } => operand
+                .refine_parameters(arguments, fresh)
+                .add_taint(kind.clone()),
+            Expression::Quantifier {
+                kind,
+                variable,
+                lower_bound,
+                upper_bound,
+                condition,
+            } => AbstractValue::make_quantifier(
+                *kind,
+                variable.refine_parameters(arguments, fresh),
+                lower_bound.refine_parameters(arguments, fresh),
+                upper_bound.refine_parameters(arguments, fresh),
+                condition.refine_parameters(arguments, fresh),
+            ),
             Expression::Rem { left, right } => left
                 .refine_parameters(arguments, fresh)
                 .remainder(right.refine_parameters(arguments, fresh)),
@@ -2699,7 +2943,7 @@ impl AbstractValueTrait for Rc<AbstractValue> {
     /// the result is simply a clone of this domain.
     ///
     /// This function is performance critical and involves a tricky trade-off: Invoking it
-    /// is expensive, particularly when expressions get large (hence k_limits::MAX_EXPRESSION_SIZE).
+    /// is expensive, particularly when expressions get large (hence k_limits::max_expression_size()).
     /// One reason for this is that expressions are traversed without doing any kind of occurs check,
     /// so expressions that are not large in memory usage (because of sharing) can still be too large
     /// to traverse. Currently there is no really efficient way to add an occurs check, so the
@@ -2713,7 +2957,7 @@ impl AbstractValueTrait for Rc<AbstractValue> {
     fn refine_with(&self, path_condition: &Self, depth: usize) -> Rc<AbstractValue> {
         //do not use false path conditions to refine things
         checked_precondition!(path_condition.as_bool_if_known().is_none());
-        if depth >= k_limits::MAX_REFINE_DEPTH {
+        if depth >= k_limits::max_refine_depth() {
             //todo: perhaps this should go away.
             // right now it deals with the situation where some large expressions have sizes
             // that are not accurately tracked. These really should get fixed.
@@ -2941,6 +3185,28 @@ impl AbstractValueTrait for Rc<AbstractValue> {
                 right.refine_with(path_condition, depth + 1),
                 result_type.clone(),
             ),
+            Expression::HasTaint { operand, kind } => operand
+                .refine_with(path_condition, depth + 1)
+                .has_taint(kind),
+            Expression::Sanitized { operand, kind } => operand
+                .refine_with(path_condition, depth + 1)
+                .remove_taint(kind.clone()),
+            Expression::Tainted { operand, kind } => operand
+                .refine_with(path_condition, depth + 1)
+                .add_taint(kind.clone()),
+            Expression::Quantifier {
+                kind,
+                variable,
+                lower_bound,
+                upper_bound,
+                condition,
+            } => AbstractValue::make_quantifier(
+                *kind,
+                variable.clone(),
+                lower_bound.refine_with(path_condition, depth + 1),
+                upper_bound.refine_with(path_condition, depth + 1),
+                condition.refine_with(path_condition, depth + 1),
+            ),
             Expression::UninterpretedCall { .. } => self.clone(),
             Expression::UnknownModelField { .. } => self.clone(),
             Expression::Variable { var_type, .. } => {
@@ -0,0 +1,101 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Support for recording the diagnostics of a crate in a baseline file, so that later runs can
+// suppress the recorded diagnostics and report only new ones.
+//
+// A diagnostic is identified by a fingerprint that does not depend on absolute source positions,
+// so that edits elsewhere in a file do not cause recorded diagnostics to be reported again.
+
+use log_derive::logfn_inputs;
+use rustc_errors::Diagnostic;
+use rustc_span::source_map::SourceMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+/// Identifies a diagnostic in a way that is stable across unrelated edits to the source.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Fingerprint {
+    /// The summary key of the function whose analysis issued the diagnostic.
+    pub summary_key: String,
+    /// The message of the diagnostic.
+    pub message: String,
+    /// The source text of the primary span of the diagnostic, with white space normalized.
+    pub span_text: String,
+}
+
+impl Fingerprint {
+    /// Computes the fingerprint of a diagnostic issued during the analysis of the function
+    /// with the given summary key.
+    pub fn new(summary_key: &str, diagnostic: &Diagnostic, source_map: &SourceMap) -> Fingerprint {
+        let span_text = diagnostic
+            .span
+            .primary_span()
+            .and_then(|span| source_map.span_to_snippet(span.source_callsite()).ok())
+            .map(|snippet| snippet.split_whitespace().collect::<Vec<_>>().join(" "))
+            .unwrap_or_default();
+        Fingerprint {
+            summary_key: summary_key.to_string(),
+            message: diagnostic.message(),
+            span_text,
+        }
+    }
+}
+
+/// A multiset of fingerprints. If the same fingerprint is recorded more than once, each
+/// recorded instance suppresses one matching diagnostic.
+#[derive(Debug, Default)]
+pub struct Baseline {
+    counts: HashMap<Fingerprint, usize>,
+}
+
+impl Baseline {
+    /// Reads a baseline that was previously written with write_to.
+    pub fn read_from(path: &Path) -> std::io::Result<Baseline> {
+        let reader = BufReader::new(File::open(path)?);
+        let fingerprints: Vec<Fingerprint> = serde_json::from_reader(reader)?;
+        let mut baseline = Baseline::default();
+        for fingerprint in fingerprints {
+            baseline.add(fingerprint);
+        }
+        Ok(baseline)
+    }
+
+    /// Writes the baseline to the file at the given path, replacing any existing content.
+    /// Fingerprints are written in sorted order so that the file can be diffed and checked in.
+    pub fn write_to(&self, path: &Path) -> std::io::Result<()> {
+        let mut fingerprints: Vec<&Fingerprint> = self
+            .counts
+            .iter()
+            .flat_map(|(fingerprint, count)| std::iter::repeat(fingerprint).take(*count))
+            .collect();
+        fingerprints.sort();
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, &fingerprints)?;
+        Ok(())
+    }
+
+    /// Records another instance of the given fingerprint.
+    #[logfn_inputs(TRACE)]
+    pub fn add(&mut self, fingerprint: Fingerprint) {
+        *self.counts.entry(fingerprint).or_insert(0) += 1;
+    }
+
+    /// If an instance of the given fingerprint is recorded in the baseline, removes it and
+    /// returns true, so that the corresponding diagnostic can be suppressed.
+    #[logfn_inputs(TRACE)]
+    pub fn suppress(&mut self, fingerprint: &Fingerprint) -> bool {
+        match self.counts.get_mut(fingerprint) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+}
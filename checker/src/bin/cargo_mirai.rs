@@ -0,0 +1,500 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+// A cargo subcommand that runs MIRAI on the crates of a workspace, so that users need not set up
+// RUSTC_WRAPPER and MIRAI_FLAGS themselves, nor touch source files to force cargo to recompile.
+//
+// The same executable plays two roles. When run by the user, via `cargo mirai`, it cleans the
+// workspace members in a separate target directory and then runs `cargo build` with itself as
+// RUSTC_WRAPPER. When run by cargo as the wrapper, it runs MIRAI on the crates that should be
+// analyzed and the real rustc on all other crates. Each MIRAI run writes its diagnostics to a SARIF
+// log, and once cargo is done the logs are read back, so that the diagnostics of all crates
+// can be reported together and counted.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{exit, Command};
+
+const USAGE: &str = "Runs MIRAI on the crates of the current workspace.
+
+Usage:
+    cargo mirai [options] [cargo build options] [-- MIRAI options]
+
+Options:
+    --dependencies    Also analyze the dependencies of the workspace members.
+    --incremental     Keep the summary store between runs, so that functions that have not
+                      changed since the last run need not be analyzed again.
+    -h, --help        Print this message.
+
+The MIRAI options are passed on to MIRAI as if they were part of MIRAI_FLAGS.
+The exit code is non zero if cargo fails or if MIRAI reports any diagnostics.";
+
+/// If set, cargo-mirai has been invoked by cargo as RUSTC_WRAPPER and should put the SARIF
+/// logs of its MIRAI runs into the directory given by the value.
+const SARIF_DIR_VAR: &str = "CARGO_MIRAI_SARIF_DIR";
+
+/// If set, crates that are not workspace members are analyzed as well.
+const DEPENDENCIES_VAR: &str = "CARGO_MIRAI_DEPENDENCIES";
+
+fn main() {
+    if let Some(sarif_dir) = env::var_os(SARIF_DIR_VAR) {
+        run_as_wrapper(PathBuf::from(sarif_dir));
+    } else {
+        run_cargo();
+    }
+}
+
+/// The arguments of `cargo mirai`, sorted by whom they are meant for.
+#[derive(Debug, Default, Eq, PartialEq)]
+struct Arguments {
+    analyze_dependencies: bool,
+    incremental: bool,
+    help: bool,
+    cargo_args: Vec<String>,
+    mirai_args: Vec<String>,
+}
+
+/// Parses the arguments that follow the path of the executable.
+fn parse_arguments(args: impl Iterator<Item = String>) -> Arguments {
+    let mut args = args.peekable();
+    // Cargo passes the name of the subcommand as the first argument.
+    if args.peek().map(String::as_str) == Some("mirai") {
+        args.next();
+    }
+    let mut arguments = Arguments::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dependencies" => arguments.analyze_dependencies = true,
+            "--incremental" => arguments.incremental = true,
+            "-h" | "--help" => arguments.help = true,
+            "--" => {
+                arguments.mirai_args.extend(args.by_ref());
+            }
+            _ => arguments.cargo_args.push(arg),
+        }
+    }
+    arguments
+}
+
+/// Runs `cargo build` on the workspace with cargo-mirai as the RUSTC_WRAPPER and reports
+/// the diagnostics found by MIRAI.
+fn run_cargo() {
+    let Arguments {
+        analyze_dependencies,
+        incremental,
+        help,
+        cargo_args,
+        mirai_args,
+    } = parse_arguments(env::args().skip(1));
+    if help {
+        println!("{}", USAGE);
+        return;
+    }
+
+    let (members, target_directory) = get_workspace_metadata(&cargo_args);
+    // Use a separate target directory, so that the output of normal builds is not affected and
+    // all crates are compiled with MIR for every function.
+    let target_dir = target_directory.join("mirai");
+    let sarif_dir = target_dir.join("sarif");
+    if sarif_dir.exists() {
+        fs::remove_dir_all(&sarif_dir)
+            .unwrap_or_else(|e| fail(&format!("cannot remove {}: {}", sarif_dir.display(), e)));
+    }
+    fs::create_dir_all(&sarif_dir)
+        .unwrap_or_else(|e| fail(&format!("cannot create {}: {}", sarif_dir.display(), e)));
+
+    // Cargo does not know that the diagnostics of an earlier run might now be different, so force
+    // it to compile the crates that are to be analyzed again.
+    let mut clean = cargo_command();
+    clean.arg("clean").arg("--target-dir").arg(&target_dir);
+    if !analyze_dependencies {
+        for member in members.iter() {
+            clean.arg("--package").arg(member);
+        }
+    }
+    if let Some(manifest_path) = get_manifest_path(&cargo_args) {
+        clean.arg("--manifest-path").arg(manifest_path);
+    }
+    run_to_completion(&mut clean);
+
+    let mirai_flags = add_mirai_args(&env::var("MIRAI_FLAGS").unwrap_or_default(), &mirai_args);
+    let mut build = cargo_command();
+    build
+        .arg("build")
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .args(&cargo_args)
+        .env("RUSTC_WRAPPER", current_exe())
+        .env(SARIF_DIR_VAR, &sarif_dir)
+        .env("MIRAI_FLAGS", mirai_flags);
+    if analyze_dependencies {
+        build.env(DEPENDENCIES_VAR, "1");
+    }
+    if incremental {
+        // The summary store then lives in the target directory, rather than in a temporary one.
+        build.env("MIRAI_SHARE_PERSISTENT_STORE", "1");
+    }
+    let cargo_succeeded = run_to_completion(&mut build);
+
+    let diagnostic_count = report_diagnostics(&sarif_dir);
+    if diagnostic_count > 0 {
+        eprintln!(
+            "cargo-mirai: MIRAI reported {} diagnostic(s)",
+            diagnostic_count
+        );
+    }
+    if !cargo_succeeded || diagnostic_count > 0 {
+        exit(1);
+    }
+}
+
+/// Runs in place of rustc on behalf of cargo. The first argument is the path of rustc and the
+/// remaining arguments are the ones cargo passes to it.
+fn run_as_wrapper(sarif_dir: PathBuf) {
+    let mut args = env::args_os().skip(1);
+    let rustc = args
+        .next()
+        .unwrap_or_else(|| fail("expected the path of rustc as the first argument"));
+    let args: Vec<OsString> = args.collect();
+    let crate_name = get_crate_name(&args);
+    let analyze = should_analyze(
+        crate_name.as_deref(),
+        env::var_os("CARGO_PRIMARY_PACKAGE").is_some(),
+        env::var_os(DEPENDENCIES_VAR).is_some(),
+    );
+    let mut command = if analyze {
+        let sarif_file = sarif_dir.join(format!(
+            "{}-{}.sarif",
+            crate_name.unwrap_or_default(),
+            std::process::id()
+        ));
+        let sarif_flag = format!(
+            "--sarif {}",
+            shellwords::escape(&sarif_file.to_string_lossy())
+        );
+        // Options that follow -- in MIRAI_FLAGS are passed on to rustc, so put this one first.
+        let mirai_flags = join_flags(&sarif_flag, &env::var("MIRAI_FLAGS").unwrap_or_default());
+        let mut command = Command::new(mirai_exe());
+        command.arg(&rustc).env("MIRAI_FLAGS", mirai_flags);
+        command
+    } else {
+        let mut command = Command::new(&rustc);
+        if crate_name.is_some() {
+            // Crates that are not analyzed may still be called by those that are.
+            command.arg("-Z").arg("always-encode-mir");
+        }
+        command
+    };
+    command.args(&args);
+    let status = command
+        .status()
+        .unwrap_or_else(|e| fail(&format!("cannot run {:?}: {}", command, e)));
+    exit(status.code().unwrap_or(1));
+}
+
+/// Returns the value of the --crate-name option among the arguments that cargo passes to rustc.
+fn get_crate_name(rustc_args: &[OsString]) -> Option<String> {
+    rustc_args
+        .iter()
+        .position(|arg| arg == "--crate-name")
+        .and_then(|i| rustc_args.get(i + 1))
+        .and_then(|name| name.to_str())
+        .map(|name| name.to_string())
+}
+
+/// Returns true if MIRAI should analyze the crate with the given name, which is a member of the
+/// workspace if it is a primary package.
+fn should_analyze(
+    crate_name: Option<&str>,
+    is_primary_package: bool,
+    analyze_dependencies: bool,
+) -> bool {
+    match crate_name {
+        // Cargo probes rustc with a crate named ___, and build scripts are not worth analyzing.
+        Some(name) if name != "___" && name != "build_script_build" => {
+            is_primary_package || analyze_dependencies
+        }
+        _ => false,
+    }
+}
+
+/// Returns the names of the workspace members and the target directory of the workspace.
+fn get_workspace_metadata(cargo_args: &[String]) -> (Vec<String>, PathBuf) {
+    let mut command = cargo_command();
+    command
+        .arg("metadata")
+        .arg("--no-deps")
+        .arg("--format-version")
+        .arg("1");
+    if let Some(manifest_path) = get_manifest_path(cargo_args) {
+        command.arg("--manifest-path").arg(manifest_path);
+    }
+    let output = command
+        .output()
+        .unwrap_or_else(|e| fail(&format!("cannot run cargo metadata: {}", e)));
+    if !output.status.success() {
+        fail(&format!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .unwrap_or_else(|e| fail(&format!("cannot parse the output of cargo metadata: {}", e)));
+    // With --no-deps, the packages are just the workspace members.
+    let members = metadata["packages"]
+        .as_array()
+        .map(|packages| {
+            packages
+                .iter()
+                .filter_map(|package| package["name"].as_str())
+                .map(|name| name.to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    let target_directory = metadata["target_directory"]
+        .as_str()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| fail("cargo metadata did not provide a target directory"));
+    (members, target_directory)
+}
+
+/// Returns the value of the --manifest-path option, if it is among the cargo arguments.
+fn get_manifest_path(cargo_args: &[String]) -> Option<String> {
+    cargo_args.iter().enumerate().find_map(|(i, arg)| {
+        if arg == "--manifest-path" {
+            cargo_args.get(i + 1).cloned()
+        } else if arg.starts_with("--manifest-path=") {
+            Some(arg["--manifest-path=".len()..].to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Prints the results in the SARIF logs written by the MIRAI runs and returns their number.
+fn report_diagnostics(sarif_dir: &Path) -> usize {
+    let mut sarif_files: Vec<PathBuf> = fs::read_dir(sarif_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().map_or(false, |ext| ext == "sarif"))
+                .collect()
+        })
+        .unwrap_or_default();
+    sarif_files.sort();
+    let mut count = 0;
+    for sarif_file in sarif_files.iter() {
+        let log: serde_json::Value = match fs::read_to_string(sarif_file)
+            .map_err(|e| e.to_string())
+            .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+        {
+            Ok(log) => log,
+            Err(e) => {
+                eprintln!("cargo-mirai: cannot read {}: {}", sarif_file.display(), e);
+                continue;
+            }
+        };
+        let results = log["runs"][0]["results"].as_array();
+        for result in results.into_iter().flatten() {
+            count += 1;
+            eprintln!(
+                "{}: {}",
+                result["level"].as_str().unwrap_or("warning"),
+                result["message"]["text"].as_str().unwrap_or_default()
+            );
+            if let Some(location) = format_location(&result["locations"][0]) {
+                eprintln!("  --> {}", location);
+            }
+            for related_location in result["relatedLocations"].as_array().into_iter().flatten() {
+                let message = related_location["message"]["text"]
+                    .as_str()
+                    .unwrap_or_default();
+                match format_location(related_location) {
+                    Some(location) => eprintln!("  = note: {} at {}", message, location),
+                    None => eprintln!("  = note: {}", message),
+                }
+            }
+            eprintln!();
+        }
+    }
+    count
+}
+
+/// Formats the physical location of a SARIF location as file:line:column.
+fn format_location(location: &serde_json::Value) -> Option<String> {
+    let physical_location = &location["physicalLocation"];
+    let uri = physical_location["artifactLocation"]["uri"].as_str()?;
+    let region = &physical_location["region"];
+    Some(format!(
+        "{}:{}:{}",
+        uri,
+        region["startLine"].as_u64().unwrap_or(0),
+        region["startColumn"].as_u64().unwrap_or(0)
+    ))
+}
+
+/// Adds the MIRAI options given on the command line to the given MIRAI_FLAGS, quoting them
+/// as necessary.
+fn add_mirai_args(mirai_flags: &str, mirai_args: &[String]) -> String {
+    if mirai_args.is_empty() {
+        return mirai_flags.to_string();
+    }
+    let mirai_args: Vec<&str> = mirai_args.iter().map(String::as_str).collect();
+    join_flags(mirai_flags, &shellwords::join(&mirai_args))
+}
+
+/// Joins two strings of MIRAI flags, either of which may be empty.
+fn join_flags(first: &str, second: &str) -> String {
+    if first.is_empty() {
+        second.to_string()
+    } else if second.is_empty() {
+        first.to_string()
+    } else {
+        format!("{} {}", first, second)
+    }
+}
+
+/// Returns a command that runs the cargo that invoked this subcommand.
+fn cargo_command() -> Command {
+    Command::new(env::var_os("CARGO").unwrap_or_else(|| OsString::from("cargo")))
+}
+
+fn current_exe() -> PathBuf {
+    env::current_exe().unwrap_or_else(|e| fail(&format!("cannot find cargo-mirai: {}", e)))
+}
+
+/// Returns the path of the mirai executable, which is expected to be installed next to this one.
+fn mirai_exe() -> PathBuf {
+    let mirai = current_exe().with_file_name(format!("mirai{}", env::consts::EXE_SUFFIX));
+    if mirai.exists() {
+        mirai
+    } else {
+        PathBuf::from("mirai")
+    }
+}
+
+/// Runs the command and returns true if it succeeded.
+fn run_to_completion(command: &mut Command) -> bool {
+    command
+        .status()
+        .unwrap_or_else(|e| fail(&format!("cannot run {:?}: {}", command, e)))
+        .success()
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("cargo-mirai: {}", message);
+    exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Arguments {
+        parse_arguments(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn arguments_are_split_between_cargo_and_mirai() {
+        let arguments = parse(&[
+            "mirai",
+            "--release",
+            "--incremental",
+            "-p",
+            "foo",
+            "--",
+            "--diag=paranoid",
+            "--single_func",
+            "foo.bar",
+        ]);
+        assert_eq!(
+            arguments,
+            Arguments {
+                analyze_dependencies: false,
+                incremental: true,
+                help: false,
+                cargo_args: strings(&["--release", "-p", "foo"]),
+                mirai_args: strings(&["--diag=paranoid", "--single_func", "foo.bar"]),
+            }
+        );
+    }
+
+    #[test]
+    fn subcommand_name_is_optional() {
+        assert_eq!(
+            parse(&["--dependencies"]),
+            parse(&["mirai", "--dependencies"])
+        );
+        assert!(parse(&["--dependencies"]).analyze_dependencies);
+        assert!(parse(&["-h"]).help);
+        assert!(parse(&["mirai", "--help"]).help);
+    }
+
+    #[test]
+    fn options_after_the_separator_are_not_interpreted() {
+        let arguments = parse(&["mirai", "--", "--incremental", "--", "-h"]);
+        assert!(!arguments.incremental);
+        assert!(!arguments.help);
+        assert!(arguments.cargo_args.is_empty());
+        assert_eq!(
+            arguments.mirai_args,
+            strings(&["--incremental", "--", "-h"])
+        );
+    }
+
+    #[test]
+    fn mirai_args_are_added_to_mirai_flags() {
+        assert_eq!(add_mirai_args("", &[]), "");
+        assert_eq!(add_mirai_args("--diag=strict", &[]), "--diag=strict");
+        assert_eq!(
+            add_mirai_args("", &strings(&["--single_func", "foo.bar"])),
+            "--single_func foo.bar"
+        );
+        assert_eq!(
+            add_mirai_args("--diag=strict", &strings(&["--sarif", "my file.sarif"])),
+            "--diag=strict --sarif my\\ file.sarif"
+        );
+    }
+
+    #[test]
+    fn manifest_path_is_found_in_either_form() {
+        assert_eq!(get_manifest_path(&strings(&["--release"])), None);
+        assert_eq!(
+            get_manifest_path(&strings(&["--manifest-path", "a/Cargo.toml"])),
+            Some(String::from("a/Cargo.toml"))
+        );
+        assert_eq!(
+            get_manifest_path(&strings(&["--manifest-path=b/Cargo.toml", "--release"])),
+            Some(String::from("b/Cargo.toml"))
+        );
+    }
+
+    #[test]
+    fn only_workspace_members_are_analyzed_by_default() {
+        let args: Vec<OsString> = ["--crate-name", "foo", "--edition=2018", "src/lib.rs"]
+            .iter()
+            .map(OsString::from)
+            .collect();
+        let crate_name = get_crate_name(&args);
+        assert_eq!(crate_name.as_deref(), Some("foo"));
+        assert!(should_analyze(crate_name.as_deref(), true, false));
+        assert!(!should_analyze(crate_name.as_deref(), false, false));
+        assert!(should_analyze(crate_name.as_deref(), false, true));
+    }
+
+    #[test]
+    fn probes_and_build_scripts_are_not_analyzed() {
+        assert_eq!(get_crate_name(&[OsString::from("-vV")]), None);
+        assert!(!should_analyze(None, true, true));
+        assert!(!should_analyze(Some("___"), true, true));
+        assert!(!should_analyze(Some("build_script_build"), true, true));
+    }
+}
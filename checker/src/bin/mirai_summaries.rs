@@ -0,0 +1,195 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+// A tool for inspecting the persistent summary store that MIRAI creates while analyzing crates.
+// Summaries are stored as binary blobs, so without this tool it is hard to find out why, for
+// example, a caller was given an unexpected precondition.
+#![feature(rustc_private)]
+
+extern crate mirai;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use mirai::summaries::{Summary, SummaryStore, FORMAT_VERSION};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+fn make_parser<'a>() -> App<'a, 'a> {
+    App::new("mirai-summaries")
+        .version("v1.0.5")
+        .about("Inspects, exports and imports the contents of a MIRAI summary store.")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("store")
+                .long("store")
+                .takes_value(true)
+                .default_value(".")
+                .help("The summary store, or the directory that contains .summary_store.sled."),
+        )
+        .subcommand(
+            SubCommand::with_name("list")
+                .about("Lists the keys of the stored summaries.")
+                .arg(
+                    Arg::with_name("prefix")
+                        .default_value("")
+                        .help("Only list keys that start with this prefix."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("show")
+                .about("Pretty prints the summary stored for a key.")
+                .arg(Arg::with_name("key").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Writes the stored summaries to a JSON file.")
+                .arg(Arg::with_name("file").required(true))
+                .arg(
+                    Arg::with_name("prefix")
+                        .long("prefix")
+                        .takes_value(true)
+                        .default_value("")
+                        .help("Only export summaries with keys that start with this prefix."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Adds the summaries in a JSON file written by export to the store.")
+                .arg(Arg::with_name("file").required(true)),
+        )
+}
+
+fn main() {
+    let matches = make_parser().get_matches();
+    let store_path = get_store_path(matches.value_of("store").unwrap());
+    let result = match matches.subcommand() {
+        ("import", Some(sub_matches)) => open_store(&store_path, true)
+            .and_then(|store| import(&store, sub_matches.value_of("file").unwrap())),
+        (command, Some(sub_matches)) => {
+            open_store(&store_path, false).and_then(|store| run_query(&store, command, sub_matches))
+        }
+        _ => unreachable!(),
+    };
+    if let Err(message) = result {
+        eprintln!("mirai-summaries: {}", message);
+        std::process::exit(1);
+    }
+}
+
+/// If the given path is not itself a Sled database, assume that it is the directory that
+/// MIRAI used as the location of its summary store.
+fn get_store_path(path: &str) -> PathBuf {
+    let path = PathBuf::from(path);
+    if path.extension().map_or(false, |e| e == "sled") {
+        path
+    } else {
+        path.join(".summary_store.sled")
+    }
+}
+
+fn open_store(store_path: &Path, create_if_missing: bool) -> Result<SummaryStore, String> {
+    if !create_if_missing && !store_path.exists() {
+        return Err(format!("{} does not exist", store_path.display()));
+    }
+    SummaryStore::open(store_path)
+        .map_err(|e| format!("cannot open {}: {}", store_path.display(), e))
+}
+
+fn run_query(store: &SummaryStore, command: &str, matches: &ArgMatches<'_>) -> Result<(), String> {
+    match command {
+        "list" => {
+            for key in keys_with_prefix(store, matches.value_of("prefix").unwrap())? {
+                println!("{}", key);
+            }
+            Ok(())
+        }
+        "show" => {
+            let key = matches.value_of("key").unwrap();
+            let summary = store
+                .get(key)?
+                .ok_or_else(|| format!("there is no summary for {}", key))?;
+            print_summary(key, &summary);
+            Ok(())
+        }
+        "export" => export(
+            store,
+            matches.value_of("file").unwrap(),
+            matches.value_of("prefix").unwrap(),
+        ),
+        _ => unreachable!(),
+    }
+}
+
+fn keys_with_prefix(store: &SummaryStore, prefix: &str) -> Result<Vec<String>, String> {
+    store
+        .keys_with_prefix(prefix)
+        .map_err(|e| format!("cannot read store: {}", e))
+}
+
+fn print_summary(key: &str, summary: &Summary) {
+    println!("{}", key);
+    println!("  is_computed: {}", summary.is_computed);
+    println!("  is_angelic: {}", summary.is_angelic);
+    println!("  preconditions:");
+    for precondition in summary.preconditions.iter() {
+        println!("    message: {}", precondition.message);
+        if let Some(provenance) = &precondition.provenance {
+            println!("      provenance: {}", provenance);
+        }
+        println!("      condition: {:?}", precondition.condition);
+    }
+    println!("  side_effects:");
+    for (path, value) in summary.side_effects.iter() {
+        println!("    {:?} = {:?}", path, value);
+    }
+    println!("  post_condition: {:?}", summary.post_condition);
+    println!("  unwind_condition: {:?}", summary.unwind_condition);
+    println!("  unwind_side_effects:");
+    for (path, value) in summary.unwind_side_effects.iter() {
+        println!("    {:?} = {:?}", path, value);
+    }
+    println!("  modified_paths:");
+    for path in summary.modified_paths.iter() {
+        println!("    {:?}", path);
+    }
+}
+
+fn export(store: &SummaryStore, file_name: &str, prefix: &str) -> Result<(), String> {
+    let mut summaries = BTreeMap::new();
+    for key in keys_with_prefix(store, prefix)? {
+        if let Some(summary) = store.get(&key)? {
+            summaries.insert(key, summary);
+        }
+    }
+    let file =
+        File::create(file_name).map_err(|e| format!("cannot create {}: {}", file_name, e))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &summaries)
+        .map_err(|e| format!("cannot write {}: {}", file_name, e))?;
+    println!("exported {} summaries to {}", summaries.len(), file_name);
+    Ok(())
+}
+
+fn import(store: &SummaryStore, file_name: &str) -> Result<(), String> {
+    if store.format_version() != Some(FORMAT_VERSION) {
+        // MIRAI would discard the imported summaries along with the stale ones.
+        return Err(String::from(
+            "the store was written by a version of MIRAI with another format",
+        ));
+    }
+    let file = File::open(file_name).map_err(|e| format!("cannot open {}: {}", file_name, e))?;
+    let summaries: BTreeMap<String, Summary> = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| format!("cannot read {}: {}", file_name, e))?;
+    for (key, summary) in summaries.iter() {
+        store
+            .insert(key, summary)
+            .map_err(|e| format!("cannot store summary for {}: {}", key, e))?;
+    }
+    store
+        .flush()
+        .map_err(|e| format!("cannot write store: {}", e))?;
+    println!("imported {} summaries from {}", summaries.len(), file_name);
+    Ok(())
+}
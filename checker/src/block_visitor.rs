@@ -9,11 +9,14 @@ use crate::constant_domain::{ConstantDomain, FunctionReference};
 use crate::environment::Environment;
 use crate::expression::{Expression, ExpressionType};
 use crate::k_limits;
+use crate::k_limits::FUEL_FOR_SOLVER_QUERY;
 use crate::known_names::KnownNames;
 use crate::options::DiagLevel;
 use crate::path::PathRefinement;
 use crate::path::{Path, PathEnum, PathSelector};
+use crate::sarif;
 use crate::smt_solver::SmtResult;
+use crate::statistics::AngelicReason;
 use crate::summaries::{Precondition, Summary};
 use crate::utils;
 use crate::{abstract_value, type_visitor};
@@ -23,6 +26,7 @@ use crate::call_visitor::CallVisitor;
 use log_derive::*;
 use mirai_annotations::*;
 use rpds::HashTrieMap;
+use rustc_errors::DiagnosticBuilder;
 use rustc_hir::def_id::DefId;
 use rustc_middle::mir;
 use rustc_middle::mir::interpret::{ConstValue, Scalar};
@@ -92,10 +96,39 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
         }
     }
 
+    /// Visits the statements of the given block, but not its terminator, and then returns the
+    /// value of the given operand of the terminator.
+    #[logfn_inputs(TRACE)]
+    pub fn visit_statements_then_terminator_operand(
+        &mut self,
+        bb: mir::BasicBlock,
+        operand: &mir::Operand<'tcx>,
+    ) -> Rc<AbstractValue> {
+        let mir::BasicBlockData {
+            ref statements,
+            ref terminator,
+            ..
+        } = &self.bv.mir[bb];
+        let mut location = bb.start_location();
+        while location.statement_index < statements.len() {
+            self.visit_statement(location, &statements[location.statement_index]);
+            location.statement_index += 1;
+        }
+        self.bv.current_location = location;
+        if let Some(mir::Terminator {
+            ref source_info, ..
+        }) = *terminator
+        {
+            self.bv.current_span = source_info.span;
+        }
+        self.visit_operand(operand)
+    }
+
     /// Calls a specialized visitor for each kind of statement.
     #[logfn_inputs(TRACE)]
     fn visit_statement(&mut self, location: mir::Location, statement: &mir::Statement<'tcx>) {
         trace!("env {:?}", self.bv.current_environment);
+        self.bv.consume_fuel(1);
         self.bv.current_location = location;
         let mir::Statement { kind, source_info } = statement;
         self.bv.current_span = source_info.span;
@@ -163,8 +196,8 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
             span,
             "Inline assembly code cannot be analyzed by MIRAI. Unsoundly ignoring this.",
         );
-        self.bv.emit_diagnostic(err);
-        self.bv.assume_function_is_angelic = true;
+        self.bv.emit_diagnostic(err, sarif::Rule::InlineAssembly);
+        self.bv.make_angelic(AngelicReason::InlineAssembly);
     }
 
     /// Retag references in the given place, ensuring they got fresh tags.  This is
@@ -190,6 +223,7 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
         kind: &mir::TerminatorKind<'tcx>,
     ) {
         trace!("env {:?}", self.bv.current_environment);
+        self.bv.consume_fuel(1);
         self.bv.current_location = location;
         self.bv.current_span = source_info.span;
         match kind {
@@ -321,11 +355,53 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
                         Some(self.bv.current_environment.entry_condition.clone());
                 }
             }
+            self.check_struct_invariant_on_return();
+            self.apply_typestate_transition_on_return();
             // When the summary is prepared the current environment might be different, so remember this one.
             self.bv.exit_environment = Some(self.bv.current_environment.clone());
         }
     }
 
+    /// If the function being analyzed is a method or constructor of a type with an invariant (see
+    /// the invariant! macro), checks that the invariant holds on return and adds it to the post
+    /// condition, so that callers can rely on it. This includes methods that take &self, which
+    /// can still change the value through interior mutability.
+    #[logfn_inputs(TRACE)]
+    fn check_struct_invariant_on_return(&mut self) {
+        precondition!(self.bv.check_for_errors);
+        if let Some(invariant) = self.bv.struct_invariant.clone() {
+            let condition = invariant
+                .condition
+                .refine_paths(&self.bv.current_environment);
+            let message = Rc::new(format!(
+                "violation of the invariant of {}",
+                invariant.type_name
+            ));
+            // Report the violation at the function, since the return has no good location.
+            let saved_span = self.bv.current_span;
+            self.bv.current_span = self.bv.tcx.def_span(self.bv.def_id);
+            self.check_condition(&condition, message, true);
+            self.bv.current_span = saved_span;
+            // Post conditions are expressed in terms of the state on entry to the function.
+            self.bv.post_condition = Some(match &self.bv.post_condition {
+                Some(post_condition) => post_condition.and(condition),
+                None => condition,
+            });
+        }
+    }
+
+    /// If the function being analyzed is a method that is constrained by a typestate protocol
+    /// (see the typestate! macro), updates the state of the receiver to the one that the
+    /// transition for the method leads to, so that it becomes a side effect of the method.
+    #[logfn_inputs(TRACE)]
+    fn apply_typestate_transition_on_return(&mut self) {
+        if let Some(transition) = self.bv.typestate_transition.clone() {
+            for (path, value) in transition.side_effects.into_iter() {
+                self.bv.current_environment.update_value_at(path, value);
+            }
+        }
+    }
+
     /// Indicates a terminator that can never be reached.
     #[logfn_inputs(TRACE)]
     fn visit_unreachable(&mut self) {
@@ -476,6 +552,7 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
             "pre env {:?}",
             call_visitor.block_visitor.bv.current_environment
         );
+        call_visitor.check_measure_of_recursive_call();
         call_visitor.check_preconditions_if_necessary(&function_summary);
         call_visitor.transfer_and_refine_normal_return_state(&function_summary);
         call_visitor.transfer_and_refine_cleanup_state(&function_summary);
@@ -548,7 +625,7 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
                 // Assume the callee is perfect and assume the caller and all of its callers are perfect
                 // little angels as well. This cuts down on false positives caused by missing post
                 // conditions.
-                self.bv.assume_function_is_angelic = true;
+                self.bv.make_angelic(AngelicReason::UnknownCallee);
             }
             DiagLevel::STRICT => {
                 // Assume the callee is perfect and that the caller does not need to prove any
@@ -561,7 +638,7 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
                         self.bv.current_span,
                         "the called function could not be summarized, all bets are off",
                     );
-                    self.bv.emit_diagnostic(error);
+                    self.bv.emit_diagnostic(error, sarif::Rule::MissingSummary);
                 }
             }
         }
@@ -634,8 +711,15 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
 
     /// Emit a diagnostic to the effect that the current call might violate a the given precondition
     /// of the called function. Use the provenance and spans of the precondition to point out related locations.
+    /// If promotable is true, the precondition would have been promoted to the current function
+    /// if it were not an analysis root.
     #[logfn_inputs(TRACE)]
-    pub fn emit_diagnostic_for_precondition(&mut self, precondition: &Precondition, warn: bool) {
+    pub fn emit_diagnostic_for_precondition(
+        &mut self,
+        precondition: &Precondition,
+        warn: bool,
+        promotable: bool,
+    ) {
         precondition!(self.bv.check_for_errors);
         let mut diagnostic = if warn {
             Rc::new(format!("possible {}", precondition.message))
@@ -664,7 +748,19 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
                 err.span_note(pc_span.clone(), "related location");
             };
         }
-        self.bv.emit_diagnostic(err);
+        if warn {
+            self.add_counterexample_note(&mut err);
+        }
+        if promotable {
+            self.add_promotion_note(&mut err);
+        }
+        // Promoted preconditions of possible failures already have messages that say so.
+        let rule = if diagnostic.starts_with("possible ") {
+            sarif::Rule::PossibleError
+        } else {
+            sarif::Rule::DefiniteError
+        };
+        self.bv.emit_diagnostic(err, rule);
     }
 
     /// Extend the current post condition by the given `cond`. If none was set before,
@@ -689,7 +785,8 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
                         span,
                         "multiple post conditions must be on the same execution path",
                     );
-                    self.bv.emit_diagnostic(warning);
+                    self.bv
+                        .emit_diagnostic(warning, sarif::Rule::InconsistentPostConditions);
                 }
             }
             (_, _) => {
@@ -718,7 +815,8 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
             let message =
                 "this is unreachable, mark it as such by using the verify_unreachable! macro";
             let warning = self.bv.cv.session.struct_span_warn(span, message);
-            self.bv.emit_diagnostic(warning);
+            self.bv
+                .emit_diagnostic(warning, sarif::Rule::UnreachableAnnotation);
             return None;
         }
 
@@ -736,10 +834,11 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
                 .cv
                 .session
                 .struct_span_err(span, "provably false verification condition");
-            self.bv.emit_diagnostic(error);
+            self.bv
+                .emit_diagnostic(error, sarif::Rule::FalseVerificationCondition);
             if !is_post_condition
                 && entry_cond_as_bool.is_none()
-                && self.bv.preconditions.len() < k_limits::MAX_INFERRED_PRECONDITIONS
+                && self.bv.preconditions.len() < k_limits::max_inferred_preconditions()
             {
                 // promote the path as a precondition. I.e. the program is only correct,
                 // albeit badly written, if we never get here.
@@ -761,19 +860,45 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
         // We might get here, or not, and the condition might be false, or not.
         // Give a warning if we don't know all of the callers, or if we run into a k-limit
         if self.function_being_analyzed_is_root()
-            || self.bv.preconditions.len() >= k_limits::MAX_INFERRED_PRECONDITIONS
+            || self.bv.preconditions.len() >= k_limits::max_inferred_preconditions()
         {
             // We expect public functions to have programmer supplied preconditions
             // that preclude any assertions from failing. So, at this stage we get to
             // complain a bit.
             let span = self.bv.current_span;
-            let warning = self.bv.cv.session.struct_span_warn(span, warning.as_str());
-            self.bv.emit_diagnostic(warning);
+            let mut warning = self.bv.cv.session.struct_span_warn(span, warning.as_str());
+            self.add_counterexample_note(&mut warning);
+            self.add_promotion_note(&mut warning);
+            self.bv.emit_diagnostic(warning, sarif::Rule::PossibleError);
         }
 
         Some(warning)
     }
 
+    /// If the SMT solver found values for which the most recently checked condition is false,
+    /// add a note with these values to the given diagnostic.
+    #[logfn_inputs(TRACE)]
+    fn add_counterexample_note(&mut self, diagnostic: &mut DiagnosticBuilder<'compilation>) {
+        if let Some(counterexample) = self.bv.counterexample.take() {
+            diagnostic.note(&format!("counterexample: {}", counterexample));
+        }
+    }
+
+    /// If the function being analyzed is a private function that is an analysis root only because
+    /// of the --private_roots option, a diagnostic for a possible failure would otherwise have
+    /// been promoted to a precondition of its callers. Add a note to such a diagnostic, so that it
+    /// can be told apart from errors that are local to the function.
+    #[logfn_inputs(TRACE)]
+    fn add_promotion_note(&mut self, diagnostic: &mut DiagnosticBuilder<'compilation>) {
+        if self.bv.cv.options.private_roots
+            && self.function_being_analyzed_is_root()
+            && self.bv.preconditions.len() < k_limits::max_inferred_preconditions()
+            && !utils::is_public(self.bv.def_id, self.bv.tcx)
+        {
+            diagnostic.note("would be a precondition of the callers of this private function");
+        }
+    }
+
     /// Returns true if the function being analyzed is an analysis root.
     #[logfn_inputs(TRACE)]
     pub fn function_being_analyzed_is_root(&mut self) -> bool {
@@ -850,7 +975,7 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
                         let error = get_assert_msg_description(msg);
                         let span = self.bv.current_span;
                         let error = self.bv.cv.session.struct_span_warn(span, error);
-                        self.bv.emit_diagnostic(error);
+                        self.bv.emit_diagnostic(error, sarif::Rule::DefiniteError);
                         // No need to push a precondition, the caller can never satisfy it.
                         return;
                     }
@@ -859,14 +984,19 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
                 // At this point, we don't know that this assert is unreachable and we don't know
                 // that the condition is as expected, so we need to warn about it somewhere.
                 if self.function_being_analyzed_is_root()
-                    || self.bv.preconditions.len() >= k_limits::MAX_INFERRED_PRECONDITIONS
+                    || self.bv.preconditions.len() >= k_limits::max_inferred_preconditions()
                 {
                     // Can't make this the caller's problem.
                     let warning = format!("possible {}", get_assert_msg_description(msg));
                     let span = self.bv.current_span;
-                    let warning = self.bv.cv.session.struct_span_warn(span, warning.as_str());
-                    self.bv.emit_diagnostic(warning);
-                    return;
+                    let mut warning = self.bv.cv.session.struct_span_warn(span, warning.as_str());
+                    self.add_counterexample_note(&mut warning);
+                    self.add_promotion_note(&mut warning);
+                    self.bv.emit_diagnostic(warning, sarif::Rule::PossibleError);
+                    // The summary of a root is usually not used by callers, unless it is reused.
+                    if !self.bv.root_summary_is_reused() {
+                        return;
+                    }
                 }
 
                 // Make it the caller's problem by pushing a precondition.
@@ -876,7 +1006,7 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
                 self.bv
                     .preconditions
                     .retain(|pc| pc.spans.last() != Some(&sp));
-                if self.bv.preconditions.len() < k_limits::MAX_INFERRED_PRECONDITIONS {
+                if self.bv.preconditions.len() < k_limits::max_inferred_preconditions() {
                     let expected_cond = if expected {
                         cond_val
                     } else {
@@ -923,6 +1053,7 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
             "entry condition {:?}",
             self.bv.current_environment.entry_condition
         );
+        self.bv.counterexample = None;
         // Check if the condition is always true (or false) if we get here.
         let mut cond_as_bool = cond_val.as_bool_if_known();
         // Check if we can prove that every call to the current function will reach this call site.
@@ -965,6 +1096,7 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
             };
             self.bv.smt_solver.set_backtrack_position();
             self.bv.smt_solver.assert(&smt_expr);
+            self.bv.consume_fuel(FUEL_FOR_SOLVER_QUERY);
             if self.bv.smt_solver.solve() == SmtResult::Unsatisfiable {
                 // The solver can prove that the entry condition is always false.
                 entry_cond_as_bool = Some(false);
@@ -981,8 +1113,10 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
     #[logfn_inputs(TRACE)]
     fn solve_condition(&mut self, cond_val: &Rc<AbstractValue>) -> Option<bool> {
         let ce = &cond_val.expression;
-        let cond_smt_expr = self.bv.smt_solver.get_as_smt_predicate(ce);
-        match self.bv.smt_solver.solve_expression(&cond_smt_expr) {
+        let decision = match self
+            .bv
+            .solve_expression(ce, "can the condition be true", false)
+        {
             SmtResult::Unsatisfiable => {
                 // If we get here, the solver can prove that cond_val is always false.
                 Some(false)
@@ -990,17 +1124,25 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
             SmtResult::Satisfiable => {
                 // We could get here with cond_val being true. Or perhaps not.
                 // So lets see if !cond_val is provably false.
+                // If not, the model gives values for which cond_val is false.
                 let not_cond_expr = &cond_val.logical_not().expression;
-                let smt_expr = self.bv.smt_solver.get_as_smt_predicate(not_cond_expr);
-                if self.bv.smt_solver.solve_expression(&smt_expr) == SmtResult::Unsatisfiable {
-                    // The solver can prove that !cond_val is always false.
-                    Some(true)
-                } else {
-                    None
+                let want_counterexample = self.bv.cv.options.counterexamples;
+                match self.bv.solve_expression(
+                    not_cond_expr,
+                    "can the condition be false",
+                    want_counterexample,
+                ) {
+                    SmtResult::Unsatisfiable => {
+                        // The solver can prove that !cond_val is always false.
+                        Some(true)
+                    }
+                    _ => None,
                 }
             }
             _ => None,
-        }
+        };
+        self.bv.record_smt_decision(decision);
+        decision
     }
 
     /// Calls a specialized visitor for each kind of Rvalue
@@ -2032,6 +2174,26 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
                     })
                     | TyKind::Ref(_, ty, rustc_hir::Mutability::Mut) => match &literal.val {
                         rustc_middle::ty::ConstKind::Value(ConstValue::Scalar(Scalar::Ptr(p))) => {
+                            if let Some(rustc_middle::mir::interpret::GlobalAlloc::Static(def_id)) =
+                                self.bv.tcx.alloc_map.lock().get(p.alloc_id)
+                            {
+                                if utils::is_ghost_static(self.bv.tcx, def_id) {
+                                    // Ghost statics are tracked by name, so that updates of them
+                                    // can become side effects that callers understand.
+                                    let path = Rc::new(
+                                        PathEnum::StaticVariable {
+                                            def_id: Some(def_id),
+                                            summary_cache_key: utils::summary_key_str(
+                                                self.bv.tcx,
+                                                def_id,
+                                            ),
+                                            expression_type: ExpressionType::from(&ty.kind),
+                                        }
+                                        .into(),
+                                    );
+                                    return AbstractValue::make_reference(path);
+                                }
+                            }
                             let summary_cache_key = format!("{:?}", p).into();
                             let expression_type: ExpressionType = ExpressionType::from(&ty.kind);
                             let path = Rc::new(
@@ -2375,7 +2537,7 @@ impl<'block, 'analysis, 'compilation, 'tcx, E>
         let alignment = self.get_u128_const_val((elem_type.bit_length() / 8) as u128);
         let byte_len_value = self.get_u128_const_val(byte_len as u128);
         let array_value = self.bv.get_new_heap_block(byte_len_value, alignment, false);
-        if byte_len > k_limits::MAX_BYTE_ARRAY_LENGTH {
+        if byte_len > k_limits::max_byte_array_length() {
             return array_value;
         }
         let array_path = Path::get_as_path(array_value);
@@ -9,13 +9,20 @@ use crate::constant_domain::ConstantDomain;
 use crate::environment::Environment;
 use crate::expression::{Expression, ExpressionType, LayoutSource};
 use crate::k_limits;
-use crate::options::DiagLevel;
+use crate::k_limits::FUEL_FOR_SOLVER_QUERY;
+use crate::known_names::KnownNames;
+use crate::options::{AnalysisBudget, DiagLevel};
 use crate::path::PathRefinement;
 use crate::path::{Path, PathEnum, PathSelector};
-use crate::smt_solver::{SmtResult, SmtSolver};
+use crate::sarif;
+use crate::smt_dump;
+use crate::smt_dump::{SmtCheck, SmtQuery};
+use crate::smt_solver::{smt_result_cache_key, CachedSmtResult, SmtResult, SmtSolver};
+use crate::statistics::AngelicReason;
 use crate::summaries;
 use crate::summaries::{Precondition, Summary};
 use crate::type_visitor::TypeVisitor;
+use crate::utils;
 use crate::{abstract_value, type_visitor};
 
 use crate::block_visitor::BlockVisitor;
@@ -43,32 +50,73 @@ pub struct BodyVisitor<'analysis, 'compilation, 'tcx, E> {
     pub def_id: DefId,
     pub mir: mir::ReadOnlyBodyAndCache<'analysis, 'tcx>,
     pub smt_solver: &'analysis mut dyn SmtSolver<E>,
-    pub buffered_diagnostics: &'analysis mut Vec<DiagnosticBuilder<'compilation>>,
+    pub buffered_diagnostics: &'analysis mut Vec<(DiagnosticBuilder<'compilation>, sarif::Rule)>,
     pub active_calls: &'analysis mut Vec<DefId>,
 
     pub already_reported_errors_for_call_to: HashSet<Rc<AbstractValue>>,
+    // The reason why the current function was first assumed to be correct, if it was.
+    pub angelic_reason: Option<AngelicReason>,
     // True if the current function cannot be analyzed and hence is just assumed to be correct.
     pub assume_function_is_angelic: bool,
     pub assume_preconditions_of_next_call: bool,
     pub async_fn_summary: Option<Summary>,
     pub check_for_errors: bool,
     pub check_for_unconditional_precondition: bool,
+    // Concrete values that make the most recently checked condition false, if requested and found.
+    pub counterexample: Option<String>,
     pub current_environment: Environment,
     pub current_location: mir::Location,
     pub current_span: rustc_span::Span,
+    // The measure of the decreases! annotation at the start of the current function, if any.
+    pub decreases_measure: Option<Rc<AbstractValue>>,
     pub start_instant: Instant,
     pub exit_environment: Option<Environment>,
+    pub fixpoint_iterations: usize,
+    // The number of analysis steps taken so far, see Options::budget.
+    pub fuel_consumed: u64,
     pub function_name: Rc<String>,
     pub heap_addresses: HashMap<mir::Location, Rc<AbstractValue>>,
+    // The places that the current function declares to modify, see the modifies! macro.
+    pub modified_paths: Vec<Rc<Path>>,
+    // The location of the first modifies! annotation, if any.
+    pub modifies_span: Option<rustc_span::Span>,
     pub post_condition: Option<Rc<AbstractValue>>,
     pub post_condition_block: Option<mir::BasicBlock>,
     pub preconditions: Vec<Precondition>,
+    // The invariant of the type that the current function is a public function of, if any.
+    pub struct_invariant: Option<StructInvariant>,
+    // The transition of the typestate protocol that applies to the current function, if any.
+    pub typestate_transition: Option<TypestateTransition>,
+    // The solver checks made since the last decision, see Options::smt_dump_dir.
+    pub smt_checks: Vec<SmtCheck>,
+    // The solver queries made while visiting the body, see Options::smt_dump_dir.
+    pub smt_queries: Vec<SmtQuery>,
     pub unwind_condition: Option<Rc<AbstractValue>>,
     pub unwind_environment: Environment,
     pub fresh_variable_offset: usize,
     pub type_visitor: TypeVisitor<'analysis, 'tcx>,
 }
 
+/// The invariant of a type (see the invariant! macro), as it applies to a public function of the type.
+#[derive(Clone, Debug)]
+pub struct StructInvariant {
+    /// The name of the type, for use in diagnostics.
+    pub type_name: String,
+    /// The invariant, in terms of the parameters and result of the function.
+    pub condition: Rc<AbstractValue>,
+    /// True if the function takes &self or &mut self, so that the invariant holds on entry.
+    pub assumed_on_entry: bool,
+}
+
+/// The transition of a typestate protocol (see the typestate! macro) that applies to a method.
+#[derive(Clone, Debug)]
+pub struct TypestateTransition {
+    /// Require the receiver to be in one of the states from which the method may be called.
+    pub preconditions: Vec<Precondition>,
+    /// Update the model field of the receiver that tracks its state, in terms of the parameters.
+    pub side_effects: Vec<(Rc<Path>, Rc<AbstractValue>)>,
+}
+
 impl<'analysis, 'compilation, 'tcx, E> Debug for BodyVisitor<'analysis, 'compilation, 'tcx, E> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         "BodyVisitor".fmt(f)
@@ -82,7 +130,7 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
         crate_visitor: &'analysis mut CrateVisitor<'compilation, 'tcx>,
         def_id: DefId,
         smt_solver: &'analysis mut dyn SmtSolver<E>,
-        buffered_diagnostics: &'analysis mut Vec<DiagnosticBuilder<'compilation>>,
+        buffered_diagnostics: &'analysis mut Vec<(DiagnosticBuilder<'compilation>, sarif::Rule)>,
         active_calls: &'analysis mut Vec<DefId>,
     ) -> BodyVisitor<'analysis, 'compilation, 'tcx, E> {
         let function_name = crate_visitor
@@ -101,21 +149,32 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
             active_calls,
 
             already_reported_errors_for_call_to: HashSet::new(),
+            angelic_reason: None,
             assume_function_is_angelic: false,
             assume_preconditions_of_next_call: false,
             async_fn_summary: None,
             check_for_errors: false,
             check_for_unconditional_precondition: false, // logging + new mir code gen breaks this for now
+            counterexample: None,
             current_environment: Environment::default(),
             current_location: mir::Location::START,
             current_span: rustc_span::DUMMY_SP,
+            decreases_measure: None,
             start_instant: Instant::now(),
             exit_environment: None,
+            fixpoint_iterations: 0,
+            fuel_consumed: 0,
             function_name,
             heap_addresses: HashMap::default(),
+            modified_paths: Vec::new(),
+            modifies_span: None,
             post_condition: None,
             post_condition_block: None,
             preconditions: Vec::new(),
+            struct_invariant: None,
+            typestate_transition: None,
+            smt_checks: Vec::new(),
+            smt_queries: Vec::new(),
             unwind_condition: None,
             unwind_environment: Environment::default(),
             fresh_variable_offset: 0,
@@ -127,18 +186,27 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
     #[logfn_inputs(TRACE)]
     fn reset_visitor_state(&mut self) {
         self.already_reported_errors_for_call_to = HashSet::new();
+        self.angelic_reason = None;
         self.assume_function_is_angelic = false;
         self.check_for_errors = false;
         self.check_for_unconditional_precondition = false;
+        self.counterexample = None;
         self.current_environment = Environment::default();
         self.current_location = mir::Location::START;
         self.current_span = rustc_span::DUMMY_SP;
+        self.decreases_measure = None;
         self.start_instant = Instant::now();
         self.exit_environment = None;
+        self.fixpoint_iterations = 0;
+        self.fuel_consumed = 0;
         self.heap_addresses = HashMap::default();
+        self.modified_paths = Vec::new();
+        self.modifies_span = None;
         self.post_condition = None;
         self.post_condition_block = None;
         self.preconditions = Vec::new();
+        self.struct_invariant = None;
+        self.typestate_transition = None;
         self.unwind_condition = None;
         self.unwind_environment = Environment::default();
         self.fresh_variable_offset = 1000;
@@ -154,12 +222,23 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
         function_constant_args: &[(Rc<Path>, Rc<AbstractValue>)],
         parameter_types: &[Ty<'tcx>],
     ) -> Summary {
+        if utils::has_mirai_attribute(self.tcx, self.def_id, "skip") {
+            // The function is excluded from analysis by a #[mirai::skip] attribute, so callers
+            // have to assume that it has no preconditions and that it returns normally.
+            let mut result = Summary::default();
+            result.is_computed = true;
+            result.is_angelic = true;
+            return result;
+        }
         if cfg!(DEBUG) {
             let mut stdout = std::io::stdout();
             stdout.write_fmt(format_args!("{:?}", self.def_id)).unwrap();
             rustc_mir::util::write_mir_pretty(self.tcx, Some(self.def_id), &mut stdout).unwrap();
             info!("{:?}", stdout.flush());
         }
+        if let Some(incremental_analysis) = &mut self.cv.incremental_analysis {
+            incremental_analysis.record_visit(self.active_calls, self.def_id);
+        }
         self.active_calls.push(self.def_id);
         let (mut block_indices, contains_loop) = self.get_sorted_block_indices();
 
@@ -171,6 +250,28 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
         // as well any promoted constants.
         let mut first_state = self.promote_constants();
 
+        // If this is a method of a type with an invariant, the invariant holds on entry.
+        self.struct_invariant = self.get_struct_invariant();
+        if let Some(invariant) = &self.struct_invariant {
+            if invariant.assumed_on_entry {
+                first_state.entry_condition =
+                    first_state.entry_condition.and(invariant.condition.clone());
+            }
+        }
+
+        // If this is a method that is constrained by a typestate protocol, the method requires the
+        // receiver to be in a state from which the method may be called, and can rely on that.
+        self.typestate_transition = self.get_typestate_transition();
+        if let Some(transition) = &self.typestate_transition {
+            for precondition in transition.preconditions.iter() {
+                first_state.entry_condition = first_state
+                    .entry_condition
+                    .and(precondition.condition.clone());
+            }
+            self.preconditions
+                .extend(transition.preconditions.iter().cloned());
+        }
+
         // Add function constants.
         for (path, val) in function_constant_args.iter() {
             TypeVisitor::add_function_constants_reachable_from(
@@ -182,7 +283,7 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
             first_state.value_map = first_state.value_map.insert(path.clone(), val.clone());
         }
 
-        let elapsed_time_in_seconds = self.compute_fixed_point(
+        self.compute_fixed_point(
             &mut block_indices,
             contains_loop,
             &mut in_state,
@@ -191,18 +292,23 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
             &first_state,
         );
 
-        if elapsed_time_in_seconds >= k_limits::MAX_ANALYSIS_TIME_FOR_BODY {
-            self.report_timeout(elapsed_time_in_seconds);
+        if self.analysis_budget_is_exhausted() {
+            self.report_timeout();
         }
 
         if !self.assume_function_is_angelic {
+            let mut loop_warnings = self.check_loop_invariants(&out_state);
+            loop_warnings.append(&mut self.check_loop_measures(&in_state, &out_state));
             // Now traverse the blocks again, doing checks and emitting diagnostics.
             // terminator_state[bb] is now complete for every basic block bb in the body.
-            let elapsed_time_in_seconds =
-                self.check_for_errors(&block_indices, &mut terminator_state);
+            self.check_for_errors(&block_indices, &mut terminator_state);
+            for (span, message, rule) in loop_warnings {
+                let warning = self.cv.session.struct_span_warn(span, message);
+                self.emit_diagnostic(warning, rule);
+            }
             self.active_calls.pop();
-            if elapsed_time_in_seconds >= k_limits::MAX_ANALYSIS_TIME_FOR_BODY {
-                self.report_timeout(elapsed_time_in_seconds);
+            if self.analysis_budget_is_exhausted() {
+                self.report_timeout();
             } else {
                 // Now create a summary of the body that can be in-lined into call sites.
                 if self.async_fn_summary.is_some() {
@@ -210,46 +316,360 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
                     // todo: also translate side-effects, return result and post-condition
                 };
 
-                return summaries::summarize(
+                // The body of a contract function does not compute the new values of the places
+                // it declares to modify, so callers must forget what they know about them.
+                let modified_paths = if utils::is_foreign_contract(self.tcx, self.def_id)
+                    || self.tcx.trait_of_item(self.def_id).is_some()
+                {
+                    self.modified_paths.clone()
+                } else {
+                    vec![]
+                };
+                let summary = summaries::summarize(
                     self.mir.arg_count,
                     self.exit_environment.as_ref(),
                     &self.preconditions,
                     &self.post_condition,
                     self.unwind_condition.clone(),
                     &self.unwind_environment,
+                    &modified_paths,
                     self.tcx,
                 );
+                self.check_modified_paths(&summary);
+                self.write_smt_queries();
+                return summary;
             }
         }
+        self.write_smt_queries();
         let mut result = Summary::default();
         result.is_computed = true; // Otherwise this function keeps getting re-analyzed
         result.is_angelic = true; // Callers have to make possibly false assumptions.
         result
     }
 
-    fn report_timeout(&mut self, elapsed_time_in_seconds: u64) {
+    /// If the function being analyzed has modifies! annotations, checks that the places that it
+    /// modifies through mutable references (or pointers) that are passed to it as arguments are
+    /// all covered by the places listed in the annotations.
+    /// Modifications of statics are not checked, since they are not part of the summary.
+    #[logfn_inputs(TRACE)]
+    fn check_modified_paths(&mut self, summary: &Summary) {
+        precondition!(self.check_for_errors);
+        if let Some(span) = self.modifies_span {
+            let has_unlisted_modification = summary.side_effects.iter().any(|(path, _)| {
+                self.is_modified_through_parameter(path)
+                    && !self
+                        .modified_paths
+                        .iter()
+                        .any(|listed_path| Self::path_covers(listed_path, path))
+            });
+            if has_unlisted_modification {
+                let warning = self.cv.session.struct_span_warn(
+                    span,
+                    "the function may modify places that are not listed by modifies!",
+                );
+                self.emit_diagnostic(warning, sarif::Rule::PossibleError);
+            }
+        }
+    }
+
+    /// True if path is rooted in a parameter that is a mutable reference or pointer, so that
+    /// an update of path is visible to the caller.
+    #[logfn_inputs(TRACE)]
+    fn is_modified_through_parameter(&self, path: &Rc<Path>) -> bool {
+        let mut root = path;
+        while let PathEnum::QualifiedPath { qualifier, .. } = &root.value {
+            root = qualifier;
+        }
+        if root == path {
+            return false;
+        }
+        if let PathEnum::Parameter { ordinal } = &root.value {
+            if *ordinal < self.mir.local_decls.len() {
+                return match &self.mir.local_decls[mir::Local::from(*ordinal)].ty.kind {
+                    TyKind::Ref(_, _, rustc_hir::Mutability::Mut)
+                    | TyKind::RawPtr(rustc_middle::ty::TypeAndMut {
+                        mutbl: rustc_hir::Mutability::Mut,
+                        ..
+                    }) => true,
+                    _ => false,
+                };
+            }
+        }
+        false
+    }
+
+    /// True if path is listed_path, or a place contained in listed_path. If listed_path
+    /// dereferences a pointer, this includes places that are reached via the pointer.
+    #[logfn_inputs(TRACE)]
+    fn path_covers(listed_path: &Rc<Path>, path: &Rc<Path>) -> bool {
+        if listed_path == path || path.is_rooted_by(listed_path) {
+            return true;
+        }
+        if let PathEnum::QualifiedPath {
+            qualifier,
+            selector,
+            ..
+        } = &listed_path.value
+        {
+            if let PathSelector::Deref = selector.as_ref() {
+                return qualifier == path || path.is_rooted_by(qualifier);
+            }
+        }
+        false
+    }
+
+    /// If the function being analyzed is a public function defined in an impl of a type that has
+    /// an invariant (see the invariant! macro), returns the invariant as it applies to the function.
+    /// Only methods that take &self or &mut self, and constructors, i.e. functions without such
+    /// a parameter that return a value of the type, are affected by the invariant.
+    #[logfn_inputs(TRACE)]
+    fn get_struct_invariant(&mut self) -> Option<StructInvariant> {
+        if !utils::is_public(self.def_id, self.tcx) {
+            return None;
+        }
+        let impl_def_id = self.tcx.impl_of_method(self.def_id)?;
+        let adt_def_id = if let TyKind::Adt(def, _) = &self.tcx.type_of(impl_def_id).kind {
+            def.did
+        } else {
+            return None;
+        };
+        let invariant_def_id = utils::get_invariant_function(self.tcx, adt_def_id)?;
+        if invariant_def_id == self.def_id || self.active_calls.contains(&invariant_def_id) {
+            return None;
+        }
+        let is_value_of_type =
+            |ty: Ty<'tcx>| matches!(&ty.kind, TyKind::Adt(def, _) if def.did == adt_def_id);
+        let mut takes_self = false;
+        if self.mir.arg_count > 0 {
+            if let TyKind::Ref(_, ty, _) = &self.mir.local_decls[mir::Local::from(1usize)].ty.kind {
+                takes_self = is_value_of_type(*ty);
+            }
+        }
+        let is_constructor =
+            !takes_self && is_value_of_type(self.mir.local_decls[mir::RETURN_PLACE].ty);
+        if !takes_self && !is_constructor {
+            return None;
+        }
+        let mut condition = self.get_result_of_invariant_function(invariant_def_id)?;
+        if is_constructor {
+            // The invariant function takes a reference to the value as its parameter,
+            // but here the value is the result of the function.
+            let result = (Path::new_result(), Rc::new(abstract_value::TOP));
+            condition = condition.refine_parameters(&[result], 0);
+        }
+        Some(StructInvariant {
+            type_name: self.tcx.def_path_str(adt_def_id),
+            condition,
+            assumed_on_entry: takes_self,
+        })
+    }
+
+    /// Analyzes the body of the function generated by the invariant! macro and returns the value
+    /// that it returns, which is in terms of its parameter.
+    #[logfn_inputs(TRACE)]
+    fn get_result_of_invariant_function(
+        &mut self,
+        invariant_def_id: DefId,
+    ) -> Option<Rc<AbstractValue>> {
+        let summary = self.summarize_annotation_function(invariant_def_id)?;
+        let result_path = Path::new_result();
+        summary
+            .side_effects
+            .iter()
+            .find(|(path, _)| *path == result_path)
+            .map(|(_, value)| value.clone())
+    }
+
+    /// If the function being analyzed is a method defined in an impl of a type that has a typestate
+    /// protocol (see the typestate! macro) and the protocol has a transition for the method,
+    /// returns the transition, which is in terms of the parameters of the method.
+    #[logfn_inputs(TRACE)]
+    fn get_typestate_transition(&mut self) -> Option<TypestateTransition> {
+        let impl_def_id = self.tcx.impl_of_method(self.def_id)?;
+        let adt_def_id = if let TyKind::Adt(def, _) = &self.tcx.type_of(impl_def_id).kind {
+            def.did
+        } else {
+            return None;
+        };
+        let method_name = self.tcx.item_name(self.def_id);
+        let transition_def_id =
+            utils::get_typestate_transition_function(self.tcx, adt_def_id, &method_name.as_str())?;
+        if transition_def_id == self.def_id || self.active_calls.contains(&transition_def_id) {
+            return None;
+        }
+        let summary = self.summarize_annotation_function(transition_def_id)?;
+        Some(TypestateTransition {
+            preconditions: summary.preconditions,
+            side_effects: summary
+                .side_effects
+                .into_iter()
+                .filter(|(path, _)| path.is_rooted_by_parameter())
+                .collect(),
+        })
+    }
+
+    /// Analyzes the body of a function generated by an annotation macro, such as invariant!, and
+    /// returns its summary, unless the summary is angelic.
+    #[logfn_inputs(TRACE)]
+    fn summarize_annotation_function(&mut self, def_id: DefId) -> Option<Summary> {
+        if !self.tcx.is_mir_available(def_id) {
+            return None;
+        }
+        let elapsed_time = self.start_instant.elapsed();
+        let summary = BodyVisitor::new(
+            self.cv,
+            def_id,
+            self.smt_solver,
+            self.buffered_diagnostics,
+            self.active_calls,
+        )
+        .visit_body(&[], &[]);
+        self.start_instant = Instant::now() - elapsed_time;
+        if summary.is_angelic {
+            return None;
+        }
+        Some(summary)
+    }
+
+    fn report_timeout(&mut self) {
         // This body is beyond MIRAI for now
         if self.cv.options.diag_level != DiagLevel::RELAXED {
-            let error = self
-                .cv
-                .session
-                .struct_span_err(self.current_span, "The analysis of this function timed out");
-            self.emit_diagnostic(error);
+            let message = match self.cv.options.budget {
+                AnalysisBudget::Time => "The analysis of this function timed out",
+                AnalysisBudget::Fuel => "The analysis of this function ran out of fuel",
+            };
+            let error = self.cv.session.struct_span_err(self.current_span, message);
+            self.emit_diagnostic(error, sarif::Rule::AnalysisTimeout);
+        }
+        match self.cv.options.budget {
+            AnalysisBudget::Time => warn!(
+                "analysis of {} timed out after {} seconds",
+                self.function_name,
+                self.start_instant.elapsed().as_secs(),
+            ),
+            AnalysisBudget::Fuel => warn!(
+                "analysis of {} ran out of fuel after {} steps",
+                self.function_name, self.fuel_consumed,
+            ),
         }
-        warn!(
-            "analysis of {} timed out after {} seconds",
-            self.function_name, elapsed_time_in_seconds,
-        );
         self.active_calls.pop();
+        let reason = self.budget_exhausted_reason();
+        self.make_angelic(reason);
+    }
+
+    /// Returns true if the analysis of the function body has used up its budget, which is
+    /// wall clock time or fuel, depending on the options.
+    pub fn analysis_budget_is_exhausted(&self) -> bool {
+        match self.cv.options.budget {
+            AnalysisBudget::Time => {
+                self.start_instant.elapsed().as_secs() >= k_limits::max_analysis_time_for_body()
+            }
+            AnalysisBudget::Fuel => self.fuel_consumed >= k_limits::max_fuel_for_body(),
+        }
+    }
+
+    /// The reason to record for assuming the function to be correct once its budget is exhausted.
+    pub fn budget_exhausted_reason(&self) -> AngelicReason {
+        match self.cv.options.budget {
+            AnalysisBudget::Time => AngelicReason::Timeout,
+            AnalysisBudget::Fuel => AngelicReason::OutOfFuel,
+        }
+    }
+
+    /// Counts the given number of steps against the fuel budget of the function body.
+    pub fn consume_fuel(&mut self, steps: u64) {
+        self.fuel_consumed += steps;
+    }
+
+    /// Assume that the function being analyzed is correct, since it cannot be analyzed for
+    /// the given reason. Only the first reason is remembered.
+    pub fn make_angelic(&mut self, reason: AngelicReason) {
         self.assume_function_is_angelic = true;
+        if self.angelic_reason.is_none() {
+            self.angelic_reason = Some(reason);
+        }
+    }
+
+    /// Returns a description of the satisfying assignment found by the last call to the SMT
+    /// solver, restricted to parameters and to local variables that have source names.
+    /// The paths that the solver uses as variable names are mapped back to source names.
+    /// Can only be called after self.smt_solver.solve returned SmtResult::Satisfiable.
+    #[logfn_inputs(TRACE)]
+    pub fn get_counterexample(&self) -> Option<String> {
+        let model = self.smt_solver.get_model_as_string();
+        let assignments: Vec<String> = model
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(2, " -> ");
+                let var_name = parts.next()?.trim().trim_matches('|');
+                let value = parts.next()?.trim();
+                self.get_source_name_for(var_name)
+                    .map(|source_name| format!("{} = {}", source_name, value))
+            })
+            .collect();
+        if assignments.is_empty() {
+            None
+        } else {
+            Some(assignments.join(", "))
+        }
     }
 
-    /// Adds the given diagnostic builder to the buffer.
+    /// Maps a solver variable name, which is the debug string of a path, to the corresponding
+    /// source expression, if the path is rooted in a parameter or named local variable.
+    #[logfn_inputs(TRACE)]
+    fn get_source_name_for(&self, var_name: &str) -> Option<String> {
+        let (root, selectors) = match var_name.find('.') {
+            Some(i) => var_name.split_at(i),
+            None => (var_name, ""),
+        };
+        let ordinal = if root.starts_with("param_") {
+            root["param_".len()..].parse::<usize>().ok()?
+        } else if root.starts_with("local_") {
+            root["local_".len()..].parse::<usize>().ok()?
+        } else {
+            return None;
+        };
+        if ordinal >= self.mir.local_decls.len() {
+            // A local from a callee that leaked into the environment of the caller.
+            return None;
+        }
+        let local = mir::Local::from(ordinal);
+        let source_name = self
+            .mir
+            .var_debug_info
+            .iter()
+            .find(|info| info.place.local == local && info.place.projection.is_empty())
+            .map(|info| info.name.to_string());
+        match source_name {
+            Some(name) => Some(format!("{}{}", name, selectors)),
+            None if ordinal <= self.mir.arg_count => Some(var_name.to_string()),
+            None => None,
+        }
+    }
+
+    /// Adds the given diagnostic builder to the buffer, along with the rule it is an instance of.
     /// Buffering diagnostics gives us the chance to sort them before printing them out,
     /// which is desirable for tools that compare the diagnostics from one run of MIRAI with another.
     #[logfn_inputs(TRACE)]
-    pub fn emit_diagnostic(&mut self, mut diagnostic_builder: DiagnosticBuilder<'compilation>) {
+    pub fn emit_diagnostic(
+        &mut self,
+        mut diagnostic_builder: DiagnosticBuilder<'compilation>,
+        rule: sarif::Rule,
+    ) {
         precondition!(self.check_for_errors);
+        // Do not emit diagnostics that the user has suppressed with #[mirai::trusted] or
+        // #[mirai::allow(..)] attributes on the function or on the items that enclose it.
+        if utils::has_mirai_attribute(self.tcx, self.def_id, "trusted") {
+            diagnostic_builder.cancel();
+            return;
+        }
+        if utils::get_allowed_diagnostic_kinds(self.tcx, self.def_id)
+            .iter()
+            .any(|kind| kind == rule.id())
+        {
+            diagnostic_builder.cancel();
+            return;
+        }
         // Do not emit diagnostics for code generated by derive macros since it is currently
         // unlikely that the end user of the diagnostic will be able to anything about it.
         use rustc_span::hygiene::{ExpnData, ExpnKind, MacroKind};
@@ -264,7 +684,7 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
                 return;
             }
         }
-        self.buffered_diagnostics.push(diagnostic_builder);
+        self.buffered_diagnostics.push((diagnostic_builder, rule));
     }
 
     pub fn get_i128_const_val(&mut self, val: i128) -> Rc<AbstractValue> {
@@ -330,6 +750,7 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
                 .as_bool_if_known()
                 .is_none()
             {
+                self.consume_fuel(1);
                 local_val.refine_with(&self.current_environment.entry_condition, 0)
             } else {
                 local_val
@@ -344,7 +765,7 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
             } = &path.value
             {
                 self.lookup_static(&path, *def_id, summary_cache_key, expression_type)
-            } else if path.path_length() < k_limits::MAX_PATH_LENGTH {
+            } else if path.path_length() < k_limits::max_path_length() {
                 let mut result = TypeVisitor::try_lookup_fat_pointer_ptr(
                     &self.current_environment,
                     &path,
@@ -456,9 +877,28 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
         summary_cache_key: &Rc<String>,
         expression_type: &ExpressionType,
     ) -> Rc<AbstractValue> {
+        if def_id.map_or(false, |def_id| utils::is_ghost_static(self.tcx, def_id)) {
+            // Code that ran before the current function may have updated the ghost static, so its
+            // value is unknown on entry. Callers refine it with the values that they know about.
+            let result = AbstractValue::make_from(
+                Expression::Variable {
+                    path: path.clone(),
+                    var_type: expression_type.clone(),
+                },
+                1,
+            );
+            if expression_type.is_primitive() {
+                self.current_environment
+                    .update_value_at(path.clone(), result.clone());
+            }
+            return result;
+        }
         let mut block_visitor;
         let summary;
         let summary = if let Some(def_id) = def_id {
+            if let Some(incremental_analysis) = &mut self.cv.incremental_analysis {
+                incremental_analysis.record_local_use(self.active_calls, def_id);
+            }
             let generic_args = self.cv.substs_cache.get(&def_id).cloned();
             let callee_generic_argument_map = if let Some(generic_args) = generic_args {
                 self.type_visitor
@@ -508,6 +948,14 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
                 .cv
                 .summary_cache
                 .get_persistent_summary_for(summary_cache_key);
+            if let Some(incremental_analysis) = &mut self.cv.incremental_analysis {
+                incremental_analysis.record_stored_summary_use(
+                    self.active_calls,
+                    summary_cache_key,
+                    &Rc::new(String::new()),
+                    &summary,
+                );
+            }
             &summary
         };
         let side_effects = summary.side_effects.clone();
@@ -677,7 +1125,7 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
         mut out_state: &mut HashMap<mir::BasicBlock, Environment>,
         mut terminator_state: &mut HashMap<mir::BasicBlock, Environment>,
         first_state: &Environment,
-    ) -> u64 {
+    ) {
         let mut iteration_count = 0;
         let mut changed = true;
         while changed {
@@ -694,11 +1142,12 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
                 break;
             }
             check_for_early_break!(self);
-            if iteration_count > k_limits::MAX_FIXPOINT_ITERATIONS {
+            if iteration_count > k_limits::max_fixpoint_iterations() {
                 break;
             }
             iteration_count += 1;
         }
+        self.fixpoint_iterations = iteration_count;
         if iteration_count > 10 {
             warn!(
                 "Fixed point loop took {} iterations for {}.",
@@ -711,7 +1160,6 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
                 self.function_name
             );
         }
-        self.start_instant.elapsed().as_secs()
     }
 
     #[logfn_inputs(TRACE)]
@@ -719,7 +1167,7 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
         &mut self,
         block_indices: &[mir::BasicBlock],
         terminator_state: &mut HashMap<mir::BasicBlock, Environment>,
-    ) -> u64 {
+    ) {
         self.check_for_errors = true;
         for bb in block_indices.iter() {
             let t_state = (&terminator_state[bb]).clone();
@@ -732,7 +1180,241 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
             // caller. We model this as a false post condition.
             self.post_condition = Some(Rc::new(abstract_value::FALSE));
         }
-        self.start_instant.elapsed().as_secs()
+    }
+
+    /// Checks that the condition of every loop_invariant! call holds on entry to the loop and
+    /// after every iteration of the loop. The fixed point computation assumes the condition at
+    /// the call, so the condition is known to hold at the start of an iteration.
+    /// Returns the warnings to emit once diagnostics are being emitted.
+    #[logfn_inputs(TRACE)]
+    fn check_loop_invariants(
+        &mut self,
+        out_state: &HashMap<mir::BasicBlock, Environment>,
+    ) -> Vec<(rustc_span::Span, &'static str, sarif::Rule)> {
+        let mut warnings = Vec::new();
+        for (bb, condition, span) in self.get_calls_to(KnownNames::MiraiLoopInvariant) {
+            let blocks = if let Some(blocks) = self.get_blocks_from_loop_header_to(bb) {
+                blocks
+            } else {
+                warnings.push((
+                    span,
+                    "a loop invariant must be at the start of a loop body",
+                    sarif::Rule::InvalidAnnotation,
+                ));
+                continue;
+            };
+            let header = blocks[0];
+            let dominators = self.mir.dominators();
+            let predecessors: Vec<mir::BasicBlock> = self.mir.predecessors_for(header).to_vec();
+            let mut may_fail_on_entry = false;
+            let mut may_not_be_inductive = false;
+            for pred_bb in predecessors {
+                let mut state = out_state[&pred_bb].clone();
+                if let Some(exit_condition) = state.exit_conditions.get(&header) {
+                    state.entry_condition = exit_condition.clone();
+                } else {
+                    continue;
+                }
+                self.current_environment = state;
+                if let Some(value) = self.get_operand_value_at_end_of(&blocks, &condition) {
+                    let (cond_as_bool, entry_cond_as_bool) =
+                        self.check_condition_value_and_reachability(&value);
+                    if entry_cond_as_bool.unwrap_or(true) && !cond_as_bool.unwrap_or(false) {
+                        if dominators.is_dominated_by(pred_bb, header) {
+                            // pred_bb is the end of an iteration of the loop
+                            may_not_be_inductive = true;
+                        } else {
+                            may_fail_on_entry = true;
+                        }
+                    }
+                }
+            }
+            if may_fail_on_entry {
+                warnings.push((
+                    span,
+                    "possible violation of the loop invariant on entry to the loop",
+                    sarif::Rule::PossibleError,
+                ));
+            }
+            if may_not_be_inductive {
+                warnings.push((
+                    span,
+                    "possible violation of the loop invariant after an iteration, it may not be inductive",
+                    sarif::Rule::PossibleError,
+                ));
+            }
+        }
+        warnings
+    }
+
+    /// Checks that the measure of every decreases! call at the start of a loop body is smaller
+    /// after every iteration of the loop than it was at the start of the iteration, and that it
+    /// does not become negative. The measures of decreases! calls that are not in a loop are the
+    /// measures of recursive calls, which are checked by CallVisitor.
+    /// Returns the warnings to emit once diagnostics are being emitted.
+    #[logfn_inputs(TRACE)]
+    fn check_loop_measures(
+        &mut self,
+        in_state: &HashMap<mir::BasicBlock, Environment>,
+        out_state: &HashMap<mir::BasicBlock, Environment>,
+    ) -> Vec<(rustc_span::Span, &'static str, sarif::Rule)> {
+        let mut warnings = Vec::new();
+        for (bb, measure, span) in self.get_calls_to(KnownNames::MiraiDecreases) {
+            let blocks = if let Some(blocks) = self.get_blocks_from_loop_header_to(bb) {
+                blocks
+            } else {
+                continue;
+            };
+            let header = blocks[0];
+            // The widened state at the loop header is the state at the start of any iteration,
+            // and the states of the back edges are computed from it, so the measures computed
+            // from these states share the values of the variables that the loop modifies.
+            self.current_environment = in_state[&header].clone();
+            let previous_measure =
+                if let Some(value) = self.get_operand_value_at_end_of(&blocks, &measure) {
+                    value
+                } else {
+                    continue;
+                };
+            let dominators = self.mir.dominators();
+            let predecessors: Vec<mir::BasicBlock> = self.mir.predecessors_for(header).to_vec();
+            for pred_bb in predecessors {
+                if !dominators.is_dominated_by(pred_bb, header) {
+                    // pred_bb enters the loop, rather than ending an iteration of it.
+                    continue;
+                }
+                let mut state = out_state[&pred_bb].clone();
+                if let Some(exit_condition) = state.exit_conditions.get(&header) {
+                    state.entry_condition = exit_condition.clone();
+                } else {
+                    continue;
+                }
+                self.current_environment = state;
+                if let Some(next_measure) = self.get_operand_value_at_end_of(&blocks, &measure) {
+                    if let Some(message) =
+                        self.check_decreasing_measure(&next_measure, &previous_measure)
+                    {
+                        warnings.push((span, message, sarif::Rule::PossibleError));
+                        break;
+                    }
+                }
+            }
+        }
+        warnings
+    }
+
+    /// Checks that the given measure is smaller than the previous measure and is not negative,
+    /// given the current environment. Returns the message of the warning to issue if that is
+    /// not known to be the case.
+    #[logfn_inputs(TRACE)]
+    pub fn check_decreasing_measure(
+        &mut self,
+        measure: &Rc<AbstractValue>,
+        previous_measure: &Rc<AbstractValue>,
+    ) -> Option<&'static str> {
+        let decreases = measure.less_than(previous_measure.clone());
+        let (decreases_as_bool, entry_cond_as_bool) =
+            self.check_condition_value_and_reachability(&decreases);
+        if !entry_cond_as_bool.unwrap_or(true) {
+            return None;
+        }
+        if !decreases_as_bool.unwrap_or(false) {
+            return Some("possible non-termination, the decreases measure may not decrease");
+        }
+        if measure.expression.infer_type().is_signed_integer() {
+            let zero = self.get_i128_const_val(0);
+            let (non_negative_as_bool, _) =
+                self.check_condition_value_and_reachability(&measure.greater_or_equal(zero));
+            if !non_negative_as_bool.unwrap_or(false) {
+                return Some("possible non-termination, the decreases measure may become negative");
+            }
+        }
+        None
+    }
+
+    /// Returns the block, the argument operand and the span of every call to the function with
+    /// the given known name that has a single argument, such as loop_invariant! calls.
+    fn get_calls_to(
+        &mut self,
+        known_name: KnownNames,
+    ) -> Vec<(mir::BasicBlock, mir::Operand<'tcx>, rustc_span::Span)> {
+        let mut calls = Vec::new();
+        let mir = self.mir;
+        for (bb, data) in mir.basic_blocks().iter_enumerated() {
+            if let Some(mir::Terminator {
+                source_info,
+                kind: mir::TerminatorKind::Call { func, args, .. },
+            }) = &data.terminator
+            {
+                if let mir::Operand::Constant(constant) = func {
+                    if let TyKind::FnDef(def_id, ..) = &constant.literal.ty.kind {
+                        if self.cv.known_names_cache.get(self.tcx, *def_id) == known_name
+                            && args.len() == 1
+                        {
+                            calls.push((bb, args[0].clone(), source_info.span));
+                        }
+                    }
+                }
+            }
+        }
+        calls
+    }
+
+    /// Returns the blocks from the header of the loop whose body starts with the given block, to
+    /// the given block. Every block in the list, other than the header, must have the block before
+    /// it as its only predecessor. Returns None if there is no such loop.
+    pub fn get_blocks_from_loop_header_to(
+        &self,
+        bb: mir::BasicBlock,
+    ) -> Option<Vec<mir::BasicBlock>> {
+        let dominators = self.mir.dominators();
+        let mut blocks = vec![bb];
+        let mut block = bb;
+        loop {
+            let predecessors = self.mir.predecessors_for(block);
+            if predecessors
+                .iter()
+                .any(|pred_bb| dominators.is_dominated_by(*pred_bb, block))
+            {
+                // block is the target of a back edge, so it is the loop header.
+                blocks.reverse();
+                return Some(blocks);
+            }
+            if predecessors.len() != 1 || blocks.contains(&predecessors[0]) {
+                return None;
+            }
+            block = predecessors[0];
+            blocks.push(block);
+        }
+    }
+
+    /// Visits the given blocks, starting from the current environment, and returns the value of
+    /// the given operand, such as a loop invariant condition, at the call in the last block.
+    /// Returns None if the call is not reachable from the current environment.
+    fn get_operand_value_at_end_of(
+        &mut self,
+        blocks: &[mir::BasicBlock],
+        operand: &mir::Operand<'tcx>,
+    ) -> Option<Rc<AbstractValue>> {
+        let mut terminator_state = HashMap::new();
+        for (i, bb) in blocks.iter().enumerate() {
+            if i + 1 == blocks.len() {
+                return Some(
+                    BlockVisitor::new(self).visit_statements_then_terminator_operand(*bb, operand),
+                );
+            }
+            BlockVisitor::new(self).visit_basic_block(*bb, &mut terminator_state);
+            let exit_condition = self
+                .current_environment
+                .exit_conditions
+                .get(&blocks[i + 1])?
+                .clone();
+            if !exit_condition.as_bool_if_known().unwrap_or(true) {
+                return None;
+            }
+            self.current_environment.entry_condition = exit_condition;
+        }
+        None
     }
 
     #[logfn_inputs(TRACE)]
@@ -882,7 +1564,7 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
                     p_state.widen(&i_state, &path_condition)
                 };
                 let joined_condition = path_condition.or(i_state.entry_condition.clone());
-                if joined_condition.expression_size > k_limits::MAX_EXPRESSION_SIZE {
+                if joined_condition.expression_size > k_limits::max_expression_size() {
                     j_state.entry_condition = Rc::new(abstract_value::TRUE);
                 } else {
                     j_state.entry_condition = joined_condition;
@@ -1046,7 +1728,7 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
                 let span = self.current_span;
                 let message = "effective offset is outside allocated range";
                 let warning = self.cv.session.struct_span_warn(span, message);
-                self.emit_diagnostic(warning);
+                self.emit_diagnostic(warning, sarif::Rule::OffsetOutOfRange);
             }
         }
     }
@@ -1057,6 +1739,15 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
         self.active_calls.len() <= 1
     }
 
+    /// Returns true if the function being analyzed is an analysis root whose summary is also
+    /// used at its call sites (see Options::bottom_up). Such a summary must have the
+    /// preconditions that the function would promote to its callers if it were not a root,
+    /// along with the diagnostics that it reports instead as a root.
+    #[logfn_inputs(TRACE)]
+    pub fn root_summary_is_reused(&mut self) -> bool {
+        self.cv.options.bottom_up && self.function_being_analyzed_is_root()
+    }
+
     /// Adds a (rpath, rvalue) pair to the current environment for every pair in effects
     /// for which the path is rooted by source_path and where rpath is path re-rooted with
     /// target_path and rvalue is value refined by replacing all occurrences of parameter values
@@ -1304,7 +1995,7 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
                     self.current_span,
                     "the pointer points to memory that has already been deallocated",
                 );
-                self.emit_diagnostic(error);
+                self.emit_diagnostic(error, sarif::Rule::DeallocatedMemory);
             }
             let layouts_match = old_length
                 .equals(new_length.clone())
@@ -1327,7 +2018,7 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
                     }
                 );
                 let error = self.cv.session.struct_span_err(self.current_span, &message);
-                self.emit_diagnostic(error);
+                self.emit_diagnostic(error, sarif::Rule::InconsistentLayout);
             }
         }
     }
@@ -1378,6 +2069,7 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
             };
             self.smt_solver.set_backtrack_position();
             self.smt_solver.assert(&smt_expr);
+            self.consume_fuel(FUEL_FOR_SOLVER_QUERY);
             if self.smt_solver.solve() == SmtResult::Unsatisfiable {
                 // The solver can prove that the entry condition is always false.
                 entry_cond_as_bool = Some(false);
@@ -1394,8 +2086,7 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
     #[logfn_inputs(TRACE)]
     fn solve_condition(&mut self, cond_val: &Rc<AbstractValue>) -> Option<bool> {
         let ce = &cond_val.expression;
-        let cond_smt_expr = self.smt_solver.get_as_smt_predicate(ce);
-        match self.smt_solver.solve_expression(&cond_smt_expr) {
+        let decision = match self.solve_expression(ce, "can the condition be true", false) {
             SmtResult::Unsatisfiable => {
                 // If we get here, the solver can prove that cond_val is always false.
                 Some(false)
@@ -1404,8 +2095,9 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
                 // We could get here with cond_val being true. Or perhaps not.
                 // So lets see if !cond_val is provably false.
                 let not_cond_expr = &cond_val.logical_not().expression;
-                let smt_expr = self.smt_solver.get_as_smt_predicate(not_cond_expr);
-                if self.smt_solver.solve_expression(&smt_expr) == SmtResult::Unsatisfiable {
+                if self.solve_expression(not_cond_expr, "can the condition be false", false)
+                    == SmtResult::Unsatisfiable
+                {
                     // The solver can prove that !cond_val is always false.
                     Some(true)
                 } else {
@@ -1413,6 +2105,129 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
                 }
             }
             _ => None,
+        };
+        self.record_smt_decision(decision);
+        decision
+    }
+
+    /// Establishes if the given expression can be true in the current context of the solver,
+    /// without changing the context. The context must consist of just the entry condition of the
+    /// current block, since that is what the result is cached for. The result is taken from the
+    /// SMT result cache if possible, unless the cache is disabled by Options::no_smt_cache or a
+    /// counterexample is wanted, in which case self.counterexample is updated if the expression
+    /// is satisfiable.
+    /// The query is recorded if Options::smt_dump_dir is set.
+    pub fn solve_expression(
+        &mut self,
+        expression: &Expression,
+        description: &'static str,
+        want_counterexample: bool,
+    ) -> SmtResult {
+        // Fuel is consumed even if the result is cached, so that the outcome of the analysis
+        // does not depend on the state of the cache.
+        self.consume_fuel(FUEL_FOR_SOLVER_QUERY);
+        let options = self.cv.options;
+        let solver_limit = options.solver_limit();
+        let cache_key = if want_counterexample || options.no_smt_cache {
+            None
+        } else {
+            let key = smt_result_cache_key(
+                options.smt_backend(),
+                &self.current_environment.entry_condition.expression,
+                expression,
+            );
+            if let Some(cached) = self.cv.summary_cache.get_smt_result(&key) {
+                if cached.can_be_reused_with(solver_limit) {
+                    if options.smt_dump_dir.is_some() {
+                        // The query is recorded as if it had been solved, so that the dump does
+                        // not depend on the state of the cache.
+                        let smt_expression = self.smt_solver.get_as_smt_predicate(expression);
+                        self.smt_solver.set_backtrack_position();
+                        self.smt_solver.assert(&smt_expression);
+                        self.record_smt_check(description, &cached.result, true);
+                        self.smt_solver.backtrack();
+                    }
+                    return cached.result;
+                }
+            }
+            Some(key)
+        };
+        let smt_expression = self.smt_solver.get_as_smt_predicate(expression);
+        self.smt_solver.set_backtrack_position();
+        self.smt_solver.assert(&smt_expression);
+        let result = self.smt_solver.solve();
+        self.record_smt_check(description, &result, false);
+        if want_counterexample && result == SmtResult::Satisfiable {
+            self.counterexample = self.get_counterexample();
+        }
+        self.smt_solver.backtrack();
+        if let Some(key) = cache_key {
+            self.cv.summary_cache.set_smt_result(
+                &key,
+                &CachedSmtResult {
+                    result: result.clone(),
+                    limit: solver_limit,
+                },
+            );
+        }
+        result
+    }
+
+    /// Records the current state of the solver, along with the result of solving it, or the
+    /// cached result, if Options::smt_dump_dir is set. Must be called before the solver backtracks.
+    pub fn record_smt_check(
+        &mut self,
+        description: &'static str,
+        result: &SmtResult,
+        cached: bool,
+    ) {
+        if self.cv.options.smt_dump_dir.is_some() {
+            let solver_state = self.smt_solver.get_solver_state_as_string();
+            self.smt_checks.push(SmtCheck {
+                description,
+                solver_state,
+                result: result.clone(),
+                cached,
+            });
+        }
+    }
+
+    /// Combines the checks recorded since the last decision into a query about the condition
+    /// at the current span, if Options::smt_dump_dir is set.
+    pub fn record_smt_decision(&mut self, decision: Option<bool>) {
+        if self.cv.options.smt_dump_dir.is_some() {
+            let span = self
+                .cv
+                .session
+                .source_map()
+                .span_to_string(self.current_span);
+            let checks = std::mem::take(&mut self.smt_checks);
+            self.smt_queries.push(SmtQuery {
+                span,
+                checks,
+                decision,
+            });
+        }
+    }
+
+    /// Writes the queries recorded while visiting the body to the SMT-LIB2 file of this function.
+    /// If the function is visited more than once, the queries of later visits are appended.
+    fn write_smt_queries(&mut self) {
+        let options = self.cv.options;
+        if let Some(dir) = &options.smt_dump_dir {
+            if self.smt_queries.is_empty() {
+                return;
+            }
+            let path = smt_dump::smt2_file_path(dir, &self.function_name);
+            let append = !self.cv.smt_dump_files.insert(path.clone());
+            if let Err(err) = smt_dump::write_smt_queries(&path, &self.smt_queries, append) {
+                self.cv.session.warn(&format!(
+                    "unable to write SMT queries to {}: {}",
+                    path.display(),
+                    err
+                ));
+            }
+            self.smt_queries.clear();
         }
     }
 
@@ -1566,6 +2381,15 @@ impl<'analysis, 'compilation, 'tcx, E> BodyVisitor<'analysis, 'compilation, 'tcx
             self.current_environment.update_value_at(target_path, value);
             return;
         }
+        // Taint that was added to, or removed from, the value as a whole stays with its parts.
+        if let Some(value) = self.current_environment.value_at(&source_path) {
+            if let Expression::Sanitized { .. } | Expression::Tainted { .. } = &value.expression {
+                if move_elements {
+                    value_map = value_map.remove(&source_path);
+                }
+                value_map = value_map.insert(target_path, value.clone());
+            }
+        }
         self.current_environment.value_map = value_map;
     }
 
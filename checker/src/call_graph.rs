@@ -0,0 +1,98 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// The graph of direct calls between the function bodies of the crate being analyzed.
+// It is used to order the analysis of function bodies so that callees are analyzed before
+// their callers, which allows callers to use the summaries computed for their callees rather
+// than having to summarize them again, and to find calls that are part of mutual recursion.
+
+use log_derive::logfn;
+use rustc_data_structures::graph::scc::Sccs;
+use rustc_data_structures::graph::vec_graph::VecGraph;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir;
+use rustc_middle::ty::{TyCtxt, TyKind};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter, Result};
+
+pub struct CallGraph {
+    /// Maps each local function body to its node index.
+    node_for: HashMap<DefId, usize>,
+    /// The strongly connected components of the graph.
+    sccs: Sccs<usize, usize>,
+}
+
+impl Debug for CallGraph {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        "CallGraph".fmt(f)
+    }
+}
+
+impl CallGraph {
+    /// Constructs the graph of direct calls between the function bodies of the local crate.
+    /// Calls via trait methods and function pointers are not resolved, so the graph serves to
+    /// improve the order in which bodies are analyzed, but not to determine what they depend on.
+    #[logfn(TRACE)]
+    pub fn new(tcx: TyCtxt<'_>) -> CallGraph {
+        let nodes: Vec<DefId> = tcx
+            .body_owners()
+            .filter(|def_id| tcx.is_mir_available(*def_id))
+            .collect();
+        let node_for: HashMap<DefId, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, def_id)| (*def_id, i))
+            .collect();
+        let mut edges: Vec<(usize, usize)> = Vec::new();
+        for (caller, def_id) in nodes.iter().enumerate() {
+            let body: &mir::Body<'_> = tcx.optimized_mir(*def_id);
+            for block in body.basic_blocks().iter() {
+                if let mir::TerminatorKind::Call { func, .. } = &block.terminator().kind {
+                    if let TyKind::FnDef(callee_def_id, _) = func.ty(body, tcx).kind {
+                        if let Some(callee) = node_for.get(&callee_def_id) {
+                            edges.push((caller, *callee));
+                        }
+                    }
+                }
+            }
+        }
+        let graph = VecGraph::new(nodes.len(), edges);
+        let sccs = Sccs::new(&graph);
+        CallGraph { node_for, sccs }
+    }
+
+    /// Returns the strongly connected components of the call graph that contain any of the
+    /// given functions, restricted to those functions. The components are ordered so that
+    /// a component comes after every component that it calls into.
+    #[logfn(TRACE)]
+    pub fn bottom_up_components(&self, def_ids: &[DefId]) -> Vec<Vec<DefId>> {
+        let mut components: Vec<Vec<DefId>> = vec![Vec::new(); self.sccs.num_sccs()];
+        let mut unknown: Vec<DefId> = Vec::new();
+        for def_id in def_ids.iter() {
+            if let Some(node) = self.node_for.get(def_id) {
+                // SCC indices are assigned in post order, so callees have smaller indices.
+                components[self.sccs.scc(*node)].push(*def_id);
+            } else {
+                unknown.push(*def_id);
+            }
+        }
+        components.push(unknown);
+        components.retain(|component| !component.is_empty());
+        components
+    }
+
+    /// True if the two functions are different and each of them can call the other via direct
+    /// calls, i.e. they are part of the same cycle of calls.
+    #[logfn(TRACE)]
+    pub fn are_mutually_recursive(&self, def_id: DefId, other_def_id: DefId) -> bool {
+        if def_id == other_def_id {
+            return false;
+        }
+        match (self.node_for.get(&def_id), self.node_for.get(&other_def_id)) {
+            (Some(node), Some(other_node)) => self.sccs.scc(*node) == self.sccs.scc(*other_node),
+            _ => false,
+        }
+    }
+}
@@ -19,12 +19,15 @@ use crate::block_visitor::BlockVisitor;
 use crate::body_visitor::BodyVisitor;
 use crate::constant_domain::{ConstantDomain, FunctionReference};
 use crate::environment::Environment;
-use crate::expression::{Expression, ExpressionType, LayoutSource};
+use crate::expression::{Expression, ExpressionType, LayoutSource, QuantifierKind};
 use crate::k_limits;
+use crate::k_limits::FUEL_FOR_SOLVER_QUERY;
 use crate::known_names::KnownNames;
 use crate::options::DiagLevel;
 use crate::path::{Path, PathEnum, PathRefinement, PathSelector};
+use crate::sarif;
 use crate::smt_solver::SmtResult;
+use crate::statistics::AngelicReason;
 use crate::summaries::{Precondition, Summary};
 use crate::{abstract_value, type_visitor, utils};
 
@@ -112,6 +115,7 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
             let summary =
                 body_visitor.visit_body(self.function_constant_args, self.actual_argument_types);
             let call_was_angelic = body_visitor.assume_function_is_angelic;
+            let angelic_reason = body_visitor.angelic_reason.clone();
             trace!(
                 "summary {:?} {:?}",
                 self.block_visitor.bv.function_name,
@@ -129,7 +133,13 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                     .summary_cache
                     .set_summary_for_call_site(func_ref, signature, summary.clone());
             }
-            self.block_visitor.bv.assume_function_is_angelic |= call_was_angelic;
+            if call_was_angelic {
+                if let Some(reason) = angelic_reason {
+                    self.block_visitor.bv.make_angelic(reason);
+                } else {
+                    self.block_visitor.bv.assume_function_is_angelic = true;
+                }
+            }
             self.block_visitor.bv.start_instant = Instant::now() - elapsed_time;
             return summary;
         } else if let Some(devirtualized_summary) = self.try_to_devirtualize() {
@@ -364,6 +374,13 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                 .summary_cache
                 .get_summary_for_call_site(&func_ref, func_args)
                 .clone();
+            if let Some(incremental_analysis) = &mut self.block_visitor.bv.cv.incremental_analysis {
+                incremental_analysis.record_summary_use(
+                    self.block_visitor.bv.active_calls,
+                    &func_ref,
+                    &result,
+                );
+            }
             if result.is_computed || func_ref.def_id.is_none() {
                 return Some(result);
             }
@@ -396,6 +413,11 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                 self.handle_abstract_value();
                 return true;
             }
+            KnownNames::MiraiAddTaint => {
+                checked_assume!(self.actual_args.len() == 1);
+                self.handle_add_or_remove_taint(true);
+                return true;
+            }
             KnownNames::MiraiAssume => {
                 checked_assume!(self.actual_args.len() == 1);
                 if self.block_visitor.bv.check_for_errors {
@@ -409,10 +431,46 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                 self.block_visitor.bv.assume_preconditions_of_next_call = true;
                 return true;
             }
+            KnownNames::MiraiExists => {
+                checked_assume!(self.actual_args.len() == 2);
+                self.handle_quantifier(QuantifierKind::Exists);
+                return true;
+            }
+            KnownNames::MiraiForall => {
+                checked_assume!(self.actual_args.len() == 2);
+                self.handle_quantifier(QuantifierKind::ForAll);
+                return true;
+            }
+            KnownNames::MiraiDecreases => {
+                checked_assume!(self.actual_args.len() == 1);
+                self.handle_decreases();
+                return true;
+            }
             KnownNames::MiraiGetModelField => {
                 self.handle_get_model_field();
                 return true;
             }
+            KnownNames::MiraiHasTaint => {
+                checked_assume!(self.actual_args.len() == 1);
+                self.handle_has_taint();
+                return true;
+            }
+            KnownNames::MiraiLoopInvariant => {
+                // The invariant is checked by BodyVisitor::check_loop_invariants.
+                checked_assume!(self.actual_args.len() == 1);
+                self.handle_assume();
+                return true;
+            }
+            KnownNames::MiraiModifies => {
+                checked_assume!(self.actual_args.len() == 1);
+                self.handle_modifies();
+                return true;
+            }
+            KnownNames::MiraiOld => {
+                checked_assume!(self.actual_args.len() == 1);
+                self.handle_old();
+                return true;
+            }
             KnownNames::MiraiPostcondition => {
                 checked_assume!(self.actual_args.len() == 3);
                 if self.block_visitor.bv.check_for_errors {
@@ -431,6 +489,11 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                 self.handle_assume();
                 return true;
             }
+            KnownNames::MiraiRemoveTaint => {
+                checked_assume!(self.actual_args.len() == 1);
+                self.handle_add_or_remove_taint(false);
+                return true;
+            }
             KnownNames::MiraiSetModelField => {
                 self.handle_set_model_field();
                 return true;
@@ -468,6 +531,11 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                 }
                 return true;
             }
+            KnownNames::MiraiTaintSink => {
+                checked_assume!(self.actual_args.len() == 1);
+                self.handle_taint_sink();
+                return true;
+            }
             KnownNames::MiraiResult => {
                 if let Some((place, target)) = &self.destination {
                     let target_path = self.block_visitor.visit_place(place);
@@ -633,7 +701,9 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                         .cv
                         .session
                         .struct_span_warn(span, message);
-                    self.block_visitor.bv.emit_diagnostic(warning);
+                    self.block_visitor
+                        .bv
+                        .emit_diagnostic(warning, sarif::Rule::UnreachableAnnotation);
                     return;
                 }
 
@@ -645,7 +715,9 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                             span,
                             "assumption is provably true and can be deleted",
                         );
-                    self.block_visitor.bv.emit_diagnostic(warning);
+                    self.block_visitor
+                        .bv
+                        .emit_diagnostic(warning, sarif::Rule::RedundantAssumption);
                 }
             }
             KnownNames::MiraiPostcondition => {
@@ -673,7 +745,7 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                     // Push a precondition so that any known or unknown caller of this function
                     // is warned that this function will fail if the precondition is not met.
                     if self.block_visitor.bv.preconditions.len()
-                        < k_limits::MAX_INFERRED_PRECONDITIONS
+                        < k_limits::max_inferred_preconditions()
                     {
                         let condition = self
                             .block_visitor
@@ -713,6 +785,7 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                         .bv
                         .smt_solver
                         .get_as_smt_predicate(path_expr);
+                    self.block_visitor.bv.consume_fuel(FUEL_FOR_SOLVER_QUERY);
                     if self.block_visitor.bv.smt_solver.solve_expression(&path_smt)
                         == SmtResult::Unsatisfiable
                     {
@@ -763,7 +836,9 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                 };
                 let span = self.block_visitor.bv.current_span;
 
-                if path_cond.unwrap_or(false) && self.function_being_analyzed_is_root() {
+                let is_reported =
+                    path_cond.unwrap_or(false) && self.function_being_analyzed_is_root();
+                if is_reported {
                     // We always get to this call and we have to assume that the function will
                     // get called, so keep the message certain.
                     // Don't, however, complain about panics in the standard contract summaries
@@ -774,9 +849,12 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                             .cv
                             .session
                             .struct_span_warn(span, msg.as_str());
-                        self.block_visitor.bv.emit_diagnostic(err);
+                        self.block_visitor
+                            .bv
+                            .emit_diagnostic(err, sarif::Rule::DefiniteError);
                     }
-                } else {
+                }
+                if !is_reported || self.block_visitor.bv.root_summary_is_reused() {
                     // We might get to this call, depending on the state at the call site.
                     //
                     // In the case when an assert macro has been called, the inverse of the assertion
@@ -1027,6 +1105,7 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
             Summary::default()
         };
 
+        self.check_measure_of_recursive_call();
         self.check_preconditions_if_necessary(&function_summary);
         self.transfer_and_refine_normal_return_state(&function_summary);
         self.transfer_and_refine_cleanup_state(&function_summary);
@@ -1190,6 +1269,355 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
         }
     }
 
+    /// Records the place referenced by the argument as one that the function being analyzed
+    /// declares to modify. See BodyVisitor::check_modified_paths.
+    #[logfn_inputs(TRACE)]
+    fn handle_modifies(&mut self) {
+        precondition!(self.actual_args.len() == 1);
+        if self.block_visitor.bv.check_for_errors {
+            let path = Path::new_deref(self.actual_args[0].0.clone())
+                .refine_paths(&self.block_visitor.bv.current_environment);
+            if path.is_rooted_by_parameter() || path.is_rooted_by_static() {
+                if self.block_visitor.bv.modifies_span.is_none() {
+                    self.block_visitor.bv.modifies_span = Some(self.block_visitor.bv.current_span);
+                }
+                self.block_visitor.bv.modified_paths.push(path);
+            } else {
+                let span = self.block_visitor.bv.current_span;
+                let warning = self.block_visitor.bv.cv.session.struct_span_warn(
+                    span,
+                    "modifies! should only list places rooted in parameters or statics",
+                );
+                self.block_visitor
+                    .bv
+                    .emit_diagnostic(warning, sarif::Rule::InvalidAnnotation);
+            }
+        }
+        let exit_condition = self
+            .block_visitor
+            .bv
+            .current_environment
+            .entry_condition
+            .clone();
+        if let Some((_, target)) = &self.destination {
+            self.block_visitor.bv.current_environment.exit_conditions = self
+                .block_visitor
+                .bv
+                .current_environment
+                .exit_conditions
+                .insert(*target, exit_condition);
+        } else {
+            assume_unreachable!();
+        }
+    }
+
+    /// Records the measure of a decreases! call that is not at the start of a loop body as the
+    /// measure of the current function, which must be smaller for every call the function makes
+    /// to itself. The measures of loops are checked by BodyVisitor::check_loop_measures.
+    #[logfn_inputs(TRACE)]
+    fn handle_decreases(&mut self) {
+        precondition!(self.actual_args.len() == 1);
+        let measure = self.actual_args[0].1.clone();
+        if self.block_visitor.bv.check_for_errors && !measure.expression.infer_type().is_integer() {
+            let span = self.block_visitor.bv.current_span;
+            let warning = self
+                .block_visitor
+                .bv
+                .cv
+                .session
+                .struct_span_warn(span, "the measure of decreases! should be an integer");
+            self.block_visitor
+                .bv
+                .emit_diagnostic(warning, sarif::Rule::InvalidAnnotation);
+        }
+        let block = self.block_visitor.bv.current_location.block;
+        if self
+            .block_visitor
+            .bv
+            .get_blocks_from_loop_header_to(block)
+            .is_none()
+        {
+            self.block_visitor.bv.decreases_measure = Some(measure);
+        }
+        let exit_condition = self
+            .block_visitor
+            .bv
+            .current_environment
+            .entry_condition
+            .clone();
+        if let Some((_, target)) = &self.destination {
+            self.block_visitor.bv.current_environment.exit_conditions = self
+                .block_visitor
+                .bv
+                .current_environment
+                .exit_conditions
+                .insert(*target, exit_condition);
+        } else {
+            assume_unreachable!();
+        }
+    }
+
+    /// If the current function calls itself and has a decreases! measure, checks that the
+    /// measure of the called instance, given the arguments of the call, is smaller than the
+    /// measure of the current instance and is not negative. If the current function has a
+    /// measure and calls a function that can call it back, the measure is not checked across
+    /// the cycle of calls, so this is reported instead.
+    #[logfn_inputs(TRACE)]
+    pub fn check_measure_of_recursive_call(&mut self) {
+        if !self.block_visitor.bv.check_for_errors {
+            return;
+        }
+        let def_id = self.block_visitor.bv.def_id;
+        if self.callee_def_id != def_id {
+            if self.block_visitor.bv.decreases_measure.is_some()
+                && self
+                    .block_visitor
+                    .bv
+                    .cv
+                    .call_graph()
+                    .are_mutually_recursive(def_id, self.callee_def_id)
+            {
+                let span = self.block_visitor.bv.current_span;
+                let warning = self.block_visitor.bv.cv.session.struct_span_warn(
+                    span,
+                    "possible non-termination, the decreases measure is not checked for mutually recursive calls",
+                );
+                self.block_visitor
+                    .bv
+                    .emit_diagnostic(warning, sarif::Rule::PossibleError);
+            }
+            return;
+        }
+        if let Some(measure) = self.block_visitor.bv.decreases_measure.clone() {
+            let callee_measure = measure
+                .refine_parameters(
+                    self.actual_args,
+                    self.block_visitor.bv.fresh_variable_offset,
+                )
+                .refine_paths(&self.block_visitor.bv.current_environment);
+            if let Some(message) = self
+                .block_visitor
+                .bv
+                .check_decreasing_measure(&callee_measure, &measure)
+            {
+                let span = self.block_visitor.bv.current_span;
+                let warning = self
+                    .block_visitor
+                    .bv
+                    .cv
+                    .session
+                    .struct_span_warn(span, message);
+                self.block_visitor
+                    .bv
+                    .emit_diagnostic(warning, sarif::Rule::PossibleError);
+            }
+        }
+    }
+
+    /// Update the state so that the call result is the value that the place referenced by the
+    /// argument had on entry to the function. Since the entry state is not known, this is
+    /// a value expressed in terms of the parameters, which callers refine with their own state
+    /// prior to the call.
+    #[logfn_inputs(TRACE)]
+    fn handle_old(&mut self) {
+        precondition!(self.actual_args.len() == 1);
+        let destination = self.destination;
+        if let Some((place, target)) = &destination {
+            let target_path = self.block_visitor.visit_place(place);
+            let target_rustc_type = self
+                .block_visitor
+                .bv
+                .type_visitor
+                .get_rustc_place_type(place, self.block_visitor.bv.current_span);
+            let source_path = Path::new_deref(self.actual_args[0].0.clone())
+                .refine_paths(&self.block_visitor.bv.current_environment);
+            let old_value = if source_path.is_rooted_by_parameter() {
+                // Look up the path in an empty environment, which models the state on entry to
+                // the function.
+                let saved_environment = std::mem::replace(
+                    &mut self.block_visitor.bv.current_environment,
+                    Environment::default(),
+                );
+                let old_value = self
+                    .block_visitor
+                    .bv
+                    .lookup_path_and_refine_result(source_path, target_rustc_type);
+                self.block_visitor.bv.current_environment = saved_environment;
+                old_value
+            } else {
+                if self.block_visitor.bv.check_for_errors {
+                    let span = self.block_visitor.bv.current_span;
+                    let warning = self.block_visitor.bv.cv.session.struct_span_warn(
+                        span,
+                        "the argument of old! should be a place rooted in a parameter",
+                    );
+                    self.block_visitor
+                        .bv
+                        .emit_diagnostic(warning, sarif::Rule::InvalidAnnotation);
+                }
+                self.block_visitor
+                    .bv
+                    .lookup_path_and_refine_result(source_path, target_rustc_type)
+            };
+            self.block_visitor
+                .bv
+                .current_environment
+                .update_value_at(target_path, old_value);
+            let exit_condition = self
+                .block_visitor
+                .bv
+                .current_environment
+                .entry_condition
+                .clone();
+            self.block_visitor.bv.current_environment.exit_conditions = self
+                .block_visitor
+                .bv
+                .current_environment
+                .exit_conditions
+                .insert(*target, exit_condition);
+        } else {
+            assume_unreachable!();
+        }
+    }
+
+    /// Update the state so that the call result is a condition that is quantified over the
+    /// indices in the range given by the first argument. The quantified condition is the result
+    /// of the closure given by the second argument, with the closure parameter as the bound
+    /// variable.
+    #[logfn_inputs(TRACE)]
+    fn handle_quantifier(&mut self, kind: QuantifierKind) {
+        precondition!(self.actual_args.len() == 2);
+        let destination = self.destination;
+        if let Some((place, target)) = &destination {
+            let target_path = self.block_visitor.visit_place(place);
+            let usize_type = self.block_visitor.bv.tcx.types.usize;
+            let range_path = self.actual_args[0].0.clone();
+            let start_path = Path::new_field(range_path.clone(), 0)
+                .refine_paths(&self.block_visitor.bv.current_environment);
+            let lower_bound = self
+                .block_visitor
+                .bv
+                .lookup_path_and_refine_result(start_path, usize_type);
+            let end_path = Path::new_field(range_path, 1)
+                .refine_paths(&self.block_visitor.bv.current_environment);
+            let upper_bound = self
+                .block_visitor
+                .bv
+                .lookup_path_and_refine_result(end_path, usize_type);
+            // Use a local that cannot clash with the locals of this function or the closure.
+            let bound_variable_path =
+                Path::new_local(self.block_visitor.bv.fresh_variable_offset + 999_999);
+            let quantifier = if let Some(condition) =
+                self.get_result_of_quantified_closure(bound_variable_path.clone())
+            {
+                AbstractValue::make_quantifier(
+                    kind,
+                    bound_variable_path,
+                    lower_bound,
+                    upper_bound,
+                    condition,
+                )
+            } else {
+                AbstractValue::make_typed_unknown(ExpressionType::Bool)
+            };
+            self.block_visitor
+                .bv
+                .current_environment
+                .update_value_at(target_path, quantifier);
+            let exit_condition = self
+                .block_visitor
+                .bv
+                .current_environment
+                .entry_condition
+                .clone();
+            self.block_visitor.bv.current_environment.exit_conditions = self
+                .block_visitor
+                .bv
+                .current_environment
+                .exit_conditions
+                .insert(*target, exit_condition);
+        } else {
+            assume_unreachable!();
+        }
+    }
+
+    /// Returns the result of calling the closure that is the second argument with a variable
+    /// of type usize at the given path as its argument, if the closure has a summary.
+    #[logfn_inputs(TRACE)]
+    fn get_result_of_quantified_closure(
+        &mut self,
+        bound_variable_path: Rc<Path>,
+    ) -> Option<Rc<AbstractValue>> {
+        let closure_ty = self.actual_argument_types[1];
+        if let TyKind::Closure(def_id, substs) = closure_ty.kind {
+            let specialized_substs = self.block_visitor.bv.type_visitor.specialize_substs(
+                substs,
+                &self.block_visitor.bv.type_visitor.generic_argument_map,
+            );
+            let func_const = self
+                .visit_function_reference(def_id, closure_ty, specialized_substs)
+                .clone();
+            // The closure body takes a reference to the closure as its first parameter.
+            let closure_path = self.actual_args[1].0.clone();
+            let closure_reference_path =
+                Path::new_local(self.block_visitor.bv.fresh_variable_offset + 999_998);
+            let closure_reference = AbstractValue::make_reference(closure_path);
+            self.block_visitor
+                .bv
+                .current_environment
+                .update_value_at(closure_reference_path.clone(), closure_reference.clone());
+            let usize_type = self.block_visitor.bv.tcx.types.usize;
+            let bound_variable = AbstractValue::make_from(
+                Expression::Variable {
+                    path: bound_variable_path.clone(),
+                    var_type: ExpressionType::Usize,
+                },
+                1,
+            );
+            let actual_args = vec![
+                (closure_reference_path, closure_reference),
+                (bound_variable_path, bound_variable),
+            ];
+            let actual_argument_types = vec![closure_ty, usize_type];
+            let generic_arguments = self.block_visitor.bv.cv.substs_cache.get(&def_id).cloned();
+            let generic_argument_map = self
+                .block_visitor
+                .bv
+                .type_visitor
+                .generic_argument_map
+                .clone();
+            let fresh = self.block_visitor.bv.fresh_variable_offset;
+            let mut block_visitor = BlockVisitor::<E>::new(self.block_visitor.bv);
+            let mut closure_call_visitor = CallVisitor::new(
+                &mut block_visitor,
+                def_id,
+                generic_arguments,
+                generic_argument_map,
+                func_const,
+            );
+            closure_call_visitor.actual_args = &actual_args;
+            closure_call_visitor.actual_argument_types = &actual_argument_types;
+            closure_call_visitor.callee_known_name = KnownNames::None;
+            let summary = closure_call_visitor.get_function_summary()?;
+            if !summary.is_computed || summary.is_angelic {
+                return None;
+            }
+            let result_path = Path::new_result();
+            let result = summary
+                .side_effects
+                .iter()
+                .find(|(path, _)| *path == result_path)
+                .map(|(_, value)| value.clone())?;
+            Some(
+                result
+                    .refine_parameters(&actual_args, fresh)
+                    .refine_paths(&self.block_visitor.bv.current_environment),
+            )
+        } else {
+            None
+        }
+    }
+
     fn handle_post_condition(&mut self) {
         precondition!(self.actual_args.len() == 3);
         let condition = self.actual_args[0].1.clone();
@@ -1232,7 +1660,9 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                 .cv
                 .session
                 .struct_span_warn(span, "preconditions should be reached unconditionally");
-            self.block_visitor.bv.emit_diagnostic(warning);
+            self.block_visitor
+                .bv
+                .emit_diagnostic(warning, sarif::Rule::ConditionalPrecondition);
             self.block_visitor.bv.check_for_unconditional_precondition = false;
         }
         let exit_condition = self
@@ -1271,6 +1701,229 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
         }
     }
 
+    /// Update the state so that the value of the place referenced by the argument, and the values
+    /// of all of its parts, are tainted (or no longer tainted) with the kind of taint given by the
+    /// generic argument K of the callee.
+    #[logfn_inputs(TRACE)]
+    fn handle_add_or_remove_taint(&mut self, add: bool) {
+        precondition!(self.actual_args.len() == 1);
+        let kind = self.get_taint_kind();
+        let (path, _) = self.get_tainted_place();
+        let parts: Vec<(Rc<Path>, Rc<AbstractValue>)> = self
+            .block_visitor
+            .bv
+            .current_environment
+            .value_map
+            .iter()
+            .filter(|(p, _)| **p == path || p.is_rooted_by(&path))
+            .map(|(p, v)| (p.clone(), v.clone()))
+            .collect();
+        let parts = if parts.is_empty() {
+            let (path, rustc_type) = self.get_tainted_place();
+            let value = self
+                .block_visitor
+                .bv
+                .lookup_path_and_refine_result(path.clone(), rustc_type);
+            vec![(path, value)]
+        } else {
+            parts
+        };
+        for (part_path, part_value) in parts {
+            if let Expression::HeapBlock { .. }
+            | Expression::Offset { .. }
+            | Expression::Reference(..) = &part_value.expression
+            {
+                // Taint is a property of data, not of pointers to data.
+                continue;
+            }
+            let tagged_value = if add {
+                part_value.add_taint(kind.clone())
+            } else {
+                part_value.remove_taint(kind.clone())
+            };
+            self.block_visitor
+                .bv
+                .current_environment
+                .update_value_at(part_path, tagged_value);
+        }
+        if let Some((_, target)) = &self.destination {
+            let exit_condition = self
+                .block_visitor
+                .bv
+                .current_environment
+                .entry_condition
+                .clone();
+            self.block_visitor.bv.current_environment.exit_conditions = self
+                .block_visitor
+                .bv
+                .current_environment
+                .exit_conditions
+                .insert(*target, exit_condition);
+        } else {
+            assume_unreachable!();
+        }
+    }
+
+    /// Update the state so that the call result is a value that is true if the value of the place
+    /// referenced by the argument, or the value of any of its parts, is tainted with the kind of
+    /// taint given by the generic argument K of the callee.
+    #[logfn_inputs(TRACE)]
+    fn handle_has_taint(&mut self) {
+        precondition!(self.actual_args.len() == 1);
+        let destination = self.destination;
+        if let Some((place, target)) = &destination {
+            let target_path = self.block_visitor.visit_place(place);
+            let kind = self.get_taint_kind();
+            let has_taint = self.get_taint_of_place(&kind);
+            self.block_visitor
+                .bv
+                .current_environment
+                .update_value_at(target_path, has_taint);
+            let exit_condition = self
+                .block_visitor
+                .bv
+                .current_environment
+                .entry_condition
+                .clone();
+            self.block_visitor.bv.current_environment.exit_conditions = self
+                .block_visitor
+                .bv
+                .current_environment
+                .exit_conditions
+                .insert(*target, exit_condition);
+        } else {
+            assume_unreachable!();
+        }
+    }
+
+    /// Checks that the value of the place referenced by the argument, and the values of all of its
+    /// parts, are not tainted with the kind of taint given by the generic argument K of the callee.
+    /// If that depends on the values of parameters, the check becomes a precondition, so that
+    /// callers check it for the arguments that flow into the sink.
+    #[logfn_inputs(TRACE)]
+    fn handle_taint_sink(&mut self) {
+        precondition!(self.actual_args.len() == 1);
+        if self.block_visitor.bv.check_for_errors {
+            let kind = self.get_taint_kind();
+            let has_taint = self.get_taint_of_place(&kind);
+            let (has_taint_as_bool, entry_cond_as_bool) = self
+                .block_visitor
+                .check_condition_value_and_reachability(&has_taint);
+            if has_taint_as_bool.unwrap_or(true) && entry_cond_as_bool.unwrap_or(true) {
+                let span = self.block_visitor.bv.current_span;
+                let message = format!("data tainted with {} reaches a sink", kind);
+                let is_certain = has_taint_as_bool.unwrap_or(false);
+                let is_reported = is_certain
+                    && (entry_cond_as_bool.unwrap_or(false)
+                        || self.function_being_analyzed_is_root());
+                if is_reported {
+                    let (message, rule) = if entry_cond_as_bool.unwrap_or(false) {
+                        (message, sarif::Rule::DefiniteError)
+                    } else {
+                        (format!("possible {}", message), sarif::Rule::PossibleError)
+                    };
+                    let warning = self
+                        .block_visitor
+                        .bv
+                        .cv
+                        .session
+                        .struct_span_warn(span, message.as_str());
+                    self.block_visitor.bv.emit_diagnostic(warning, rule);
+                }
+                let is_promoted = !is_reported
+                    || (!entry_cond_as_bool.unwrap_or(false)
+                        && self.block_visitor.bv.root_summary_is_reused());
+                if is_promoted
+                    && self.block_visitor.bv.preconditions.len()
+                        < k_limits::max_inferred_preconditions()
+                {
+                    let condition = self
+                        .block_visitor
+                        .bv
+                        .current_environment
+                        .entry_condition
+                        .logical_not()
+                        .or(has_taint.logical_not());
+                    let precondition = Precondition {
+                        condition,
+                        message: Rc::new(message),
+                        provenance: None,
+                        spans: vec![span],
+                    };
+                    self.block_visitor.bv.preconditions.push(precondition);
+                }
+            }
+        }
+        if let Some((_, target)) = &self.destination {
+            let exit_condition = self
+                .block_visitor
+                .bv
+                .current_environment
+                .entry_condition
+                .clone();
+            self.block_visitor.bv.current_environment.exit_conditions = self
+                .block_visitor
+                .bv
+                .current_environment
+                .exit_conditions
+                .insert(*target, exit_condition);
+        } else {
+            assume_unreachable!();
+        }
+    }
+
+    /// Returns the name of the kind of taint that is the generic argument K of the callee.
+    #[logfn_inputs(TRACE)]
+    fn get_taint_kind(&self) -> Rc<String> {
+        let sym = rustc_span::Symbol::intern("K");
+        let kind_type = (self.callee_generic_argument_map.as_ref())
+            .expect("taint annotations must be called with generic arguments")
+            .get(&sym)
+            .expect("taint annotations must have generic argument K");
+        Rc::new(match kind_type.kind {
+            TyKind::Adt(def, _) => self.block_visitor.bv.tcx.def_path_str(def.did),
+            _ => format!("{:?}", kind_type),
+        })
+    }
+
+    /// Returns the path and type of the place referenced by the first argument. Since taint is
+    /// a property of data, rather than of pointers to data, any references stored in the place
+    /// are followed to the places that they reference.
+    #[logfn_inputs(TRACE)]
+    fn get_tainted_place(&mut self) -> (Rc<Path>, Ty<'tcx>) {
+        let mut path = Path::new_deref(self.actual_args[0].0.clone())
+            .refine_paths(&self.block_visitor.bv.current_environment);
+        let mut rustc_type = self.actual_argument_types[0];
+        if let TyKind::Ref(_, ty, _) = rustc_type.kind {
+            rustc_type = ty;
+        }
+        while let TyKind::Ref(_, ty, _) = rustc_type.kind {
+            path = Path::new_deref(path).refine_paths(&self.block_visitor.bv.current_environment);
+            rustc_type = ty;
+        }
+        (path, rustc_type)
+    }
+
+    /// Returns a value that is true if the value of the place referenced by the first argument,
+    /// or the value of any of its parts, is tainted with the given kind of taint.
+    #[logfn_inputs(TRACE)]
+    fn get_taint_of_place(&mut self, kind: &Rc<String>) -> Rc<AbstractValue> {
+        let (path, rustc_type) = self.get_tainted_place();
+        if let Some(has_taint) = self
+            .block_visitor
+            .bv
+            .current_environment
+            .has_taint_at(&path, kind)
+        {
+            has_taint
+        } else {
+            self.block_visitor
+                .bv
+                .lookup_path_and_refine_result(path, rustc_type)
+                .has_taint(kind)
+        }
+    }
+
     /// Update the state to reflect the assignment of the model field.
     #[logfn_inputs(TRACE)]
     fn handle_set_model_field(&mut self) {
@@ -1566,7 +2219,9 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                     .cv
                     .session
                     .struct_span_warn(span, message);
-                self.block_visitor.bv.emit_diagnostic(warning);
+                self.block_visitor
+                    .bv
+                    .emit_diagnostic(warning, sarif::Rule::OffsetOutOfRange);
             }
         }
     }
@@ -1662,7 +2317,15 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                 // Assume the callee is perfect and assume the caller and all of its callers are perfect
                 // little angels as well. This cuts down on false positives caused by missing post
                 // conditions.
-                self.block_visitor.bv.assume_function_is_angelic = true;
+                let callee = if let Some(func_ref) = &self.callee_func_ref {
+                    func_ref.summary_cache_key.to_string()
+                } else {
+                    utils::summary_key_str(self.block_visitor.bv.tcx, self.callee_def_id)
+                        .to_string()
+                };
+                self.block_visitor
+                    .bv
+                    .make_angelic(AngelicReason::MissingSummary { callee });
             }
             DiagLevel::STRICT => {
                 // Assume the callee is perfect and that the caller does not need to prove any
@@ -1675,7 +2338,9 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                         self.block_visitor.bv.current_span,
                         "the called function could not be summarized, all bets are off",
                     );
-                    self.block_visitor.bv.emit_diagnostic(error);
+                    self.block_visitor
+                        .bv
+                        .emit_diagnostic(error, sarif::Rule::MissingSummary);
                 }
             }
         }
@@ -1724,6 +2389,7 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                 .as_bool_if_known()
                 .is_none()
             {
+                self.block_visitor.bv.consume_fuel(1);
                 refined_condition = refined_condition.refine_with(
                     &self.block_visitor.bv.current_environment.entry_condition,
                     0,
@@ -1743,7 +2409,7 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                 // The precondition is definitely false.
                 if entry_cond_as_bool.unwrap_or(false) {
                     // We always get to this call
-                    self.issue_diagnostic_for_call(precondition, false);
+                    self.issue_diagnostic_for_call(precondition, false, false);
                     return;
                 } else {
                     // Promote the precondition, but be assertive.
@@ -1755,8 +2421,12 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
             }
 
             // If the current function is not an analysis root, promote the precondition, subject to a k-limit.
-            if !self.function_being_analyzed_is_root()
-                && self.block_visitor.bv.preconditions.len() < k_limits::MAX_INFERRED_PRECONDITIONS
+            // A root whose summary is reused promotes it as well, but also reports it.
+            let is_root = self.function_being_analyzed_is_root();
+            let is_promotable = !is_root || self.block_visitor.bv.root_summary_is_reused();
+            if is_promotable
+                && self.block_visitor.bv.preconditions.len()
+                    < k_limits::max_inferred_preconditions()
             {
                 // Promote the callee precondition to a precondition of the current function.
                 // Unless, of course, if the precondition is already a precondition of the
@@ -1766,39 +2436,62 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                         || pc.provenance == precondition.provenance
                 });
                 if seen_precondition {
-                    continue;
+                    if !is_root {
+                        continue;
+                    }
+                } else {
+                    self.promote_precondition(precondition, refined_condition);
+                    if !is_root {
+                        return;
+                    }
                 }
-                let promoted_condition = self
-                    .block_visitor
-                    .bv
-                    .current_environment
-                    .entry_condition
-                    .logical_not()
-                    .or(refined_condition);
-                let mut stacked_spans = vec![self.block_visitor.bv.current_span];
-                stacked_spans.append(&mut precondition.spans.clone());
-                let promoted_precondition = Precondition {
-                    condition: promoted_condition,
-                    message: precondition.message.clone(),
-                    provenance: precondition.provenance.clone(),
-                    spans: stacked_spans,
-                };
-                self.block_visitor
-                    .bv
-                    .preconditions
-                    .push(promoted_precondition);
-                return;
             }
 
-            // The precondition cannot be promoted, so the buck stops here.
-            self.issue_diagnostic_for_call(precondition, warn);
+            // The precondition cannot be promoted, or is also reported by a root, so the buck
+            // stops here.
+            self.issue_diagnostic_for_call(precondition, warn, true);
         }
     }
 
+    /// Adds the given callee precondition, refined to the call site, to the preconditions
+    /// of the current function.
+    #[logfn_inputs(TRACE)]
+    fn promote_precondition(
+        &mut self,
+        precondition: &Precondition,
+        refined_condition: Rc<AbstractValue>,
+    ) {
+        let promoted_condition = self
+            .block_visitor
+            .bv
+            .current_environment
+            .entry_condition
+            .logical_not()
+            .or(refined_condition);
+        let mut stacked_spans = vec![self.block_visitor.bv.current_span];
+        stacked_spans.append(&mut precondition.spans.clone());
+        let promoted_precondition = Precondition {
+            condition: promoted_condition,
+            message: precondition.message.clone(),
+            provenance: precondition.provenance.clone(),
+            spans: stacked_spans,
+        };
+        self.block_visitor
+            .bv
+            .preconditions
+            .push(promoted_precondition);
+    }
+
     // Issue a diagnostic, but only if there isn't already a diagnostic for this
-    // function call.
+    // function call. If promotable is true, the precondition would have been promoted to
+    // the current function if it were not an analysis root.
     #[logfn_inputs(TRACE)]
-    fn issue_diagnostic_for_call(&mut self, precondition: &Precondition, warn: bool) {
+    fn issue_diagnostic_for_call(
+        &mut self,
+        precondition: &Precondition,
+        warn: bool,
+        promotable: bool,
+    ) {
         if self.block_visitor.bv.check_for_errors
             && !self
                 .block_visitor
@@ -1807,7 +2500,7 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                 .contains(&self.callee_fun_val)
         {
             self.block_visitor
-                .emit_diagnostic_for_precondition(precondition, warn);
+                .emit_diagnostic_for_precondition(precondition, warn, promotable);
             self.block_visitor
                 .bv
                 .already_reported_errors_for_call_to
@@ -1823,9 +2516,15 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
             // Assign function result to place
             let target_path = self.block_visitor.visit_place(place);
             let return_value_path = Path::new_result();
+            // The post condition is expressed in terms of the state on entry to the function,
+            // so it has to be refined with the state prior to the call.
+            let pre_call_environment = self.block_visitor.bv.current_environment.clone();
 
             // Transfer side effects
             if function_summary.is_computed && !function_summary.is_angelic {
+                // Places that the function modifies, but that the contract is silent about
+                self.havoc_modified_paths(&function_summary.modified_paths);
+
                 // Effects on the heap
                 for (path, value) in function_summary.side_effects.iter() {
                     if path.is_rooted_by_abstract_heap_block() {
@@ -1844,6 +2543,25 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                     check_for_early_return!(self.block_visitor.bv);
                 }
 
+                // Effects on ghost statics
+                for (path, value) in function_summary.side_effects.iter() {
+                    if path.is_rooted_by_static() {
+                        // The value may depend on the value the static had before the call.
+                        let rvalue = value
+                            .clone()
+                            .refine_parameters(
+                                self.actual_args,
+                                self.block_visitor.bv.fresh_variable_offset,
+                            )
+                            .refine_paths(&pre_call_environment);
+                        self.block_visitor
+                            .bv
+                            .current_environment
+                            .update_value_at(path.clone(), rvalue);
+                    }
+                    check_for_early_return!(self.block_visitor.bv);
+                }
+
                 // Effects on the call result
                 self.transfer_and_refine(
                     &function_summary.side_effects,
@@ -1907,29 +2625,48 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                     .bv
                     .type_visitor
                     .get_place_type(place, self.block_visitor.bv.current_span);
-                let result_val = AbstractValue::make_from(
-                    Expression::Variable {
-                        path: target_path,
-                        var_type,
-                    },
-                    1,
-                );
+                let result_val = self
+                    .block_visitor
+                    .bv
+                    .current_environment
+                    .value_at(&target_path)
+                    .cloned()
+                    .unwrap_or_else(|| {
+                        AbstractValue::make_from(
+                            Expression::Variable {
+                                path: target_path.clone(),
+                                var_type,
+                            },
+                            1,
+                        )
+                    });
                 let return_value_path =
                     Path::new_local(self.block_visitor.bv.fresh_variable_offset);
 
                 return_value_env.update_value_at(return_value_path, result_val);
-                let refined_post_condition = post_condition
-                    .refine_parameters(
-                        self.actual_args,
-                        self.block_visitor.bv.fresh_variable_offset,
-                    )
-                    .refine_paths(&return_value_env);
+                for (path, value) in self
+                    .block_visitor
+                    .bv
+                    .current_environment
+                    .value_map
+                    .iter()
+                    .filter(|(p, _)| p.is_rooted_by(&target_path))
+                {
+                    return_value_env.update_value_at(path.clone(), value.clone());
+                }
+                let refined_post_condition = post_condition.refine_parameters(
+                    self.actual_args,
+                    self.block_visitor.bv.fresh_variable_offset,
+                );
                 debug!(
                     "refined post condition before path refinement {:?}",
                     refined_post_condition
                 );
-                let refined_post_condition =
-                    refined_post_condition.refine_paths(&self.block_visitor.bv.current_environment);
+                // The result of the call is only known in the post call state, so refine
+                // references to it in a second step.
+                let refined_post_condition = refined_post_condition
+                    .refine_paths(&pre_call_environment)
+                    .refine_paths(&return_value_env);
                 debug!("refined post condition {:?}", refined_post_condition);
                 exit_condition = exit_condition.and(refined_post_condition);
             }
@@ -1943,6 +2680,71 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
         }
     }
 
+    /// Forgets what is known about the places that the summary of a contract function declares to
+    /// be modified (see the modifies! macro), by giving them values that are unknown, other than
+    /// that they result from this call.
+    #[logfn_inputs(TRACE)]
+    fn havoc_modified_paths(&mut self, modified_paths: &[Rc<Path>]) {
+        for modified_path in modified_paths.iter() {
+            let target_path = modified_path
+                .refine_parameters(
+                    self.actual_args,
+                    self.block_visitor.bv.fresh_variable_offset,
+                )
+                .refine_paths(&self.block_visitor.bv.current_environment);
+            // If the place is reached via a pointer, everything reachable via the pointer is
+            // modified, but not the pointer itself.
+            let root = match &target_path.value {
+                PathEnum::QualifiedPath {
+                    qualifier,
+                    selector,
+                    ..
+                } if matches!(selector.as_ref(), PathSelector::Deref) => qualifier.clone(),
+                _ => target_path.clone(),
+            };
+            let mut paths_to_havoc: Vec<(Rc<Path>, ExpressionType)> = self
+                .block_visitor
+                .bv
+                .current_environment
+                .value_map
+                .iter()
+                .filter(|(p, _)| **p == target_path || p.is_rooted_by(&root))
+                .map(|(p, v)| (p.clone(), v.expression.infer_type()))
+                .collect();
+            let target_type: ExpressionType = (&self
+                .block_visitor
+                .bv
+                .type_visitor
+                .get_path_rustc_type(&target_path, self.block_visitor.bv.current_span)
+                .kind)
+                .into();
+            if target_type != ExpressionType::NonPrimitive
+                && !paths_to_havoc.iter().any(|(p, _)| *p == target_path)
+            {
+                paths_to_havoc.push((target_path.clone(), target_type));
+            }
+            for (path, result_type) in paths_to_havoc.into_iter() {
+                let value = AbstractValue::make_from(
+                    Expression::UninterpretedCall {
+                        callee: self.callee_fun_val.clone(),
+                        arguments: self
+                            .actual_args
+                            .iter()
+                            .map(|(_, arg)| arg.clone())
+                            .collect(),
+                        result_type,
+                        path: path.clone(),
+                    },
+                    1,
+                );
+                self.block_visitor
+                    .bv
+                    .current_environment
+                    .update_value_at(path, value);
+            }
+        }
+    }
+
     /// Handle the case where the called function does not complete normally.
     #[logfn_inputs(TRACE)]
     pub fn transfer_and_refine_cleanup_state(&mut self, function_summary: &Summary) {
@@ -2258,7 +3060,9 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                     self.block_visitor.bv.current_span,
                     "the pointer points to memory that has already been deallocated",
                 );
-                self.block_visitor.bv.emit_diagnostic(error);
+                self.block_visitor
+                    .bv
+                    .emit_diagnostic(error, sarif::Rule::DeallocatedMemory);
             }
             let layouts_match = old_length
                 .equals(new_length.clone())
@@ -2287,7 +3091,9 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                     .cv
                     .session
                     .struct_span_err(self.block_visitor.bv.current_span, &message);
-                self.block_visitor.bv.emit_diagnostic(error);
+                self.block_visitor
+                    .bv
+                    .emit_diagnostic(error, sarif::Rule::InconsistentLayout);
             }
         }
     }
@@ -2306,7 +3112,9 @@ impl<'call, 'block, 'analysis, 'compilation, 'tcx, E>
                         self.block_visitor.bv.current_span,
                         "this argument should be a string literal, do not call this function directly",
                     );
-                    self.block_visitor.bv.emit_diagnostic(error);
+                    self.block_visitor
+                        .bv
+                        .emit_diagnostic(error, sarif::Rule::InvalidAnnotation);
                 }
                 Rc::new("dummy argument".to_string())
             }
@@ -4,22 +4,35 @@
 // LICENSE file in the root directory of this source tree.
 #![allow(clippy::borrowed_box)]
 
+use crate::config::ExclusionMode;
 use crate::constant_domain::ConstantValueCache;
 use crate::crate_visitor::CrateVisitor;
+use crate::incremental::IncrementalAnalysis;
 use crate::known_names::KnownNamesCache;
 use crate::options::Options;
 use crate::summaries::PersistentSummaryCache;
 
+use lazy_static::lazy_static;
 use log::info;
 use log_derive::*;
+use regex::Regex;
 use rustc_driver::Compilation;
 use rustc_interface::{interface, Queries};
 use rustc_middle::ty::TyCtxt;
-use std::collections::HashMap;
+use rustc_session::config::Input;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Formatter, Result};
 use std::path::PathBuf;
 use tempdir::TempDir;
 
+lazy_static! {
+    // Crate level attributes that enable and register the mirai tool, possibly inside cfg_attr.
+    static ref FEATURE_REGISTER_TOOL: Regex =
+        Regex::new(r"#!\[[^\]]*\bfeature\s*\([^)]*\bregister_tool\b").unwrap();
+    static ref REGISTER_TOOL_MIRAI: Regex =
+        Regex::new(r"#!\[[^\]]*\bregister_tool\s*\([^)]*\bmirai\b").unwrap();
+}
+
 /// Private state used to implement the callbacks.
 pub struct MiraiCallbacks {
     /// Options provided to the analysis.
@@ -71,12 +84,16 @@ impl rustc_driver::Callbacks for MiraiCallbacks {
     fn config(&mut self, config: &mut interface::Config) {
         self.file_name = config.input.source_name().to_string();
         info!("Processing input file: {}", self.file_name);
+        self.options.read_config_file();
         if self.options.test_only {
             if config.opts.test {
-                if Self::is_test_black_listed(&self.file_name) {
-                    // This file is known not to compile with the test flag.
-                    // This happens in Libra code.
-                    std::process::exit(0);
+                if let Some(exclusion) = self.options.exclusion_for_crate(&self.file_name) {
+                    if exclusion.when == ExclusionMode::TestOnly {
+                        // Files that are excluded only from test analysis are typically
+                        // known not to compile with the test flag, so stop right here.
+                        info!("skipping {}: {}", self.file_name, exclusion.reason);
+                        std::process::exit(0);
+                    }
                 }
             } else {
                 // In test only mode we only run MIRAI when the --tests flag has been set.
@@ -85,6 +102,17 @@ impl rustc_driver::Callbacks for MiraiCallbacks {
         }
 
         config.crate_cfg.insert(("mirai".to_string(), None));
+        // Register mirai as a tool, so that the crate can use attributes such as #[mirai::skip],
+        // typically via #[cfg_attr(mirai, mirai::skip)]. A crate that does this itself would
+        // fail to compile if the attributes were declared twice.
+        let source = crate_source(&config.input);
+        let crate_attr = &mut config.opts.debugging_opts.crate_attr;
+        if !FEATURE_REGISTER_TOOL.is_match(&source) {
+            crate_attr.push("feature(register_tool)".to_string());
+        }
+        if !REGISTER_TOOL_MIRAI.is_match(&source) {
+            crate_attr.push("register_tool(mirai)".to_string());
+        }
         match &config.output_dir {
             None => {
                 self.output_directory = std::env::temp_dir();
@@ -134,47 +162,14 @@ impl rustc_driver::Callbacks for MiraiCallbacks {
 }
 
 impl MiraiCallbacks {
-    fn is_test_black_listed(file_name: &str) -> bool {
-        file_name.contains("storage/storage-service/src") || file_name.contains("client/cli/src")
-    }
-
-    fn is_black_listed(file_name: &str) -> bool {
-        file_name.contains("admission-control/admission-control-proto/src") // resolve error
-            || file_name.contains("crypto/crypto/src") // false positives
-            || file_name.contains("crypto/crypto-derive/src") // false positives
-            || file_name.contains("common/bitvec/src") // false positives
-            || file_name.contains("common/bounded-executor/src") // false positive: possible assertion failed: ptr.as_ptr() as usize & NUM_FLAG == 0
-            || file_name.contains("common/debug-interface/src") // false positives
-            || file_name.contains("common/futures-semaphore/src") // false positive: possible assertion failed: ptr.as_ptr() as usize & NUM_FLAG == 0
-            || file_name.contains("common/metrics/src") // false positives
-            || file_name.contains("language/bytecode-verifier/src") // takes too long
-            || file_name.contains("language/compiler/bytecode-source-map/src") // false positives
-            || file_name.contains("language/compiler/ir-to-bytecode/syntax/src") // false positives
-            || file_name.contains("language/stdlib/src") // false positives
-            || file_name.contains("language/move-lang/src") // takes too long
-            || file_name.contains("language/move-vm/state/src") // false positives
-            || file_name.contains("language/vm/src") // takes too long
-            || file_name.contains("network/src") // false positives
-            || file_name.contains("client/cli/src") // takes too long
-            || file_name.contains("client/libra_wallet/src") // false positive: self.execute(offset, len, |buffer| dst[..len].copy_from_slice(buffer));
-            || file_name.contains("state-synchronizer/src") // false positives
-            || file_name.contains("storage/jellyfish-merkle/src") // false positives due to complex loops beyond what we can handle right now
-            || file_name.contains("storage/libradb/src") // takes too long
-            || file_name.contains("storage/schemadb/src") // takes too long
-            || file_name.contains("storage/scratchpad/src") // false positives
-            || file_name.contains("types/src") // takes too long
-    }
-
     /// Analyze the crate currently being compiled, using the information given in compiler and tcx.
     #[logfn(TRACE)]
     fn analyze_with_mirai<'tcx>(&mut self, compiler: &interface::Compiler, tcx: TyCtxt<'tcx>) {
-        if self.options.test_only {
-            if Self::is_test_black_listed(&self.file_name) {
-                return;
-            }
-        } else if Self::is_black_listed(&self.file_name) {
+        if let Some(exclusion) = self.options.exclusion_for_crate(&self.file_name) {
+            info!("skipping {}: {}", self.file_name, exclusion.reason);
             return;
         }
+        self.options.k_limits.install();
 
         let output_dir = String::from(self.output_directory.to_str().expect("valid string"));
         let summary_store_path = if std::env::var("MIRAI_SHARE_PERSISTENT_STORE").is_ok() {
@@ -187,14 +182,29 @@ impl MiraiCallbacks {
             "storing summaries for {} at {}/.summary_store.sled",
             self.file_name, summary_store_path
         );
+        let options = std::mem::take(&mut self.options);
+        // Records of earlier analyses are only available if the summary store outlives this run.
+        let incremental_analysis = if std::env::var("MIRAI_SHARE_PERSISTENT_STORE").is_ok()
+            && std::env::var("MIRAI_START_FRESH").is_err()
+            && !self.test_run
+        {
+            Some(IncrementalAnalysis::new(tcx, &options))
+        } else {
+            None
+        };
         let mut crate_visitor = CrateVisitor {
             buffered_diagnostics: Vec::new(),
+            call_graph: None,
             constant_value_cache: ConstantValueCache::default(),
             diagnostics_for: HashMap::new(),
             file_name: self.file_name.as_str(),
+            function_statistics: Vec::new(),
+            incremental_analysis,
             known_names_cache: KnownNamesCache::create_cache_from_language_items(),
-            options: &std::mem::take(&mut self.options),
+            options: &options,
             session: compiler.session(),
+            smt_dump_files: HashSet::new(),
+            smt_lib_solver: None,
             substs_cache: HashMap::new(),
             summary_cache: PersistentSummaryCache::new(tcx, summary_store_path),
             tcx,
@@ -203,3 +213,11 @@ impl MiraiCallbacks {
         crate_visitor.analyze_some_bodies();
     }
 }
+
+/// Returns the source text of the root module of the crate, or an empty string if it cannot be read.
+fn crate_source(input: &Input) -> String {
+    match input {
+        Input::File(path) => std::fs::read_to_string(path).unwrap_or_default(),
+        Input::Str { input, .. } => input.clone(),
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Support for reading a mirai.toml file that configures an analysis run. Such a file lists
+// crates and functions that should not be analyzed, along with the reasons for excluding them,
+// and can override the limits in k_limits. For example:
+//
+// [[exclude]]
+// path = "language/vm/src"
+// reason = "takes too long"
+//
+// [[exclude]]
+// function = "my_crate.foo.bar"
+// reason = "false positive: loop is too complex"
+// when = "not_test_only"
+//
+// [k_limits]
+// max_analysis_time_for_body = 60
+
+use crate::k_limits::KLimits;
+
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// The name of the configuration file that is looked for if no file is given explicitly.
+pub const CONFIG_FILE_NAME: &str = "mirai.toml";
+
+/// The contents of a mirai.toml file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub exclude: Vec<Exclusion>,
+    pub k_limits: KLimits,
+}
+
+/// Something that should not be analyzed.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Exclusion {
+    /// If present, only crates whose root source file path contains this string are excluded.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// If present, only the function with this summary key or simple name is excluded, rather
+    /// than the whole crate.
+    #[serde(default)]
+    pub function: Option<String>,
+    /// Why this should not be analyzed. Used for logging and to keep the file maintainable.
+    pub reason: String,
+    /// The kind of analysis run to which the exclusion applies.
+    #[serde(default)]
+    pub when: ExclusionMode,
+}
+
+/// Determines if an exclusion applies to runs with or without the --test_only option.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExclusionMode {
+    Always,
+    TestOnly,
+    NotTestOnly,
+}
+
+impl Default for ExclusionMode {
+    fn default() -> Self {
+        ExclusionMode::Always
+    }
+}
+
+impl Config {
+    /// Reads and validates the configuration file at the given path.
+    pub fn read_from(path: &Path) -> Result<Config, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        let config: Config = toml::from_str(&content)
+            .map_err(|e| format!("Cannot parse {}: {}", path.display(), e))?;
+        for exclusion in config.exclude.iter() {
+            if exclusion.path.is_none() && exclusion.function.is_none() {
+                return Err(format!(
+                    "Invalid exclusion in {}: one of path or function must be given",
+                    path.display()
+                ));
+            }
+        }
+        Ok(config)
+    }
+
+    /// Returns the path of the first mirai.toml file found in the given directory
+    /// or one of its ancestors.
+    pub fn find(directory: &Path) -> Option<PathBuf> {
+        directory
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILE_NAME))
+            .find(|file| file.is_file())
+    }
+}
+
+impl Exclusion {
+    /// True if this exclusion applies to the whole crate with the given root source file.
+    pub fn excludes_crate(&self, file_name: &str, test_only: bool) -> bool {
+        self.function.is_none() && self.applies_to(file_name, test_only)
+    }
+
+    /// True if this exclusion applies to the function with the given summary key or simple name,
+    /// which is part of the crate with the given root source file.
+    pub fn excludes_function(
+        &self,
+        summary_key: &str,
+        display_name: &str,
+        file_name: &str,
+        test_only: bool,
+    ) -> bool {
+        match &self.function {
+            Some(function) => {
+                (function == summary_key || function == display_name)
+                    && self.applies_to(file_name, test_only)
+            }
+            None => false,
+        }
+    }
+
+    fn applies_to(&self, file_name: &str, test_only: bool) -> bool {
+        let mode_applies = match self.when {
+            ExclusionMode::Always => true,
+            ExclusionMode::TestOnly => test_only,
+            ExclusionMode::NotTestOnly => !test_only,
+        };
+        mode_applies
+            && self
+                .path
+                .as_ref()
+                .map_or(true, |path| file_name.contains(path.as_str()))
+    }
+}
@@ -7,13 +7,25 @@
 // 'tcx is the lifetime of the type context created during the lifetime of the after_analysis call back.
 // 'analysis is the life time of the analyze_with_mirai call back that is invoked with the type context.
 
+use crate::baseline::{Baseline, Fingerprint};
 use crate::body_visitor::BodyVisitor;
-use crate::constant_domain::ConstantValueCache;
+use crate::call_graph::CallGraph;
+use crate::constant_domain::{ConstantDomain, ConstantValueCache};
 use crate::expected_errors;
+use crate::incremental::IncrementalAnalysis;
+use crate::instances;
+use crate::k_limits;
 use crate::known_names::KnownNamesCache;
 use crate::options::Options;
+use crate::sarif::{Rule, SarifLog};
+use crate::smt_lib_solver::SmtLibSolver;
+use crate::smt_solver::SmtSolver;
+#[cfg(not(feature = "z3-sys"))]
+use crate::smt_solver::SolverStub;
+use crate::statistics::{CountingSolver, CrateStatistics, FunctionStatistics};
 use crate::summaries::PersistentSummaryCache;
 use crate::utils;
+#[cfg(feature = "z3-sys")]
 use crate::z3_solver::Z3Solver;
 
 use log::*;
@@ -26,9 +38,11 @@ use rustc_middle::ty::subst::SubstsRef;
 use rustc_middle::ty::TyCtxt;
 use rustc_session::Session;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Formatter, Result};
 use std::ops::Deref;
+use std::path::PathBuf;
+use std::time::Instant;
 
 /// A visitor that takes information gathered by the Rust compiler when compiling a particular
 /// crate and then analyses some of the functions in that crate to see if any of the assertions
@@ -37,13 +51,18 @@ use std::ops::Deref;
 // 'compilation is the lifetime of the call to MiraiCallbacks::after_analysis.
 // 'tcx is the lifetime of the closure call that calls analyze_with_mirai, which calls analyze_some_bodies.
 pub struct CrateVisitor<'compilation, 'tcx> {
-    pub buffered_diagnostics: Vec<DiagnosticBuilder<'compilation>>,
+    pub buffered_diagnostics: Vec<(DiagnosticBuilder<'compilation>, Rule)>,
+    pub call_graph: Option<CallGraph>,
     pub constant_value_cache: ConstantValueCache<'tcx>,
-    pub diagnostics_for: HashMap<DefId, Vec<DiagnosticBuilder<'compilation>>>,
+    pub diagnostics_for: HashMap<DefId, Vec<(DiagnosticBuilder<'compilation>, Rule)>>,
     pub file_name: &'compilation str,
+    pub function_statistics: Vec<FunctionStatistics>,
+    pub incremental_analysis: Option<IncrementalAnalysis>,
     pub known_names_cache: KnownNamesCache,
     pub options: &'compilation Options,
     pub session: &'compilation Session,
+    pub smt_dump_files: HashSet<PathBuf>,
+    pub smt_lib_solver: Option<SmtLibSolver>,
     pub substs_cache: HashMap<DefId, SubstsRef<'tcx>>,
     pub summary_cache: PersistentSummaryCache<'tcx>,
     pub tcx: TyCtxt<'tcx>,
@@ -57,16 +76,48 @@ impl<'compilation, 'tcx> Debug for CrateVisitor<'compilation, 'tcx> {
 }
 
 impl<'compilation, 'tcx> CrateVisitor<'compilation, 'tcx> {
+    /// Returns the graph of direct calls between the function bodies of the crate, constructing
+    /// it on first use.
+    pub fn call_graph(&mut self) -> &CallGraph {
+        let tcx = self.tcx;
+        self.call_graph.get_or_insert_with(|| CallGraph::new(tcx))
+    }
+
     /// Analyze some of the bodies in the crate that is being compiled.
     #[logfn(TRACE)]
     pub fn analyze_some_bodies(&mut self) {
+        if let Some(command) = &self.options.smt_solver {
+            match SmtLibSolver::new(command, self.options.solver_limit()) {
+                Ok(smt_lib_solver) => self.smt_lib_solver = Some(smt_lib_solver),
+                Err(err) => {
+                    self.session.err(&format!(
+                        "unable to start the SMT solver `{}`: {}",
+                        command, err
+                    ));
+                    return;
+                }
+            }
+        }
         // Determine the functions we want to analyze.
         let function_whitelist = self.get_function_whitelist();
 
         // Analyze all functions that are white listed or public
         let building_standard_summaries = std::env::var("MIRAI_START_FRESH").is_ok();
+        let mut roots: Vec<DefId> = Vec::new();
         for def_id in self.tcx.body_owners() {
             let name = utils::summary_key_str(self.tcx, def_id);
+            let display_name = utils::def_id_display_name(self.tcx, def_id);
+            if let Some(exclusion) =
+                self.options
+                    .exclusion_for_function(name.as_str(), &display_name, self.file_name)
+            {
+                debug!("skipping function {}: {}", name, exclusion.reason);
+                continue;
+            }
+            if utils::has_mirai_attribute(self.tcx, def_id, "skip") {
+                debug!("skipping function {} as it is marked #[mirai::skip]", name);
+                continue;
+            }
             if let Some(white_list) = &function_whitelist {
                 if !self.included_in(white_list.as_ref(), name.as_str(), def_id) {
                     if self.options.single_func.is_none() {
@@ -79,7 +130,7 @@ impl<'compilation, 'tcx> CrateVisitor<'compilation, 'tcx> {
                 }
                 info!("analyzing function {}", name);
             } else if !building_standard_summaries {
-                if !utils::is_public(def_id, self.tcx) {
+                if !utils::is_public(def_id, self.tcx) && !self.is_private_root(def_id) {
                     debug!("skipping function {} as it is not public", name);
                     continue;
                 } else if self
@@ -93,11 +144,117 @@ impl<'compilation, 'tcx> CrateVisitor<'compilation, 'tcx> {
                     info!("analyzing function {}", name);
                 }
             }
-            self.analyze_body(def_id);
+            roots.push(def_id);
+        }
+        if self.options.bottom_up {
+            // Analyze callees before callers, so that callers can use the summaries of the callees.
+            roots = self
+                .call_graph()
+                .bottom_up_components(&roots)
+                .into_iter()
+                .flatten()
+                .collect();
+        }
+        self.analyze_roots(&roots);
+        if self.options.generic_instances {
+            let max_instances = k_limits::max_generic_instances();
+            for (def_id, generic_args) in
+                instances::collect_generic_instances(self.tcx, &roots, max_instances)
+            {
+                let name = utils::summary_key_str(self.tcx, def_id);
+                let display_name = utils::def_id_display_name(self.tcx, def_id);
+                if let Some(exclusion) = self.options.exclusion_for_function(
+                    name.as_str(),
+                    &display_name,
+                    self.file_name,
+                ) {
+                    debug!("skipping function {}: {}", name, exclusion.reason);
+                    continue;
+                }
+                if utils::has_mirai_attribute(self.tcx, def_id, "skip") {
+                    debug!("skipping function {} as it is marked #[mirai::skip]", name);
+                    continue;
+                }
+                info!("analyzing function {} with {:?}", name, generic_args);
+                self.analyze_root(def_id, Some(generic_args));
+            }
         }
+        if let Some(incremental_analysis) = &mut self.incremental_analysis {
+            incremental_analysis.save_records(
+                self.tcx,
+                &mut self.summary_cache,
+                &self.diagnostics_for,
+            );
+        }
+        self.write_function_statistics();
         self.emit_or_check_diagnostics();
     }
 
+    /// Analyze the given roots, other than those that incremental analysis finds to be up to date.
+    /// Since the analysis of a root can change summaries in the summary store that the analyses of
+    /// other roots used, or that were relied upon to find them up to date, this is repeated until
+    /// no such summaries change.
+    #[logfn(TRACE)]
+    fn analyze_roots(&mut self, roots: &[DefId]) {
+        let mut analyzed: HashSet<DefId> = HashSet::new();
+        let mut stale: HashSet<DefId> = HashSet::new();
+        for iteration in 0..=k_limits::max_fixpoint_iterations() {
+            if iteration > 0 {
+                // Summaries computed or obtained so far may be based on the old store summaries.
+                self.summary_cache.clear_transient_caches();
+            }
+            let mut analyzed_some_root = false;
+            for def_id in roots.iter().cloned() {
+                if analyzed.contains(&def_id) && !stale.contains(&def_id) {
+                    continue;
+                }
+                let name = utils::summary_key_str(self.tcx, def_id);
+                if let Some(incremental_analysis) = &mut self.incremental_analysis {
+                    if !stale.contains(&def_id)
+                        && incremental_analysis.analysis_is_up_to_date(
+                            self.tcx,
+                            &self.summary_cache,
+                            &name,
+                        )
+                    {
+                        debug!(
+                            "skipping function {} as neither it nor its dependencies have changed",
+                            name
+                        );
+                        continue;
+                    }
+                }
+                if analyzed.contains(&def_id) {
+                    debug!(
+                        "analyzing function {} again as summaries it used have changed",
+                        name
+                    );
+                    self.diagnostics_for.remove(&def_id);
+                    self.function_statistics
+                        .retain(|s| s.summary_key != *name || s.generic_arguments.is_some());
+                } else {
+                    analyzed.insert(def_id);
+                }
+                self.analyze_root(def_id, None);
+                analyzed_some_root = true;
+            }
+            if !analyzed_some_root {
+                return;
+            }
+            stale = if let Some(incremental_analysis) = &self.incremental_analysis {
+                let analyzed: Vec<DefId> = analyzed.iter().cloned().collect();
+                incremental_analysis
+                    .roots_with_changed_summaries(&self.summary_cache, &analyzed)
+                    .into_iter()
+                    .collect()
+            } else {
+                // Without a shared summary store there is nothing to be stale with respect to.
+                return;
+            };
+        }
+        warn!("the summaries used by analysis roots did not reach a fixed point");
+    }
+
     /// Use compilation options to determine a list of functions to analyze.
     /// If this returns None, default logic is used by the caller.
     #[logfn(TRACE)]
@@ -133,30 +290,191 @@ impl<'compilation, 'tcx> CrateVisitor<'compilation, 'tcx> {
             .any(|e| e.eq(&display_name) || e.eq(name))
     }
 
+    // Determine whether this private function is selected as an analysis root by the options.
+    #[logfn(TRACE)]
+    fn is_private_root(&self, def_id: DefId) -> bool {
+        if !self.options.private_roots {
+            return false;
+        }
+        if let Some(module) = &self.options.private_roots_module {
+            let module = module.trim_start_matches("crate::");
+            let path = self.tcx.def_path_str(def_id);
+            let path = path.trim_start_matches("crate::");
+            path.starts_with(module) && path[module.len()..].starts_with("::")
+        } else {
+            true
+        }
+    }
+
+    /// Analyze the given function, or the given instance of a generic function, using the SMT
+    /// solver selected by the options.
+    #[logfn(TRACE)]
+    fn analyze_root(&mut self, def_id: DefId, generic_args: Option<SubstsRef<'tcx>>) {
+        if let Some(mut smt_lib_solver) = self.smt_lib_solver.take() {
+            // The solver process is shared by all roots, so get rid of the previous root's state.
+            smt_lib_solver.reset();
+            self.analyze_root_with_solver(def_id, generic_args, &mut smt_lib_solver);
+            self.smt_lib_solver = Some(smt_lib_solver);
+        } else {
+            self.analyze_root_with_default_solver(def_id, generic_args);
+        }
+    }
+
+    #[cfg(feature = "z3-sys")]
+    fn analyze_root_with_default_solver(
+        &mut self,
+        def_id: DefId,
+        generic_args: Option<SubstsRef<'tcx>>,
+    ) {
+        let mut z3_solver = Z3Solver::new(self.options.solver_limit());
+        self.analyze_root_with_solver(def_id, generic_args, &mut z3_solver);
+    }
+
+    // Without Z3 and without an external solver, every query to the solver is undecided.
+    #[cfg(not(feature = "z3-sys"))]
+    fn analyze_root_with_default_solver(
+        &mut self,
+        def_id: DefId,
+        generic_args: Option<SubstsRef<'tcx>>,
+    ) {
+        let mut solver_stub = SolverStub::default();
+        self.analyze_root_with_solver(def_id, generic_args, &mut solver_stub);
+    }
+
+    fn analyze_root_with_solver<E>(
+        &mut self,
+        def_id: DefId,
+        generic_args: Option<SubstsRef<'tcx>>,
+        smt_solver: &mut dyn SmtSolver<E>,
+    ) {
+        if let Some(generic_args) = generic_args {
+            self.analyze_generic_instance(def_id, generic_args, smt_solver);
+        } else {
+            self.analyze_body(def_id, smt_solver);
+        }
+    }
+
     /// Run the abstract interpreter over the function body and produce a summary of its effects
     /// and collect any diagnostics into the buffer.
     #[logfn(TRACE)]
-    fn analyze_body(&mut self, def_id: DefId) {
-        let mut diagnostics: Vec<DiagnosticBuilder<'compilation>> = Vec::new();
+    fn analyze_body<E>(&mut self, def_id: DefId, smt_solver: &mut dyn SmtSolver<E>) {
+        let start_instant = Instant::now();
+        let mut diagnostics: Vec<(DiagnosticBuilder<'compilation>, Rule)> = Vec::new();
         let mut active_calls: Vec<DefId> = Vec::new();
-        let mut z3_solver = Z3Solver::default();
+        let mut smt_solver = CountingSolver::new(smt_solver);
         self.constant_value_cache.reset_heap_counter();
         let mut body_visitor = BodyVisitor::new(
             self,
             def_id,
-            &mut z3_solver,
+            &mut smt_solver,
             &mut diagnostics,
             &mut active_calls,
         );
         // Analysis local foreign contracts are not summarized and cached on demand, so we need to do it here.
         let summary = body_visitor.visit_body(&[], &[]);
+        let function_statistics = FunctionStatistics::for_body(&body_visitor);
+        self.add_function_statistics(function_statistics, start_instant, &smt_solver);
         if utils::is_foreign_contract(self.tcx, def_id) {
             self.summary_cache.set_summary_for(def_id, summary);
+        } else if self.options.bottom_up {
+            // Callers are analyzed later on and can use the summary of this function rather than
+            // computing it again. The summary has the preconditions that the function would
+            // require of its callers if it were not a root (see root_summary_is_reused).
+            let ty = self.tcx.type_of(def_id);
+            let func_const = self.constant_value_cache.get_function_constant_for(
+                def_id,
+                ty,
+                self.tcx.empty_substs_for_def_id(def_id),
+                self.tcx,
+                &mut self.known_names_cache,
+                &mut self.summary_cache,
+            );
+            if let ConstantDomain::Function(func_ref) = func_const {
+                let func_ref = func_ref.clone();
+                self.summary_cache
+                    .set_summary_for_call_site(&func_ref, None, summary);
+            }
         }
         let old_diags = self.diagnostics_for.insert(def_id, diagnostics);
         checked_assume!(old_diags.is_none());
     }
 
+    /// Run the abstract interpreter over the body of a generic function, using the given concrete
+    /// generic arguments, and collect any diagnostics into the buffer. Since the diagnostics are
+    /// reported against the generic source, each of them gets a note with the generic arguments.
+    #[logfn(TRACE)]
+    fn analyze_generic_instance<E>(
+        &mut self,
+        def_id: DefId,
+        generic_args: SubstsRef<'tcx>,
+        smt_solver: &mut dyn SmtSolver<E>,
+    ) {
+        let start_instant = Instant::now();
+        let mut diagnostics: Vec<(DiagnosticBuilder<'compilation>, Rule)> = Vec::new();
+        let mut active_calls: Vec<DefId> = Vec::new();
+        let mut smt_solver = CountingSolver::new(smt_solver);
+        self.constant_value_cache.reset_heap_counter();
+        let mut body_visitor = BodyVisitor::new(
+            self,
+            def_id,
+            &mut smt_solver,
+            &mut diagnostics,
+            &mut active_calls,
+        );
+        body_visitor.type_visitor.generic_arguments = Some(generic_args);
+        body_visitor.type_visitor.generic_argument_map = body_visitor
+            .type_visitor
+            .get_generic_arguments_map(def_id, generic_args, &[]);
+        body_visitor.visit_body(&[], &[]);
+        let mut function_statistics = FunctionStatistics::for_body(&body_visitor);
+        let generic_arguments =
+            instances::describe_generic_arguments(self.tcx, def_id, generic_args);
+        let note = format!("instantiated with {}", generic_arguments);
+        function_statistics.generic_arguments = Some(generic_arguments);
+        self.add_function_statistics(function_statistics, start_instant, &smt_solver);
+        for (diagnostic, _) in diagnostics.iter_mut() {
+            diagnostic.note(&note);
+        }
+        self.diagnostics_for
+            .entry(def_id)
+            .or_insert_with(Vec::new)
+            .extend(diagnostics.into_iter());
+    }
+
+    /// Completes the statistics of a function that was analyzed from start_instant until now
+    /// and keeps them for the report, if one was asked for.
+    fn add_function_statistics<E>(
+        &mut self,
+        mut function_statistics: FunctionStatistics,
+        start_instant: Instant,
+        smt_solver: &CountingSolver<'_, E>,
+    ) {
+        if self.options.statistics_file.is_some() {
+            function_statistics.set_analysis_time(start_instant.elapsed());
+            function_statistics.solver_queries = smt_solver.statistics();
+            self.function_statistics.push(function_statistics);
+        }
+    }
+
+    /// Writes the statistics of the analyzed functions to the file given by the options.
+    fn write_function_statistics(&self) {
+        if let Some(statistics_file) = &self.options.statistics_file {
+            let statistics = CrateStatistics {
+                crate_name: self.tcx.crate_name(LOCAL_CRATE).to_string(),
+                functions: &self.function_statistics,
+            };
+            let statistics_path = output_path(statistics_file, self.tcx, "statistics.json");
+            info!("writing statistics to {}", statistics_path.display());
+            if let Err(err) = statistics.write_to(&statistics_path) {
+                self.session.warn(&format!(
+                    "unable to write statistics to {}: {}",
+                    statistics_path.display(),
+                    err
+                ));
+            }
+        }
+    }
+
     /// Extract test functions from the promoted constants of a test runner main function.
     ///
     /// Currently, the #[test] attribute generates code like this:
@@ -212,22 +530,31 @@ impl<'compilation, 'tcx> CrateVisitor<'compilation, 'tcx> {
         if self.test_run {
             let mut expected_errors = expected_errors::ExpectedErrors::new(self.file_name);
             let mut diags = vec![];
-            self.diagnostics_for.values_mut().flatten().for_each(|db| {
-                db.cancel();
-                db.clone().buffer(&mut diags)
-            });
+            self.diagnostics_for
+                .values_mut()
+                .flatten()
+                .for_each(|(db, _)| {
+                    db.cancel();
+                    db.clone().buffer(&mut diags)
+                });
             if !expected_errors.check_messages(diags) {
                 self.session.fatal("test failed");
             }
         } else {
-            let mut diagnostics: Vec<&mut DiagnosticBuilder<'_>> =
-                self.diagnostics_for.values_mut().flatten().collect();
+            let tcx = self.tcx;
+            let mut diagnostics: Vec<(DefId, &mut DiagnosticBuilder<'_>, Rule)> = self
+                .diagnostics_for
+                .iter_mut()
+                .flat_map(|(def_id, dbs)| {
+                    dbs.iter_mut().map(move |(db, rule)| (*def_id, db, *rule))
+                })
+                .collect();
             fn compare_diagnostics<'a>(
-                x: &&mut DiagnosticBuilder<'a>,
-                y: &&mut DiagnosticBuilder<'a>,
+                x: &(DefId, &mut DiagnosticBuilder<'a>, Rule),
+                y: &(DefId, &mut DiagnosticBuilder<'a>, Rule),
             ) -> Ordering {
-                let xd: &Diagnostic = x.deref();
-                let yd: &Diagnostic = y.deref();
+                let xd: &Diagnostic = x.1.deref();
+                let yd: &Diagnostic = y.1.deref();
                 if xd.span.primary_spans().lt(&yd.span.primary_spans()) {
                     Ordering::Less
                 } else if xd.span.primary_spans().gt(&yd.span.primary_spans()) {
@@ -237,10 +564,81 @@ impl<'compilation, 'tcx> CrateVisitor<'compilation, 'tcx> {
                 }
             }
             diagnostics.sort_by(compare_diagnostics);
-            fn emit(db: &mut DiagnosticBuilder<'_>) {
-                db.emit();
+            if let Some(baseline_file) = &self.options.baseline_file {
+                let source_map = self.session.source_map();
+                let baseline_path = output_path(baseline_file, tcx, "baseline.json");
+                if self.options.write_baseline {
+                    let mut baseline = Baseline::default();
+                    for (def_id, db, _) in diagnostics.into_iter() {
+                        let summary_key = utils::summary_key_str(tcx, def_id);
+                        baseline.add(Fingerprint::new(&summary_key, db, source_map));
+                        db.cancel();
+                    }
+                    info!("writing baseline to {}", baseline_path.display());
+                    if let Err(err) = baseline.write_to(&baseline_path) {
+                        self.session.warn(&format!(
+                            "unable to write baseline to {}: {}",
+                            baseline_path.display(),
+                            err
+                        ));
+                    }
+                    return;
+                }
+                match Baseline::read_from(&baseline_path) {
+                    Ok(mut baseline) => {
+                        let mut new_diagnostics = Vec::with_capacity(diagnostics.len());
+                        for (def_id, db, rule) in diagnostics.into_iter() {
+                            let summary_key = utils::summary_key_str(tcx, def_id);
+                            let fingerprint = Fingerprint::new(&summary_key, db, source_map);
+                            if baseline.suppress(&fingerprint) {
+                                db.cancel();
+                            } else {
+                                new_diagnostics.push((def_id, db, rule));
+                            }
+                        }
+                        diagnostics = new_diagnostics;
+                    }
+                    Err(err) => {
+                        self.session.warn(&format!(
+                            "unable to read baseline from {}: {}",
+                            baseline_path.display(),
+                            err
+                        ));
+                    }
+                }
+            }
+            if let Some(sarif_file) = &self.options.sarif_file {
+                let mut log = SarifLog::new();
+                for (def_id, db, rule) in diagnostics.into_iter() {
+                    let summary_key = utils::summary_key_str(tcx, def_id);
+                    log.add_result(&summary_key, db, rule, self.session.source_map());
+                    db.cancel();
+                }
+                let sarif_path = output_path(sarif_file, tcx, "sarif");
+                info!("writing SARIF log to {}", sarif_path.display());
+                if let Err(err) = log.write_to(&sarif_path) {
+                    self.session.warn(&format!(
+                        "unable to write SARIF log to {}: {}",
+                        sarif_path.display(),
+                        err
+                    ));
+                }
+            } else {
+                fn emit(db: &mut DiagnosticBuilder<'_>) {
+                    db.emit();
+                }
+                diagnostics.into_iter().for_each(|(_, db, _)| emit(db));
             }
-            diagnostics.into_iter().for_each(emit);
         }
     }
 }
+
+// If the given file name is a directory, returns the path of a file in that directory
+// that is named after the crate being analyzed and has the given extension.
+fn output_path(file_name: &str, tcx: TyCtxt<'_>, extension: &str) -> PathBuf {
+    let mut path = PathBuf::from(file_name);
+    if path.is_dir() {
+        path.push(format!("{}.{}", tcx.crate_name(LOCAL_CRATE), extension));
+    }
+    path
+}
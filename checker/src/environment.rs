@@ -7,6 +7,7 @@ use crate::abstract_value;
 use crate::abstract_value::AbstractValue;
 use crate::abstract_value::AbstractValueTrait;
 use crate::expression::Expression;
+use crate::k_limits;
 use crate::path::{Path, PathEnum, PathRefinement};
 
 use log_derive::{logfn, logfn_inputs};
@@ -87,6 +88,54 @@ impl Environment {
         self.value_map = self.value_map.insert(path, value);
     }
 
+    /// Returns a value that is true if the value at the given path, or the value of any of its
+    /// parts, is tainted with the given kind of taint. If the value is a reference, the value
+    /// that it references is checked instead. Returns None if there are no values for the path.
+    #[logfn_inputs(TRACE)]
+    pub fn has_taint_at(&self, path: &Rc<Path>, kind: &Rc<String>) -> Option<Rc<AbstractValue>> {
+        self.has_taint_at_depth(path, kind, 0)
+    }
+
+    /// Helper for has_taint_at that gives up on chains of references that are too long.
+    #[logfn_inputs(TRACE)]
+    fn has_taint_at_depth(
+        &self,
+        path: &Rc<Path>,
+        kind: &Rc<String>,
+        depth: usize,
+    ) -> Option<Rc<AbstractValue>> {
+        if let Some(value) = self.value_at(path) {
+            match &value.expression {
+                Expression::Reference(target) => {
+                    if depth < k_limits::max_refine_depth() {
+                        return self.has_taint_at_depth(target, kind, depth + 1);
+                    }
+                    return None;
+                }
+                Expression::Sanitized { .. } | Expression::Tainted { .. } => {
+                    // Taint that was added to, or removed from, the value as a whole
+                    // overrides the taint of its parts.
+                    let has_taint = value.has_taint(kind);
+                    if has_taint.as_bool_if_known().is_some() {
+                        return Some(has_taint);
+                    }
+                }
+                _ => {}
+            }
+        }
+        let mut result: Option<Rc<AbstractValue>> = None;
+        for (part_path, part_value) in self.value_map.iter() {
+            if *part_path == *path || part_path.is_rooted_by(path) {
+                let part_has_taint = part_value.has_taint(kind);
+                result = Some(match result {
+                    Some(has_taint) => has_taint.or(part_has_taint),
+                    None => part_has_taint,
+                });
+            }
+        }
+        result
+    }
+
     /// If the path contains an abstract value that was constructed with a join, the path is
     /// concretized into two paths where the abstract value is replaced by the consequent
     /// and alternate, respectively. These paths can then be weakly updated to reflect the
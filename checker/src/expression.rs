@@ -377,6 +377,49 @@ pub enum Expression {
         /// all result in the same widened value.
         operand: Rc<AbstractValue>,
     },
+
+    // Variants added after summaries started to be persisted go here, rather than in
+    // alphabetical order, so that the (bincode) encoding of the older variants does not change.
+    /// An expression that is true if condition is true for every (or some) value of the bound
+    /// variable in the range lower_bound..upper_bound. forall!, exists!
+    Quantifier {
+        // Whether the condition must hold for every value or for some value.
+        kind: QuantifierKind,
+        // The path of the bound variable, which is of type usize. Occurrences of the bound
+        // variable in condition are Variable expressions with this path.
+        variable: Rc<Path>,
+        // The value of the inclusive lower bound of the range.
+        lower_bound: Rc<AbstractValue>,
+        // The value of the exclusive upper bound of the range.
+        upper_bound: Rc<AbstractValue>,
+        // The value of the quantified condition.
+        condition: Rc<AbstractValue>,
+    },
+
+    /// An expression that is true if the value of the operand has been tainted with the given
+    /// kind of taint, either directly or because it was computed from a tainted value.
+    HasTaint {
+        // The value that may be tainted.
+        operand: Rc<AbstractValue>,
+        // The name of the kind of taint.
+        kind: Rc<String>,
+    },
+
+    /// The value of the operand, with any taint of the given kind removed. remove_taint!
+    Sanitized {
+        // The value that is sanitized.
+        operand: Rc<AbstractValue>,
+        // The name of the kind of taint that is removed.
+        kind: Rc<String>,
+    },
+
+    /// The value of the operand, tainted with the given kind of taint. add_taint!
+    Tainted {
+        // The value that is tainted.
+        operand: Rc<AbstractValue>,
+        // The name of the kind of taint that is added.
+        kind: Rc<String>,
+    },
 }
 
 /// Used by Expression::AbstractHeapBlockLayout
@@ -387,6 +430,13 @@ pub enum LayoutSource {
     ReAlloc,
 }
 
+/// Used by Expression::Quantifier
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum QuantifierKind {
+    Exists,
+    ForAll,
+}
+
 impl Debug for Expression {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match self {
@@ -455,6 +505,9 @@ impl Debug for Expression {
             Expression::IntrinsicBinary { left, right, name } => {
                 f.write_fmt(format_args!("({:?}).{:?}({:?})", left, name, right))
             }
+            Expression::HasTaint { operand, kind } => {
+                f.write_fmt(format_args!("has_taint({:?}, {})", operand, kind))
+            }
             Expression::IntrinsicBitVectorUnary {
                 operand,
                 bit_length,
@@ -493,10 +546,23 @@ impl Debug for Expression {
             Expression::Offset { left, right } => {
                 f.write_fmt(format_args!("&({:?})[{:?}]", left, right))
             }
+            Expression::Quantifier {
+                kind,
+                variable,
+                lower_bound,
+                upper_bound,
+                condition,
+            } => f.write_fmt(format_args!(
+                "{:?}({:?} in ({:?})..({:?}), {:?})",
+                kind, variable, lower_bound, upper_bound, condition
+            )),
             Expression::Reference(path) => f.write_fmt(format_args!("&({:?})", path)),
             Expression::Rem { left, right } => {
                 f.write_fmt(format_args!("({:?}) % ({:?})", left, right))
             }
+            Expression::Sanitized { operand, kind } => {
+                f.write_fmt(format_args!("remove_taint({:?}, {})", operand, kind))
+            }
             Expression::Shl { left, right } => {
                 f.write_fmt(format_args!("({:?}) << ({:?})", left, right))
             }
@@ -515,6 +581,9 @@ impl Debug for Expression {
             Expression::SubOverflows { left, right, .. } => {
                 f.write_fmt(format_args!("overflows(({:?}) - ({:?}))", left, right))
             }
+            Expression::Tainted { operand, kind } => {
+                f.write_fmt(format_args!("add_taint({:?}, {})", operand, kind))
+            }
             Expression::UninterpretedCall {
                 callee,
                 arguments,
@@ -559,6 +628,7 @@ impl Expression {
             Expression::BitAnd { left, .. } => left.expression.infer_type(),
             Expression::BitNot { result_type, .. } => result_type.clone(),
             Expression::BitOr { left, .. } => left.expression.infer_type(),
+            Expression::HasTaint { .. } => Bool,
             Expression::HeapBlock { .. } => NonPrimitive,
             Expression::HeapBlockLayout { .. } => NonPrimitive,
             Expression::IntrinsicBinary { left, name, .. } => match name {
@@ -665,14 +735,17 @@ impl Expression {
             Expression::Neg { operand } => operand.expression.infer_type(),
             Expression::Or { .. } => Bool,
             Expression::Offset { .. } => Reference,
+            Expression::Quantifier { .. } => Bool,
             Expression::Reference(_) => Reference,
             Expression::Rem { left, .. } => left.expression.infer_type(),
+            Expression::Sanitized { operand, .. } => operand.expression.infer_type(),
             Expression::Shl { left, .. } => left.expression.infer_type(),
             Expression::ShlOverflows { .. } => Bool,
             Expression::Shr { result_type, .. } => result_type.clone(),
             Expression::ShrOverflows { .. } => Bool,
             Expression::Sub { left, .. } => left.expression.infer_type(),
             Expression::SubOverflows { .. } => Bool,
+            Expression::Tainted { operand, .. } => operand.expression.infer_type(),
             Expression::UninterpretedCall { result_type, .. } => result_type.clone(),
             Expression::UnknownModelField { default, .. } => default.expression.infer_type(),
             Expression::Variable { var_type, .. } => var_type.clone(),
@@ -755,9 +828,23 @@ impl Expression {
                 left.expression.record_heap_blocks(result);
                 right.expression.record_heap_blocks(result);
             }
-            Expression::Neg { operand } | Expression::LogicalNot { operand } => {
+            Expression::HasTaint { operand, .. }
+            | Expression::Neg { operand }
+            | Expression::LogicalNot { operand }
+            | Expression::Sanitized { operand, .. }
+            | Expression::Tainted { operand, .. } => {
                 operand.expression.record_heap_blocks(result);
             }
+            Expression::Quantifier {
+                lower_bound,
+                upper_bound,
+                condition,
+                ..
+            } => {
+                lower_bound.expression.record_heap_blocks(result);
+                upper_bound.expression.record_heap_blocks(result);
+                condition.expression.record_heap_blocks(result);
+            }
             Expression::Reference(path) => path.record_heap_blocks(result),
             Expression::Variable { path, .. } => path.record_heap_blocks(result),
             _ => (),
@@ -0,0 +1,373 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Support for skipping the analysis of functions that have not changed since they were last
+// analyzed, as outlined in documentation/IncrementalAnalysis.md.
+//
+// Whenever a function body is analyzed, a record is kept of a hash of its MIR, of the local
+// functions whose summaries it used (these are summarized from their own MIR and so are tracked
+// via their own records) and of hashes of the summaries it obtained from the summary store.
+// On a later run, a public function need not be analyzed again if it did not give rise to any
+// diagnostics and none of these have changed, transitively. Since local functions can call
+// each other recursively, the records are followed until no new ones are reached.
+//
+// Analyzing a function can also change summaries in the store (the summaries of foreign
+// contracts, for instance), which other functions of the same crate may already have used
+// during the current run, or have used during an earlier run and hence been skipped on the
+// strength of. The caller therefore repeats the analysis of such functions until none of the
+// summaries that they used have changed.
+
+use crate::constant_domain::FunctionReference;
+use crate::options::Options;
+use crate::sarif;
+use crate::summaries::{PersistentSummaryCache, Summary};
+use crate::utils;
+
+use log_derive::{logfn, logfn_inputs};
+use rustc_data_structures::stable_hasher::{HashStable, StableHasher};
+use rustc_errors::DiagnosticBuilder;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir;
+use rustc_middle::ty::TyCtxt;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Formatter, Result};
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+/// What is known about the most recent analysis of a function body.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct BodyRecord {
+    /// A hash of the options that were used for the analysis and of the versions of the crates
+    /// that the analyzed crate depends on.
+    pub configuration_hash: u64,
+    /// A hash of the MIR of the function body and its promoted constants.
+    pub mir_hash: u64,
+    /// The summary keys of the functions from the current crate whose summaries were used.
+    pub local_callees: Vec<Rc<String>>,
+    /// The summaries that were obtained from the summary store.
+    pub consumed_summaries: Vec<ConsumedSummary>,
+    /// True if the analysis of the body as an analysis root gave rise to diagnostics.
+    pub had_diagnostics: bool,
+}
+
+/// Identifies a summary obtained from the summary store, along with a hash of its value.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ConsumedSummary {
+    pub summary_key: Rc<String>,
+    pub argument_type_key: Rc<String>,
+    pub summary_hash: u64,
+}
+
+/// The dependencies of a function body that are collected while it is being analyzed.
+#[derive(Debug, Default)]
+struct BodyDependencies {
+    local_callees: HashSet<DefId>,
+    /// Maps the summary key and argument type key of a summary obtained from the summary store
+    /// to the hash of the summary when it was most recently obtained.
+    consumed_summaries: HashMap<(Rc<String>, Rc<String>), u64>,
+}
+
+/// Tracks the dependencies of the bodies analyzed during the current run and decides
+/// which bodies need not be analyzed at all.
+pub struct IncrementalAnalysis {
+    configuration_hash: u64,
+    dependencies: HashMap<DefId, BodyDependencies>,
+    local_bodies: HashMap<Rc<String>, DefId>,
+    mir_hashes: HashMap<DefId, u64>,
+}
+
+impl Debug for IncrementalAnalysis {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        "IncrementalAnalysis".fmt(f)
+    }
+}
+
+impl IncrementalAnalysis {
+    /// Prepares for an incremental analysis of the crate with the given type context.
+    #[logfn(TRACE)]
+    pub fn new(tcx: TyCtxt<'_>, options: &Options) -> IncrementalAnalysis {
+        let local_bodies = tcx
+            .body_owners()
+            .map(|def_id| (utils::summary_key_str(tcx, def_id), def_id))
+            .collect();
+        // Summaries computed from the bodies of other crates can change when those crates do.
+        let mut configuration = format!("{:?}", options);
+        for crate_num in tcx.crates().iter() {
+            configuration.push_str(&format!(
+                " {}:{}",
+                tcx.crate_name(*crate_num),
+                tcx.crate_hash(*crate_num)
+            ));
+        }
+        IncrementalAnalysis {
+            configuration_hash: hash_str(&configuration),
+            dependencies: HashMap::new(),
+            local_bodies,
+            mir_hashes: HashMap::new(),
+        }
+    }
+
+    /// Records that the body of def_id is about to be analyzed, while the bodies in
+    /// active_calls are being analyzed.
+    #[logfn_inputs(TRACE)]
+    pub fn record_visit(&mut self, active_calls: &[DefId], def_id: DefId) {
+        if !def_id.is_local() {
+            // Changes to the bodies of other crates are covered by the configuration hash.
+            return;
+        }
+        self.dependencies.entry(def_id).or_default();
+        if let Some(caller) = Self::innermost_local(active_calls) {
+            self.record_local_callee(caller, def_id);
+        }
+    }
+
+    /// Records that the given summary of func_ref was used while the bodies in active_calls
+    /// are being analyzed. The use is attributed to the innermost local body.
+    #[logfn_inputs(TRACE)]
+    pub fn record_summary_use(
+        &mut self,
+        active_calls: &[DefId],
+        func_ref: &FunctionReference,
+        summary: &Summary,
+    ) {
+        match func_ref.def_id {
+            Some(callee) if callee.is_local() => self.record_local_use(active_calls, callee),
+            _ => self.record_stored_summary_use(
+                active_calls,
+                &func_ref.summary_cache_key,
+                &func_ref.argument_type_key,
+                summary,
+            ),
+        }
+    }
+
+    /// Records that the summary of the local function callee was used while the bodies in
+    /// active_calls are being analyzed.
+    #[logfn_inputs(TRACE)]
+    pub fn record_local_use(&mut self, active_calls: &[DefId], callee: DefId) {
+        if let Some(def_id) = Self::innermost_local(active_calls) {
+            if callee.is_local() {
+                self.record_local_callee(def_id, callee);
+            }
+        }
+    }
+
+    /// Records that the given summary, obtained from the summary store using the given keys,
+    /// was used while the bodies in active_calls are being analyzed.
+    #[logfn_inputs(TRACE)]
+    pub fn record_stored_summary_use(
+        &mut self,
+        active_calls: &[DefId],
+        summary_key: &Rc<String>,
+        argument_type_key: &Rc<String>,
+        summary: &Summary,
+    ) {
+        if let Some(def_id) = Self::innermost_local(active_calls) {
+            self.dependencies
+                .entry(def_id)
+                .or_default()
+                .consumed_summaries
+                .insert(
+                    (summary_key.clone(), argument_type_key.clone()),
+                    hash_summary(summary),
+                );
+        }
+    }
+
+    fn innermost_local(active_calls: &[DefId]) -> Option<DefId> {
+        active_calls.iter().rev().find(|d| d.is_local()).cloned()
+    }
+
+    fn record_local_callee(&mut self, def_id: DefId, callee: DefId) {
+        if def_id != callee {
+            self.dependencies
+                .entry(def_id)
+                .or_default()
+                .local_callees
+                .insert(callee);
+        }
+    }
+
+    /// Returns true if the body of the given analysis root was analyzed before without giving
+    /// rise to diagnostics and neither its MIR nor anything it depends on has changed since.
+    #[logfn(TRACE)]
+    pub fn analysis_is_up_to_date(
+        &mut self,
+        tcx: TyCtxt<'_>,
+        summary_cache: &PersistentSummaryCache<'_>,
+        root_key: &Rc<String>,
+    ) -> bool {
+        let mut visited: HashSet<Rc<String>> = HashSet::new();
+        let mut worklist = vec![root_key.clone()];
+        while let Some(key) = worklist.pop() {
+            if !visited.insert(key.clone()) {
+                continue;
+            }
+            let def_id = if let Some(def_id) = self.local_bodies.get(&key) {
+                *def_id
+            } else {
+                // A local function without a body has a summary that cannot change.
+                continue;
+            };
+            let record = if let Some(record) = summary_cache.get_body_record(&key) {
+                record
+            } else {
+                return false;
+            };
+            if record.configuration_hash != self.configuration_hash
+                || (key == *root_key && record.had_diagnostics)
+                || record.mir_hash != self.get_mir_hash(tcx, def_id)
+            {
+                return false;
+            }
+            for consumed in record.consumed_summaries.iter() {
+                let summary_hash = stored_summary_hash(
+                    summary_cache,
+                    &consumed.summary_key,
+                    &consumed.argument_type_key,
+                );
+                if summary_hash != consumed.summary_hash {
+                    return false;
+                }
+            }
+            worklist.extend(record.local_callees.into_iter());
+        }
+        true
+    }
+
+    /// Returns the given analysis roots, which have been analyzed during this run, that
+    /// directly or via local callees used summaries from the summary store that have since
+    /// been changed, for example by the analysis of later roots.
+    #[logfn(TRACE)]
+    pub fn roots_with_changed_summaries(
+        &self,
+        summary_cache: &PersistentSummaryCache<'_>,
+        roots: &[DefId],
+    ) -> Vec<DefId> {
+        let mut current: HashMap<&(Rc<String>, Rc<String>), bool> = HashMap::new();
+        let mut is_current = |consumed_summary: &(Rc<String>, Rc<String>), hash: u64| -> bool {
+            *current.entry(consumed_summary).or_insert_with(|| {
+                let (summary_key, argument_type_key) = consumed_summary;
+                stored_summary_hash(summary_cache, summary_key, argument_type_key) == hash
+            })
+        };
+        let mut result = Vec::new();
+        for root in roots.iter() {
+            let mut visited: HashSet<DefId> = HashSet::new();
+            let mut worklist = vec![*root];
+            while let Some(def_id) = worklist.pop() {
+                if !visited.insert(def_id) {
+                    continue;
+                }
+                if let Some(dependencies) = self.dependencies.get(&def_id) {
+                    if !dependencies
+                        .consumed_summaries
+                        .iter()
+                        .all(|(consumed_summary, hash)| is_current(consumed_summary, *hash))
+                    {
+                        result.push(*root);
+                        break;
+                    }
+                    worklist.extend(dependencies.local_callees.iter());
+                }
+            }
+        }
+        result
+    }
+
+    /// Updates the records of all bodies analyzed during this run.
+    #[logfn(TRACE)]
+    pub fn save_records<'compilation>(
+        &mut self,
+        tcx: TyCtxt<'_>,
+        summary_cache: &mut PersistentSummaryCache<'_>,
+        diagnostics_for: &HashMap<DefId, Vec<(DiagnosticBuilder<'compilation>, sarif::Rule)>>,
+    ) {
+        let def_ids: Vec<DefId> = self.dependencies.keys().cloned().collect();
+        for def_id in def_ids {
+            let mir_hash = self.get_mir_hash(tcx, def_id);
+            let dependencies = &self.dependencies[&def_id];
+            let mut local_callees: Vec<Rc<String>> = dependencies
+                .local_callees
+                .iter()
+                .map(|callee| summary_cache.get_summary_key_for(*callee).clone())
+                .collect();
+            local_callees.sort();
+            let mut consumed_summaries: Vec<ConsumedSummary> = dependencies
+                .consumed_summaries
+                .iter()
+                .map(
+                    |((summary_key, argument_type_key), summary_hash)| ConsumedSummary {
+                        summary_key: summary_key.clone(),
+                        argument_type_key: argument_type_key.clone(),
+                        summary_hash: *summary_hash,
+                    },
+                )
+                .collect();
+            consumed_summaries.sort();
+            let record = BodyRecord {
+                configuration_hash: self.configuration_hash,
+                mir_hash,
+                local_callees,
+                consumed_summaries,
+                had_diagnostics: diagnostics_for
+                    .get(&def_id)
+                    .map_or(false, |diagnostics| !diagnostics.is_empty()),
+            };
+            let key = summary_cache.get_summary_key_for(def_id).clone();
+            summary_cache.set_body_record(&key, &record);
+        }
+    }
+
+    /// Returns a hash of the MIR of the given function body and its promoted constants that does
+    /// not depend on source locations, so that edits elsewhere in the source file do not cause
+    /// the body to be analyzed again. Other definitions are identified by their def path hashes,
+    /// which are stable across compilations.
+    fn get_mir_hash(&mut self, tcx: TyCtxt<'_>, def_id: DefId) -> u64 {
+        *self.mir_hashes.entry(def_id).or_insert_with(|| {
+            if !tcx.is_mir_available(def_id) {
+                return 0;
+            }
+            let mut hasher = StableHasher::new();
+            tcx.def_path_hash(def_id).hash(&mut hasher);
+            let mut hcx = tcx.create_stable_hashing_context();
+            hcx.while_hashing_spans(false, |hcx| {
+                let body: &mir::Body<'_> = tcx.optimized_mir(def_id);
+                body.hash_stable(hcx, &mut hasher);
+                for promoted in tcx.promoted_mir(def_id).iter() {
+                    promoted.hash_stable(hcx, &mut hasher);
+                }
+            });
+            hasher.finish::<u64>()
+        })
+    }
+}
+
+/// Returns the hash of the summary that is currently in the summary store for the given keys.
+fn stored_summary_hash(
+    summary_cache: &PersistentSummaryCache<'_>,
+    summary_key: &str,
+    argument_type_key: &str,
+) -> u64 {
+    let summary = summary_cache
+        .get_persistent_summary_using_arg_types_if_possible(summary_key, argument_type_key)
+        .unwrap_or_else(|| summary_cache.get_persistent_summary_for(summary_key));
+    hash_summary(&summary)
+}
+
+/// Returns a hash of the persisted form of the given summary.
+pub fn hash_summary(summary: &Summary) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bincode::serialize(summary).unwrap().hash(&mut hasher);
+    hasher.finish()
+}
+
+fn hash_str(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
@@ -0,0 +1,121 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Support for analyzing generic functions of the crate via the concrete instances in which
+// they are used. Generic functions are not analysis roots, since the analysis of a body that
+// has type parameters is necessarily imprecise. Instead, the calls made by the analysis roots
+// are followed to find the generic functions they use, along with the generic arguments they
+// use them with, and each such instance is then analyzed as if it were a root.
+
+use crate::utils;
+
+use log_derive::logfn_inputs;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir;
+use rustc_middle::ty::subst::{GenericArgKind, InternalSubsts, Subst, SubstsRef};
+use rustc_middle::ty::{Instance, InstanceDef, ParamEnv, TyCtxt, TyKind, TypeFoldable};
+use std::collections::{HashMap, HashSet};
+
+/// Returns the instances of the generic functions of the local crate that are called, directly
+/// or indirectly, by the given analysis roots, in the order in which they were found.
+/// At most max_instances instances are returned for each generic function, which also serves to
+/// bound the search if a generic function calls itself with ever larger generic arguments.
+#[logfn_inputs(TRACE)]
+pub fn collect_generic_instances<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    roots: &[DefId],
+    max_instances: usize,
+) -> Vec<(DefId, SubstsRef<'tcx>)> {
+    let mut result: Vec<(DefId, SubstsRef<'tcx>)> = Vec::new();
+    let mut instance_counts: HashMap<DefId, usize> = HashMap::new();
+    let mut visited: HashSet<(DefId, SubstsRef<'tcx>)> = HashSet::new();
+    let mut worklist: Vec<(DefId, SubstsRef<'tcx>)> = roots
+        .iter()
+        .map(|def_id| (*def_id, InternalSubsts::identity_for_item(tcx, *def_id)))
+        .collect();
+    while let Some((def_id, generic_args)) = worklist.pop() {
+        if !visited.insert((def_id, generic_args)) || !tcx.is_mir_available(def_id) {
+            continue;
+        }
+        let body: &mir::Body<'tcx> = tcx.optimized_mir(def_id);
+        for block in body.basic_blocks().iter() {
+            let func = if let mir::TerminatorKind::Call { func, .. } = &block.terminator().kind {
+                func
+            } else {
+                continue;
+            };
+            let (callee_def_id, callee_generic_args) = match func.ty(body, tcx).kind {
+                TyKind::FnDef(callee_def_id, callee_generic_args) => {
+                    (callee_def_id, callee_generic_args)
+                }
+                _ => continue,
+            };
+            let callee_generic_args = callee_generic_args.subst(tcx, generic_args);
+            if callee_generic_args.needs_subst() {
+                // The call depends on the generic arguments of a root that is itself generic.
+                continue;
+            }
+            let callee_generic_args =
+                tcx.normalize_erasing_regions(ParamEnv::reveal_all(), callee_generic_args);
+            let instance = if let Some(instance) = Instance::resolve(
+                tcx,
+                ParamEnv::reveal_all(),
+                callee_def_id,
+                callee_generic_args,
+            ) {
+                instance
+            } else {
+                continue;
+            };
+            let resolved_def_id = if let InstanceDef::Item(def_id) = instance.def {
+                def_id
+            } else {
+                continue;
+            };
+            if !resolved_def_id.is_local() || visited.contains(&(resolved_def_id, instance.substs))
+            {
+                continue;
+            }
+            if tcx
+                .generics_of(resolved_def_id)
+                .requires_monomorphization(tcx)
+            {
+                if !utils::are_concrete(instance.substs)
+                    || result.contains(&(resolved_def_id, instance.substs))
+                {
+                    continue;
+                }
+                let count = instance_counts.entry(resolved_def_id).or_insert(0);
+                if *count >= max_instances {
+                    continue;
+                }
+                *count += 1;
+                result.push((resolved_def_id, instance.substs));
+            }
+            worklist.push((resolved_def_id, instance.substs));
+        }
+    }
+    result
+}
+
+/// Returns a description of the generic arguments of an instance of the given generic function,
+/// such as "T = i32, U = bool", for use in diagnostics.
+#[logfn_inputs(TRACE)]
+pub fn describe_generic_arguments<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    def_id: DefId,
+    generic_args: SubstsRef<'tcx>,
+) -> String {
+    let mut bindings: Vec<String> = Vec::new();
+    InternalSubsts::for_item(tcx, def_id, |param_def, _| {
+        if let Some(gen_arg) = generic_args.get(param_def.index as usize) {
+            if let GenericArgKind::Type(ty) = gen_arg.unpack() {
+                bindings.push(format!("{} = {}", param_def.name, ty));
+            }
+        }
+        tcx.mk_param_from_def(param_def) // not used
+    });
+    bindings.join(", ")
+}
@@ -6,12 +6,32 @@
 // Somewhat arbitrary constants used to limit things in the abstract interpreter that may
 // take too long or use too much memory.
 
+use serde::Deserialize;
+use std::cell::Cell;
+
 /// The maximum number of seconds that MIRAI is willing to analyze a function body for.
 pub const MAX_ANALYSIS_TIME_FOR_BODY: u64 = 20;
 
+/// The maximum number of milliseconds that the built-in Z3 solver is allowed to spend on a query.
+pub const MAX_SOLVER_TIME_IN_MILLISECONDS: u64 = 100;
+
+/// The maximum number of resource units (rlimit) that the built-in Z3 solver is allowed to spend
+/// on a query, if the analysis is limited by fuel rather than by time.
+pub const MAX_SOLVER_RESOURCES_FOR_QUERY: u64 = 1_000_000;
+
+/// The maximum number of steps that MIRAI is willing to take when analyzing a function body,
+/// if the analysis is limited by fuel rather than by time.
+pub const MAX_FUEL_FOR_BODY: u64 = 500_000;
+
+/// The number of steps that a query to the SMT solver counts as, when the analysis is limited by fuel.
+pub const FUEL_FOR_SOLVER_QUERY: u64 = 10;
+
 /// The maximum number of elements in a byte array that will be individually tracked.
 pub const MAX_BYTE_ARRAY_LENGTH: usize = 10;
 
+/// The maximum number of instances of a generic function that are analyzed.
+pub const MAX_GENERIC_INSTANCES: usize = 10;
+
 /// Helps to limit the size of summaries.
 pub const MAX_INFERRED_PRECONDITIONS: usize = 50;
 
@@ -26,3 +46,113 @@ pub const MAX_PATH_LENGTH: usize = 30;
 
 /// Refining values with a path condition that is a really deep expression leads to exponential blow up.
 pub const MAX_REFINE_DEPTH: usize = 9;
+
+/// The values of the limits that are used by an analysis run. By default these are the constants
+/// above, but each of them can be overridden in the [k_limits] table of a mirai.toml file.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct KLimits {
+    pub max_analysis_time_for_body: u64,
+    pub max_fuel_for_body: u64,
+    pub max_solver_time_in_milliseconds: u64,
+    pub max_solver_resources_for_query: u64,
+    pub max_byte_array_length: usize,
+    pub max_generic_instances: usize,
+    pub max_inferred_preconditions: usize,
+    pub max_expression_size: u64,
+    pub max_fixpoint_iterations: usize,
+    pub max_path_length: usize,
+    pub max_refine_depth: usize,
+}
+
+impl Default for KLimits {
+    fn default() -> Self {
+        KLimits {
+            max_analysis_time_for_body: MAX_ANALYSIS_TIME_FOR_BODY,
+            max_fuel_for_body: MAX_FUEL_FOR_BODY,
+            max_solver_time_in_milliseconds: MAX_SOLVER_TIME_IN_MILLISECONDS,
+            max_solver_resources_for_query: MAX_SOLVER_RESOURCES_FOR_QUERY,
+            max_byte_array_length: MAX_BYTE_ARRAY_LENGTH,
+            max_generic_instances: MAX_GENERIC_INSTANCES,
+            max_inferred_preconditions: MAX_INFERRED_PRECONDITIONS,
+            max_expression_size: MAX_EXPRESSION_SIZE,
+            max_fixpoint_iterations: MAX_FIXPOINT_ITERATIONS,
+            max_path_length: MAX_PATH_LENGTH,
+            max_refine_depth: MAX_REFINE_DEPTH,
+        }
+    }
+}
+
+// Abstract values and paths are constructed without access to the options of the analysis run,
+// so the limits are copied into this variable by KLimits::install and all of the analysis code
+// reads them with the functions below, rather than from the options.
+// It is thread local because the test runner analyzes several crates concurrently.
+thread_local! {
+    static INSTALLED_LIMITS: Cell<KLimits> = Cell::new(KLimits::default());
+}
+
+impl KLimits {
+    /// Makes these limits the ones used by the analysis on the current thread.
+    pub fn install(&self) {
+        INSTALLED_LIMITS.with(|limits| limits.set(*self));
+    }
+}
+
+fn installed_limits() -> KLimits {
+    INSTALLED_LIMITS.with(Cell::get)
+}
+
+/// The installed value of KLimits::max_analysis_time_for_body.
+pub fn max_analysis_time_for_body() -> u64 {
+    installed_limits().max_analysis_time_for_body
+}
+
+/// The installed value of KLimits::max_fuel_for_body.
+pub fn max_fuel_for_body() -> u64 {
+    installed_limits().max_fuel_for_body
+}
+
+/// The installed value of KLimits::max_solver_time_in_milliseconds.
+pub fn max_solver_time_in_milliseconds() -> u64 {
+    installed_limits().max_solver_time_in_milliseconds
+}
+
+/// The installed value of KLimits::max_solver_resources_for_query.
+pub fn max_solver_resources_for_query() -> u64 {
+    installed_limits().max_solver_resources_for_query
+}
+
+/// The installed value of KLimits::max_byte_array_length.
+pub fn max_byte_array_length() -> usize {
+    installed_limits().max_byte_array_length
+}
+
+/// The installed value of KLimits::max_generic_instances.
+pub fn max_generic_instances() -> usize {
+    installed_limits().max_generic_instances
+}
+
+/// The installed value of KLimits::max_inferred_preconditions.
+pub fn max_inferred_preconditions() -> usize {
+    installed_limits().max_inferred_preconditions
+}
+
+/// The installed value of KLimits::max_expression_size.
+pub fn max_expression_size() -> u64 {
+    installed_limits().max_expression_size
+}
+
+/// The installed value of KLimits::max_fixpoint_iterations.
+pub fn max_fixpoint_iterations() -> usize {
+    installed_limits().max_fixpoint_iterations
+}
+
+/// The installed value of KLimits::max_path_length.
+pub fn max_path_length() -> usize {
+    installed_limits().max_path_length
+}
+
+/// The installed value of KLimits::max_refine_depth.
+pub fn max_refine_depth() -> usize {
+    installed_limits().max_refine_depth
+}
@@ -94,6 +94,19 @@ pub enum KnownNames {
     StdOpsFunctionFnOnceCallOnce,
     StdPanickingBeginPanic,
     StdPanickingBeginPanicFmt,
+
+    // Names added after summaries started to be persisted go here, rather than in alphabetical
+    // order, so that the (bincode) encoding of the older names does not change.
+    MiraiAddTaint,
+    MiraiDecreases,
+    MiraiExists,
+    MiraiForall,
+    MiraiHasTaint,
+    MiraiLoopInvariant,
+    MiraiModifies,
+    MiraiOld,
+    MiraiRemoveTaint,
+    MiraiTaintSink,
 }
 
 /// An analysis lifetime cache that contains a map from def ids to known names.
@@ -314,15 +327,25 @@ impl KnownNamesCache {
                     "ops" => get_known_name_for_ops_namespace(def_path_data_iter),
                     "panicking" => get_known_name_for_panicking_namespace(def_path_data_iter),
                     "mirai_abstract_value" => KnownNames::MiraiAbstractValue,
+                    "mirai_add_taint" => KnownNames::MiraiAddTaint,
                     "mirai_assume" => KnownNames::MiraiAssume,
                     "mirai_assume_preconditions" => KnownNames::MiraiAssumePreconditions,
+                    "mirai_decreases" => KnownNames::MiraiDecreases,
+                    "mirai_exists" => KnownNames::MiraiExists,
+                    "mirai_forall" => KnownNames::MiraiForall,
                     "mirai_get_model_field" => KnownNames::MiraiGetModelField,
+                    "mirai_has_taint" => KnownNames::MiraiHasTaint,
+                    "mirai_loop_invariant" => KnownNames::MiraiLoopInvariant,
+                    "mirai_modifies" => KnownNames::MiraiModifies,
+                    "mirai_old" => KnownNames::MiraiOld,
                     "mirai_postcondition" => KnownNames::MiraiPostcondition,
                     "mirai_precondition_start" => KnownNames::MiraiPreconditionStart,
                     "mirai_precondition" => KnownNames::MiraiPrecondition,
+                    "mirai_remove_taint" => KnownNames::MiraiRemoveTaint,
                     "mirai_result" => KnownNames::MiraiResult,
                     "mirai_set_model_field" => KnownNames::MiraiSetModelField,
                     "mirai_shallow_clone" => KnownNames::MiraiShallowClone,
+                    "mirai_taint_sink" => KnownNames::MiraiTaintSink,
                     "mirai_verify" => KnownNames::MiraiVerify,
                     _ => KnownNames::None,
                 })
@@ -35,56 +35,66 @@ extern crate rustc_target;
 extern crate log;
 
 /// If the currently analyzed function has been marked as angelic because was discovered
-/// to do something that cannot be analyzed, or if the time taken to analyze the current
-/// function exceeded k_limits::MAX_ANALYSIS_TIME_FOR_BODY, break out of the current loop.
-/// When a timeout happens, currently analyzed function is marked as angelic.
+/// to do something that cannot be analyzed, or if the analysis of the current function has
+/// used up its budget (time or fuel, see Options::budget), break out of the current loop.
+/// When the budget runs out, currently analyzed function is marked as angelic.
 macro_rules! check_for_early_break {
     ($sel:expr) => {
         if $sel.assume_function_is_angelic {
             break;
         }
-        let elapsed_time_in_seconds = $sel.start_instant.elapsed().as_secs();
-        if elapsed_time_in_seconds >= k_limits::MAX_ANALYSIS_TIME_FOR_BODY {
-            $sel.assume_function_is_angelic = true;
+        if $sel.analysis_budget_is_exhausted() {
+            let reason = $sel.budget_exhausted_reason();
+            $sel.make_angelic(reason);
             break;
         }
     };
 }
 
 /// If the currently analyzed function has been marked as angelic because was discovered
-/// to do something that cannot be analyzed, or if the time taken to analyze the current
-/// function exceeded k_limits::MAX_ANALYSIS_TIME_FOR_BODY, return to the caller.
-/// When a timeout happens, currently analyzed function is marked as angelic.
+/// to do something that cannot be analyzed, or if the analysis of the current function has
+/// used up its budget (time or fuel, see Options::budget), return to the caller.
+/// When the budget runs out, currently analyzed function is marked as angelic.
 macro_rules! check_for_early_return {
     ($sel:expr) => {
         if $sel.assume_function_is_angelic {
             return;
         }
-        let elapsed_time_in_seconds = $sel.start_instant.elapsed().as_secs();
-        if elapsed_time_in_seconds >= k_limits::MAX_ANALYSIS_TIME_FOR_BODY {
-            $sel.assume_function_is_angelic = true;
+        if $sel.analysis_budget_is_exhausted() {
+            let reason = $sel.budget_exhausted_reason();
+            $sel.make_angelic(reason);
             return;
         }
     };
 }
 
 pub mod abstract_value;
+pub mod baseline;
 pub mod block_visitor;
 pub mod body_visitor;
+pub mod call_graph;
 pub mod call_visitor;
 pub mod callbacks;
+pub mod config;
 pub mod constant_domain;
 pub mod crate_visitor;
 pub mod environment;
 pub mod expected_errors;
 pub mod expression;
+pub mod incremental;
+pub mod instances;
 pub mod interval_domain;
 pub mod k_limits;
 pub mod known_names;
 pub mod options;
 pub mod path;
+pub mod sarif;
+pub mod smt_dump;
+pub mod smt_lib_solver;
 pub mod smt_solver;
+pub mod statistics;
 pub mod summaries;
 pub mod type_visitor;
 pub mod utils;
+#[cfg(feature = "z3-sys")]
 pub mod z3_solver;
@@ -3,11 +3,16 @@
 // This source code is licensed under the MIT license found in the
 // LICENSE file in the root directory of this source tree.
 
+use crate::config::{Config, Exclusion};
+use crate::k_limits::KLimits;
+use crate::smt_solver::SolverLimit;
+
 use clap::{App, AppSettings, Arg, Error, ErrorKind};
 use itertools::Itertools;
 use mirai_annotations::*;
 use rustc_session::config::ErrorOutputType;
 use rustc_session::early_error;
+use std::path::PathBuf;
 
 /// Creates the clap::App metadata for argument parsing.
 fn make_options_parser<'a>() -> App<'a, 'a> {
@@ -33,6 +38,79 @@ fn make_options_parser<'a>() -> App<'a, 'a> {
         .default_value("relaxed")
         .help("Level of diagnostics.\n")
         .long_help("With `relaxed`, false positives will be avoided where possible.\nWith 'strict' optimistic assumptions are made about unanalyzable calls.\nWith `paranoid`, all errors will be reported.\n"))
+    .arg(Arg::with_name("budget")
+        .long("budget")
+        .possible_values(&["time", "fuel"])
+        .takes_value(true)
+        .help("The kind of budget that limits the analysis of a function body.\n")
+        .long_help("With `time` (the default), the analysis of a function body is abandoned after max_analysis_time_for_body seconds.\nWith `fuel`, it is abandoned after max_fuel_for_body steps, where a step is the analysis of a statement or terminator, the refinement of a value with a path condition, or part of an SMT solver query. This makes analysis results the same on fast and slow machines.\n"))
+    .arg(Arg::with_name("config")
+        .long("config")
+        .takes_value(true)
+        .help("Read exclusions and k-limits from the given mirai.toml file.")
+        .long_help("If not given, the first mirai.toml file found in the directory of the Cargo manifest of the crate being compiled, or in one of its ancestors, is used."))
+    .arg(Arg::with_name("private_roots")
+        .long("private_roots")
+        .takes_value(false)
+        .help("Analyze private functions as roots, rather than only public functions.")
+        .long_help("Possible failures in private functions are reported directly, rather than being promoted to preconditions of their callers. Diagnostics that would otherwise have been promoted get a note to say so, which distinguishes them from errors that are local to the function."))
+    .arg(Arg::with_name("private_roots_in")
+        .long("private_roots_in")
+        .takes_value(true)
+        .value_name("module path")
+        .help("Analyze the private functions in the given module as roots.")
+        .long_help("Like --private_roots, but only for private functions whose path, such as `my_module::helper`, starts with the given module path."))
+    .arg(Arg::with_name("bottom_up")
+        .long("bottom_up")
+        .takes_value(false)
+        .help("Analyze functions in bottom up order of the call graph.")
+        .long_help("The strongly connected components of the call graph of the crate are analyzed so that callees come before their callers. The summaries of analyzed functions are then reused when analyzing their callers. The components are analyzed sequentially, since the compiler's type context and the abstract values of MIRAI cannot be shared between threads."))
+    .arg(Arg::with_name("generic_instances")
+        .long("generic_instances")
+        .takes_value(false)
+        .help("Analyze the instances of generic functions that are used by analyzed functions.")
+        .long_help("Generic functions are not analyzed by themselves. With this option, the concrete instantiations of generic functions that are reachable from the analyzed functions are each analyzed with their generic arguments. Diagnostics are reported against the generic source, with a note giving the generic arguments."))
+    .arg(Arg::with_name("counterexamples")
+        .long("counterexamples")
+        .takes_value(false)
+        .help("Add concrete values that lead to a possible failure to diagnostics.")
+        .long_help("When the SMT solver finds that an assertion or precondition might fail, the satisfying assignment is reported as a note, with parameters and variables referred to by their source names."))
+    .arg(Arg::with_name("baseline")
+        .long("baseline")
+        .takes_value(true)
+        .help("Suppress diagnostics that are recorded in the given baseline file.")
+        .long_help("Only diagnostics that do not match a fingerprint in the given baseline file are reported. If the path is a directory, the file <crate name>.baseline.json in that directory is used."))
+    .arg(Arg::with_name("write_baseline")
+        .long("write_baseline")
+        .takes_value(false)
+        .requires("baseline")
+        .help("Record all diagnostics in the baseline file, rather than reporting them.")
+        .long_help("Writes the fingerprints (function summary key, message and source text) of all diagnostics to the file given by --baseline, replacing its contents. No diagnostics are reported."))
+    .arg(Arg::with_name("statistics")
+        .long("statistics")
+        .takes_value(true)
+        .help("Write statistics about the analysis of each function to the given file.")
+        .long_help("For every analyzed function, the JSON file lists the analysis time, the number of basic blocks and fixed point iterations, the outcomes of the SMT solver queries and whether the function was assumed to be correct without being fully analyzed, and why. If the path is an existing directory, the file <crate name>.statistics.json inside it is used."))
+    .arg(Arg::with_name("sarif")
+        .long("sarif")
+        .takes_value(true)
+        .help("Write diagnostics to the given file as a SARIF log.")
+        .long_help("Diagnostics are written as a SARIF 2.1.0 log rather than to stderr. If the path is an existing directory, the log is written to <crate name>.sarif inside it."))
+    .arg(Arg::with_name("smt_solver")
+        .long("smt_solver")
+        .takes_value(true)
+        .help("Use the given SMT solver executable, rather than the built in Z3 library.")
+        .long_help("The value is the path of a solver executable, followed by the arguments that make it read SMT-LIB2 commands from its standard input, for example \"z3 -in\", \"cvc5 --incremental\" or \"yices-smt2 --incremental\". MIRAI asks the solver to give up on a query after max_solver_time_in_milliseconds with (set-option :timeout), or after max_solver_resources_for_query with (set-option :rlimit) if the budget is fuel, and restarts it if it does not respond shortly after. Solvers that do not understand these options need time limits of their own, which are passed in the same way as other arguments.\n"))
+    .arg(Arg::with_name("no_smt_cache")
+        .long("no_smt_cache")
+        .takes_value(false)
+        .help("Do not use or update the cache of SMT solver results.")
+        .long_help("Every query is given to the SMT solver, rather than being answered from the results cached in the summary store, which is useful when the solver is suspected of giving wrong answers, or when comparing solvers."))
+    .arg(Arg::with_name("smt_dump")
+        .long("smt_dump")
+        .takes_value(true)
+        .help("Write the SMT solver queries of each function to an SMT-LIB2 file in the given directory.")
+        .long_help("Every query made to decide a condition is written to <summary key>.smt2 in the given directory, preceded by comments that give the source location of the condition and the decision that was taken. Each query is enclosed in (push 1) and (pop 1), so that the file can be given to an SMT solver as is."))
 }
 
 /// Represents options passed to MIRAI.
@@ -41,6 +119,22 @@ pub struct Options {
     pub single_func: Option<String>,
     pub test_only: bool,
     pub diag_level: DiagLevel,
+    pub budget: AnalysisBudget,
+    pub private_roots: bool,
+    pub private_roots_module: Option<String>,
+    pub bottom_up: bool,
+    pub generic_instances: bool,
+    pub counterexamples: bool,
+    pub baseline_file: Option<String>,
+    pub write_baseline: bool,
+    pub sarif_file: Option<String>,
+    pub statistics_file: Option<String>,
+    pub smt_dump_dir: Option<String>,
+    pub smt_solver: Option<String>,
+    pub no_smt_cache: bool,
+    pub config_file: Option<String>,
+    pub exclusions: Vec<Exclusion>,
+    pub k_limits: KLimits,
 }
 
 /// Represents diag level.
@@ -69,6 +163,23 @@ impl Default for DiagLevel {
     }
 }
 
+/// Represents the kind of budget that limits the analysis of a function body.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AnalysisBudget {
+    /// Analysis of a function body stops once it has taken longer than the
+    /// max_analysis_time_for_body k-limit. The results can depend on the speed and load of the machine.
+    Time,
+    /// Analysis of a function body stops once it has taken more than max_fuel_for_body steps.
+    /// The results are the same on every machine.
+    Fuel,
+}
+
+impl Default for AnalysisBudget {
+    fn default() -> Self {
+        AnalysisBudget::Time
+    }
+}
+
 impl Options {
     /// Parse options from an argument string. The argument string will be split using unix
     /// shell escaping rules. Any content beyond the leftmost `--` token will be returned
@@ -138,6 +249,119 @@ impl Options {
                 _ => assume_unreachable!(),
             };
         }
+        if matches.is_present("budget") {
+            self.budget = match matches.value_of("budget").unwrap() {
+                "time" => AnalysisBudget::Time,
+                "fuel" => AnalysisBudget::Fuel,
+                _ => assume_unreachable!(),
+            };
+        }
+        if matches.is_present("config") {
+            self.config_file = matches.value_of("config").map(|s| s.to_string());
+        }
+        if matches.is_present("private_roots") {
+            self.private_roots = true;
+        }
+        if matches.is_present("private_roots_in") {
+            self.private_roots = true;
+            self.private_roots_module = matches.value_of("private_roots_in").map(|s| s.to_string());
+        }
+        if matches.is_present("bottom_up") {
+            self.bottom_up = true;
+        }
+        if matches.is_present("generic_instances") {
+            self.generic_instances = true;
+        }
+        if matches.is_present("counterexamples") {
+            self.counterexamples = true;
+        }
+        if matches.is_present("baseline") {
+            self.baseline_file = matches.value_of("baseline").map(|s| s.to_string());
+        }
+        if matches.is_present("write_baseline") {
+            self.write_baseline = true;
+        }
+        if matches.is_present("sarif") {
+            self.sarif_file = matches.value_of("sarif").map(|s| s.to_string());
+        }
+        if matches.is_present("statistics") {
+            self.statistics_file = matches.value_of("statistics").map(|s| s.to_string());
+        }
+        if matches.is_present("smt_dump") {
+            self.smt_dump_dir = matches.value_of("smt_dump").map(|s| s.to_string());
+        }
+        if matches.is_present("smt_solver") {
+            self.smt_solver = matches.value_of("smt_solver").map(|s| s.to_string());
+        }
+        if matches.is_present("no_smt_cache") {
+            self.no_smt_cache = true;
+        }
         args[rustc_args_start..].to_vec()
     }
+
+    /// Reads the configuration file given by the config option or, if there is no such option,
+    /// the first mirai.toml file found in the directory of the Cargo manifest of the crate being
+    /// compiled (or the current directory) or one of its ancestors. The exclusions and k-limits
+    /// found in the file become part of these options.
+    pub fn read_config_file(&mut self) {
+        let path = match &self.config_file {
+            Some(file_name) => Some(PathBuf::from(file_name)),
+            None => {
+                let directory = std::env::var("CARGO_MANIFEST_DIR")
+                    .map(PathBuf::from)
+                    .or_else(|_| std::env::current_dir())
+                    .unwrap_or_default();
+                Config::find(&directory)
+            }
+        };
+        if let Some(path) = path {
+            let config = Config::read_from(&path)
+                .unwrap_or_else(|msg| early_error(ErrorOutputType::default(), &msg));
+            info!("read configuration from {}", path.display());
+            self.exclusions = config.exclude;
+            self.k_limits = config.k_limits;
+        }
+    }
+
+    /// Returns the first exclusion that applies to the crate with the given root source file.
+    pub fn exclusion_for_crate(&self, file_name: &str) -> Option<&Exclusion> {
+        self.exclusions
+            .iter()
+            .find(|e| e.excludes_crate(file_name, self.test_only))
+    }
+
+    /// Returns the first exclusion that applies to the function with the given summary key or
+    /// simple name, which is part of the crate with the given root source file.
+    pub fn exclusion_for_function(
+        &self,
+        summary_key: &str,
+        display_name: &str,
+        file_name: &str,
+    ) -> Option<&Exclusion> {
+        self.exclusions
+            .iter()
+            .find(|e| e.excludes_function(summary_key, display_name, file_name, self.test_only))
+    }
+
+    /// Identifies the solver that answers SMT queries, for use in the keys of the SMT result cache.
+    pub fn smt_backend(&self) -> &str {
+        match &self.smt_solver {
+            Some(command) => command,
+            None if cfg!(feature = "z3-sys") => "z3 library",
+            None => "none",
+        }
+    }
+
+    /// Returns the limit on the effort of the solver for a single query. If the analysis is
+    /// limited by fuel, so is the solver, so that its results do not depend on the machine.
+    pub fn solver_limit(&self) -> SolverLimit {
+        match self.budget {
+            AnalysisBudget::Time => {
+                SolverLimit::Time(self.k_limits.max_solver_time_in_milliseconds)
+            }
+            AnalysisBudget::Fuel => {
+                SolverLimit::Resources(self.k_limits.max_solver_resources_for_query)
+            }
+        }
+    }
 }
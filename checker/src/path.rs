@@ -275,6 +275,16 @@ impl Path {
         }
     }
 
+    /// True if path is a static variable, or a qualified path rooted by a static variable.
+    #[logfn_inputs(TRACE)]
+    pub fn is_rooted_by_static(&self) -> bool {
+        match &self.value {
+            PathEnum::QualifiedPath { qualifier, .. } => qualifier.is_rooted_by_static(),
+            PathEnum::StaticVariable { .. } => true,
+            _ => false,
+        }
+    }
+
     // Returns the length of the path.
     #[logfn_inputs(TRACE)]
     pub fn path_length(&self) -> usize {
@@ -385,7 +395,7 @@ impl Path {
             }
         }
         let qualifier_length = qualifier.path_length();
-        if qualifier_length >= k_limits::MAX_PATH_LENGTH {
+        if qualifier_length >= k_limits::max_path_length() {
             warn!("max path length exceeded {:?}.{:?}", qualifier, selector);
         }
         assume!(qualifier_length < 1_000_000_000); // We'll run out of memory long before this happens
@@ -0,0 +1,323 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Support for writing MIRAI diagnostics as a SARIF 2.1.0 log, so that results can be consumed by
+// code review tools and tracked over time without having to scrape human readable output.
+// See https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html for the format.
+
+use log_derive::logfn_inputs;
+use rustc_errors::{Diagnostic, Level};
+use rustc_span::source_map::SourceMap;
+use rustc_span::{MultiSpan, Span};
+use serde::Serialize;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::rc::Rc;
+
+/// The rules that MIRAI diagnostics are classified into. Each entry is a rule id along with
+/// a short description of the kind of problem reported by diagnostics with that id.
+/// The entries are in the same order as the variants of Rule.
+pub const RULES: &[(&str, &str)] = &[
+    (
+        "analysis_timeout",
+        "The analysis of a function took too long and was abandoned.",
+    ),
+    (
+        "conditional_precondition",
+        "A precondition is not reached unconditionally.",
+    ),
+    (
+        "deallocated_memory",
+        "Memory is used or deallocated after having been deallocated.",
+    ),
+    (
+        "false_verification_condition",
+        "A verification condition is provably false.",
+    ),
+    (
+        "inconsistent_layout",
+        "Memory is deallocated or reallocated with a layout that differs from its allocation.",
+    ),
+    (
+        "inconsistent_post_conditions",
+        "Post conditions are declared on different execution paths.",
+    ),
+    (
+        "inline_assembly",
+        "Inline assembly cannot be analyzed and is ignored.",
+    ),
+    (
+        "invalid_annotation",
+        "An annotation is used in a way that MIRAI does not support.",
+    ),
+    (
+        "missing_summary",
+        "A called function could not be summarized.",
+    ),
+    (
+        "offset_out_of_range",
+        "A pointer offset is outside of the allocated range.",
+    ),
+    (
+        "possible_error",
+        "An assertion, precondition or other runtime check might fail.",
+    ),
+    (
+        "redundant_assumption",
+        "An assumption is provably true and can be deleted.",
+    ),
+    (
+        "unreachable_annotation",
+        "An annotation is unreachable and should be replaced with an unreachable marker.",
+    ),
+    (
+        "definite_error",
+        "An assertion, precondition or other runtime check will fail if reached.",
+    ),
+];
+
+/// The kind of a MIRAI diagnostic. Every diagnostic is given its rule where it is issued and the
+/// rule is buffered along with the diagnostic, so that the SARIF log can classify it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Rule {
+    AnalysisTimeout,
+    ConditionalPrecondition,
+    DeallocatedMemory,
+    FalseVerificationCondition,
+    InconsistentLayout,
+    InconsistentPostConditions,
+    InlineAssembly,
+    InvalidAnnotation,
+    MissingSummary,
+    OffsetOutOfRange,
+    PossibleError,
+    RedundantAssumption,
+    UnreachableAnnotation,
+    DefiniteError,
+}
+
+impl Rule {
+    /// The rule id, which is used in SARIF logs and by #[mirai::allow(..)] attributes.
+    pub fn id(self) -> &'static str {
+        RULES[self.index()].0
+    }
+
+    /// The index of the rule in RULES.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// The root object of a SARIF file.
+#[derive(Serialize)]
+pub struct SarifLog {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<Run>,
+}
+
+#[derive(Serialize)]
+struct Run {
+    tool: Tool,
+    results: Vec<SarifResult>,
+}
+
+#[derive(Serialize)]
+struct Tool {
+    driver: ToolComponent,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolComponent {
+    name: &'static str,
+    version: &'static str,
+    information_uri: &'static str,
+    rules: Vec<ReportingDescriptor>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReportingDescriptor {
+    id: &'static str,
+    short_description: Message,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id: &'static str,
+    rule_index: usize,
+    level: &'static str,
+    message: Message,
+    locations: Vec<Location>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    related_locations: Vec<Location>,
+}
+
+#[derive(Serialize)]
+struct Message {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Location {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    physical_location: Option<PhysicalLocation>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    logical_locations: Vec<LogicalLocation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<Message>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation {
+    artifact_location: ArtifactLocation,
+    region: Region,
+}
+
+#[derive(Serialize)]
+struct ArtifactLocation {
+    uri: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Region {
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LogicalLocation {
+    fully_qualified_name: String,
+    kind: &'static str,
+}
+
+impl SarifLog {
+    /// Creates a log with a single run and no results.
+    pub fn new() -> SarifLog {
+        let rules = RULES
+            .iter()
+            .map(|(id, description)| ReportingDescriptor {
+                id: *id,
+                short_description: Message {
+                    text: (*description).to_string(),
+                },
+            })
+            .collect();
+        SarifLog {
+            schema: "https://json.schemastore.org/sarif-2.1.0.json",
+            version: "2.1.0",
+            runs: vec![Run {
+                tool: Tool {
+                    driver: ToolComponent {
+                        name: "MIRAI",
+                        version: env!("CARGO_PKG_VERSION"),
+                        information_uri: "https://github.com/facebookexperimental/MIRAI",
+                        rules,
+                    },
+                },
+                results: vec![],
+            }],
+        }
+    }
+
+    /// Adds a result for the given diagnostic, which is an instance of the given rule and was
+    /// issued while analyzing the function with the given summary key. The primary span of the diagnostic becomes the location of
+    /// the result and the spans of its notes (the stack of locations that lead to a promoted
+    /// precondition) become related locations.
+    pub fn add_result(
+        &mut self,
+        summary_key: &Rc<String>,
+        diagnostic: &Diagnostic,
+        rule: Rule,
+        source_map: &SourceMap,
+    ) {
+        let message = diagnostic.message();
+        let rule_index = rule.index();
+        let level = match diagnostic.level {
+            Level::Bug | Level::Fatal | Level::Error => "error",
+            Level::Warning => "warning",
+            _ => "note",
+        };
+        let logical_location = LogicalLocation {
+            fully_qualified_name: summary_key.to_string(),
+            kind: "function",
+        };
+        let location = Location {
+            id: None,
+            physical_location: Self::get_physical_location(&diagnostic.span, source_map),
+            logical_locations: vec![logical_location],
+            message: None,
+        };
+        let related_locations = diagnostic
+            .children
+            .iter()
+            .enumerate()
+            .map(|(i, child)| Location {
+                id: Some(i),
+                physical_location: Self::get_physical_location(&child.span, source_map),
+                logical_locations: vec![],
+                message: Some(Message {
+                    text: child.message(),
+                }),
+            })
+            .collect();
+        self.runs[0].results.push(SarifResult {
+            rule_id: RULES[rule_index].0,
+            rule_index,
+            level,
+            message: Message { text: message },
+            locations: vec![location],
+            related_locations,
+        });
+    }
+
+    /// Writes the log to the file at the given path, replacing any existing content.
+    pub fn write_to(&self, path: &Path) -> std::io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    fn get_physical_location(
+        span: &MultiSpan,
+        source_map: &SourceMap,
+    ) -> Option<PhysicalLocation> {
+        span.primary_span()
+            .map(|span: Span| span.source_callsite())
+            .map(|span| {
+                let lo = source_map.lookup_char_pos(span.lo());
+                let hi = source_map.lookup_char_pos(span.hi());
+                PhysicalLocation {
+                    artifact_location: ArtifactLocation {
+                        uri: lo.file.name.to_string(),
+                    },
+                    region: Region {
+                        start_line: lo.line,
+                        start_column: lo.col.0 + 1,
+                        end_line: hi.line,
+                        end_column: hi.col.0 + 1,
+                    },
+                }
+            })
+    }
+}
+
+impl Default for SarifLog {
+    fn default() -> Self {
+        SarifLog::new()
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Support for writing the queries that the analysis of a function makes to the SMT solver to a
+// file in SMT-LIB2 format, along with the source locations of the conditions being decided and
+// the decisions that were taken. Each query can be fed to an SMT solver on the command line,
+// which makes it possible to reproduce solver timeouts and problems with the encoding of
+// MIRAI expressions without running MIRAI.
+
+use crate::smt_solver::SmtResult;
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// A single call to the solver, along with the state of the solver at the time of the call.
+#[derive(Debug)]
+pub struct SmtCheck {
+    /// Describes what the check tries to establish.
+    pub description: &'static str,
+    /// The declarations and assertions of the solver, in SMT-LIB2 format.
+    pub solver_state: String,
+    pub result: SmtResult,
+    /// True if the result was taken from the SMT result cache rather than from the solver.
+    pub cached: bool,
+}
+
+/// The checks made to decide a condition at a particular source location.
+#[derive(Debug)]
+pub struct SmtQuery {
+    pub span: String,
+    pub checks: Vec<SmtCheck>,
+    /// Some(b) if the condition was decided to always be b.
+    pub decision: Option<bool>,
+}
+
+/// Returns the path of the file in the given directory that holds the queries of the function
+/// with the given summary key.
+pub fn smt2_file_path(dir: &str, function_name: &str) -> PathBuf {
+    let file_name: String = function_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let mut path = PathBuf::from(dir);
+    path.push(format!("{}.smt2", file_name));
+    path
+}
+
+/// Writes the given queries to the file at the given path, either replacing its content
+/// or appending to it.
+pub fn write_smt_queries(path: &Path, queries: &[SmtQuery], append: bool) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let file = if append {
+        OpenOptions::new().append(true).create(true).open(path)?
+    } else {
+        File::create(path)?
+    };
+    let mut writer = BufWriter::new(file);
+    for query in queries.iter() {
+        writeln!(writer, "; condition at {}", query.span)?;
+        writeln!(writer, "; decision: {}", describe_decision(query.decision))?;
+        for check in query.checks.iter() {
+            writeln!(writer, "; check: {}", check.description)?;
+            if check.cached {
+                writeln!(
+                    writer,
+                    "; result: {} (cached)",
+                    describe_result(&check.result)
+                )?;
+            } else {
+                writeln!(writer, "; result: {}", describe_result(&check.result))?;
+            }
+            writeln!(writer, "(push 1)")?;
+            writeln!(writer, "{}", check.solver_state.trim_end())?;
+            writeln!(writer, "(check-sat)")?;
+            writeln!(writer, "(pop 1)")?;
+        }
+        writeln!(writer)?;
+    }
+    writer.flush()
+}
+
+fn describe_decision(decision: Option<bool>) -> &'static str {
+    match decision {
+        Some(true) => "the condition is always true",
+        Some(false) => "the condition is always false",
+        None => "the condition may be true or false",
+    }
+}
+
+fn describe_result(result: &SmtResult) -> &'static str {
+    match result {
+        SmtResult::Satisfiable => "sat",
+        SmtResult::Unsatisfiable => "unsat",
+        SmtResult::Undefined => "unknown",
+    }
+}
@@ -0,0 +1,1655 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+
+// An SMT solver that runs a solver executable, such as z3, cvc5 or yices-smt2, as a child process
+// and talks to it in SMT-LIB2 via its standard input and output. MIRAI expressions are translated
+// to SMT-LIB2 terms in the same way that z3_solver.rs translates them to Z3 ASTs, so that the
+// outcomes of queries should only differ where the solvers themselves differ.
+//
+// A few of the terms that the translation produces, such as bv2nat and ^, are not part of the
+// SMT-LIB2 standard, although the common solvers understand them. If the solver reports an error
+// for a command, the next query is treated as undecided, which is always a safe outcome.
+
+use crate::abstract_value::AbstractValue;
+use crate::abstract_value::AbstractValueTrait;
+use crate::constant_domain::ConstantDomain;
+use crate::expression::{Expression, ExpressionType, QuantifierKind};
+use crate::known_names::KnownNames;
+use crate::path::{Path, PathEnum, PathSelector};
+use crate::smt_solver::SmtResult;
+use crate::smt_solver::SmtSolver;
+use crate::smt_solver::SolverLimit;
+
+use log::{debug, warn};
+use log_derive::logfn_inputs;
+use mirai_annotations::{checked_assume, checked_assume_eq};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter, Result};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A term in SMT-LIB2 syntax.
+pub type SmtLibExpressionType = String;
+
+const ANY_SORT: &str = "Any";
+const BOOL_SORT: &str = "Bool";
+const INT_SORT: &str = "Int";
+const F32_SORT: &str = "(_ FloatingPoint 8 24)";
+const F64_SORT: &str = "(_ FloatingPoint 11 53)";
+const NEAREST_EVEN: &str = "RNE";
+// 2^128, used to interpret a bit vector of length 128 as a signed integer.
+const TWO_TO_THE_128: &str = "340282366920938463463374607431768211456";
+// Echoed by the solver after the responses to a batch of commands.
+const END_OF_RESPONSES: &str = "mirai-end-of-responses";
+
+// How much longer than its own time limit the solver is given to respond, before it is
+// assumed to hang and is restarted.
+const GRACE_PERIOD_IN_MILLISECONDS: u64 = 1_000;
+// How long the solver is given to respond if it is limited by resources rather than by time.
+// Solvers that do not understand the resource limit are only stopped by this deadline.
+const RESOURCE_LIMITED_DEADLINE_IN_MILLISECONDS: u64 = 60_000;
+
+pub struct SmtLibSolver {
+    command: String,
+    words: Vec<String>,
+    limit: SolverLimit,
+    deadline: Duration,
+    process: RefCell<SolverProcess>,
+}
+
+struct SolverProcess {
+    connection: Connection,
+    // Commands that have not yet been sent to the solver. They are sent in one go by the next
+    // command that needs an answer from the solver.
+    pending_commands: Vec<String>,
+    // The declarations made so far. These are global, so backtracking does not undo them.
+    declarations: Vec<String>,
+    // Maps the symbols declared so far to the names they were made from.
+    declared_symbols: HashMap<String, String>,
+    // The assertions made in each of the nested contexts, starting with the outermost context.
+    assertions: Vec<Vec<String>>,
+    fresh_constant_count: usize,
+}
+
+// A running solver process. Its responses are read by a thread of their own, so that waiting
+// for a response can be given a deadline.
+struct Connection {
+    child: Child,
+    input: ChildStdin,
+    responses: Receiver<std::io::Result<String>>,
+}
+
+impl Debug for SmtLibSolver {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        "SmtLibSolver".fmt(f)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl SmtLibSolver {
+    /// Starts the solver given by the command, which is the path of the solver executable,
+    /// followed by the arguments that make it read SMT-LIB2 commands from its standard input,
+    /// for example "z3 -in" or "cvc5 --incremental". The solver is asked to give up on a query
+    /// once it exceeds the given limit, and is restarted if it does not respond in time.
+    #[logfn_inputs(TRACE)]
+    pub fn new(command: &str, limit: SolverLimit) -> std::io::Result<SmtLibSolver> {
+        let words = shellwords::split(command).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "mismatched quotes in solver command",
+            )
+        })?;
+        if words.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "empty solver command",
+            ));
+        }
+        let deadline = Duration::from_millis(match limit {
+            SolverLimit::Time(milliseconds) => milliseconds + GRACE_PERIOD_IN_MILLISECONDS,
+            SolverLimit::Resources(_) => RESOURCE_LIMITED_DEADLINE_IN_MILLISECONDS,
+        });
+        let connection = Connection::start(&words)?;
+        let solver = SmtLibSolver {
+            command: command.to_string(),
+            words,
+            limit,
+            deadline,
+            process: RefCell::new(SolverProcess {
+                connection,
+                pending_commands: Vec::new(),
+                declarations: Vec::new(),
+                declared_symbols: HashMap::new(),
+                assertions: vec![Vec::new()],
+                fresh_constant_count: 0,
+            }),
+        };
+        solver.send_setup_commands();
+        Ok(solver)
+    }
+
+    /// Discards all declarations and assertions, so that the solver can be reused for the
+    /// analysis of another function body.
+    #[logfn_inputs(TRACE)]
+    pub fn reset(&mut self) {
+        {
+            let mut process = self.process.borrow_mut();
+            process.pending_commands.clear();
+            process.pending_commands.push("(reset)".to_string());
+            process.declarations.clear();
+            process.declared_symbols.clear();
+            process.assertions = vec![Vec::new()];
+            process.fresh_constant_count = 0;
+        }
+        self.send_setup_commands();
+    }
+
+    /// Replaces a solver process that did not respond in time, or that exited, with a new one
+    /// that has the same declarations and assertions.
+    fn restart(&self) {
+        let connection = match Connection::start(&self.words) {
+            Ok(connection) => connection,
+            Err(err) => {
+                warn!("unable to restart {}: {}", self.command, err);
+                return;
+            }
+        };
+        {
+            let mut process = self.process.borrow_mut();
+            process.connection = connection;
+            process.pending_commands.clear();
+        }
+        // The new process must be set up before the declarations and assertions are replayed.
+        self.send_setup_commands();
+        let mut process = self.process.borrow_mut();
+        let mut commands = process.declarations.clone();
+        for (i, context) in process.assertions.iter().enumerate() {
+            if i > 0 {
+                commands.push("(push 1)".to_string());
+            }
+            commands.extend(context.iter().cloned());
+        }
+        process.pending_commands = commands;
+    }
+
+    /// Sends the commands that configure the solver, after any pending commands, such as the
+    /// (reset) that would otherwise undo them.
+    fn send_setup_commands(&self) {
+        // Declarations are made lazily, while translating expressions, so they may happen
+        // in a nested context and must survive backtracking out of it.
+        let setup_commands = vec![
+            "(set-option :global-declarations true)".to_string(),
+            "(set-option :produce-models true)".to_string(),
+            "(set-logic ALL)".to_string(),
+            format!("(declare-sort {} 0)", ANY_SORT),
+        ];
+        self.process
+            .borrow_mut()
+            .pending_commands
+            .extend(setup_commands);
+        // Not every solver understands these options, and the ones that do not reply with an
+        // error, which must not count against the next query.
+        let option = match self.limit {
+            SolverLimit::Time(milliseconds) => format!("(set-option :timeout {})", milliseconds),
+            SolverLimit::Resources(resources) => format!("(set-option :rlimit {})", resources),
+        };
+        match self.send_and_receive(&option) {
+            Ok((errors, response)) => {
+                if !errors.is_empty() || response == "unsupported" {
+                    debug!("{} does not support {}: {:?}", self.command, option, errors);
+                }
+            }
+            Err(err) => warn!("unable to communicate with {}: {}", self.command, err),
+        }
+    }
+
+    /// Sends the pending commands, followed by the given command, and returns the response
+    /// to the given command, along with any errors reported for the pending commands.
+    /// Returns an error of kind TimedOut if the solver does not respond before the deadline.
+    fn send_and_receive(&self, command: &str) -> std::io::Result<(Vec<String>, String)> {
+        let mut process = self.process.borrow_mut();
+        let mut commands = std::mem::take(&mut process.pending_commands);
+        commands.push(command.to_string());
+        // Commands that succeed mostly produce no output, so the echoed marker is needed to
+        // tell when all of the responses have been read.
+        commands.push(format!("(echo \"{}\")", END_OF_RESPONSES));
+        for command in commands.iter() {
+            writeln!(process.connection.input, "{}", command)?;
+        }
+        process.connection.input.flush()?;
+        let deadline = Instant::now() + self.deadline;
+        let mut errors = Vec::new();
+        let mut last_response = String::new();
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let response = match process.connection.responses.recv_timeout(timeout) {
+                Ok(response) => response?,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "the solver did not respond in time",
+                    ));
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "the solver exited",
+                    ));
+                }
+            };
+            if response.trim_matches('"') == END_OF_RESPONSES {
+                return Ok((errors, last_response));
+            } else if response.starts_with("(error") {
+                errors.push(response);
+            } else {
+                last_response = response;
+            }
+        }
+    }
+
+    fn assert_term(&self, term: String) {
+        let command = format!("(assert {})", term);
+        let mut process = self.process.borrow_mut();
+        process.assertions.last_mut().unwrap().push(command.clone());
+        process.pending_commands.push(command);
+    }
+
+    /// Returns the symbol for a constant with the given name and sort, declaring it if necessary.
+    /// The sort is part of the symbol, since Z3 treats constants with the same name but different
+    /// sorts as different constants.
+    fn constant(&self, name: &str, sort: &str) -> String {
+        let symbol: String = format!("{}:{}", name, sort)
+            .chars()
+            .map(|c| if c == '|' || c == '\\' { '_' } else { c })
+            .collect();
+        let symbol = format!("|{}|", symbol);
+        let mut process = self.process.borrow_mut();
+        if !process.declared_symbols.contains_key(&symbol) {
+            process
+                .declared_symbols
+                .insert(symbol.clone(), name.to_string());
+            let declaration = format!("(declare-const {} {})", symbol, sort);
+            process.declarations.push(declaration.clone());
+            process.pending_commands.push(declaration);
+        }
+        symbol
+    }
+
+    fn fresh_constant(&self, sort: &str) -> String {
+        let count = {
+            let mut process = self.process.borrow_mut();
+            process.fresh_constant_count += 1;
+            process.fresh_constant_count
+        };
+        self.constant(&format!("fresh!{}", count), sort)
+    }
+}
+
+impl Connection {
+    fn start(words: &[String]) -> std::io::Result<Connection> {
+        let mut child = Command::new(&words[0])
+            .args(&words[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let input = child.stdin.take().unwrap();
+        let mut output = BufReader::new(child.stdout.take().unwrap());
+        let (sender, responses) = channel();
+        // The thread ends when the solver exits, which it does when the connection is dropped.
+        thread::spawn(move || loop {
+            let response = read_response(&mut output);
+            let failed = response.is_err();
+            if sender.send(response).is_err() || failed {
+                break;
+            }
+        });
+        Ok(Connection {
+            child,
+            input,
+            responses,
+        })
+    }
+}
+
+/// Reads the next complete response from the solver, which is either a symbol or an
+/// S-expression that may span several lines.
+fn read_response(output: &mut BufReader<ChildStdout>) -> std::io::Result<String> {
+    let mut response = String::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut in_quoted_symbol = false;
+    loop {
+        let mut line = String::new();
+        if output.read_line(&mut line)? == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "the solver exited",
+            ));
+        }
+        for c in line.chars() {
+            match c {
+                '"' if !in_quoted_symbol => in_string = !in_string,
+                '|' if !in_string => in_quoted_symbol = !in_quoted_symbol,
+                '(' if !in_string && !in_quoted_symbol => depth += 1,
+                ')' if !in_string && !in_quoted_symbol => depth -= 1,
+                _ => {}
+            }
+        }
+        response.push_str(&line);
+        if depth <= 0 && !in_string && !in_quoted_symbol && !response.trim().is_empty() {
+            return Ok(response.trim().to_string());
+        }
+    }
+}
+
+/// Returns the elements of the given S-expression list, without the enclosing parentheses.
+fn list_elements(list: &str) -> Vec<String> {
+    let list = list.trim();
+    let list = if list.starts_with('(') && list.ends_with(')') {
+        &list[1..list.len() - 1]
+    } else {
+        list
+    };
+    let mut elements = Vec::new();
+    let mut element = String::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut in_quoted_symbol = false;
+    for c in list.chars() {
+        match c {
+            '"' if !in_quoted_symbol => in_string = !in_string,
+            '|' if !in_string => in_quoted_symbol = !in_quoted_symbol,
+            '(' if !in_string && !in_quoted_symbol => depth += 1,
+            ')' if !in_string && !in_quoted_symbol => depth -= 1,
+            c if c.is_whitespace() && depth == 0 && !in_string && !in_quoted_symbol => {
+                if !element.is_empty() {
+                    elements.push(std::mem::take(&mut element));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        element.push(c);
+    }
+    if !element.is_empty() {
+        elements.push(element);
+    }
+    elements
+}
+
+impl SmtSolver<SmtLibExpressionType> for SmtLibSolver {
+    #[logfn_inputs(TRACE)]
+    fn as_debug_string(&self, expression: &SmtLibExpressionType) -> String {
+        expression.clone()
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn assert(&self, expression: &SmtLibExpressionType) {
+        self.assert_term(expression.clone());
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn backtrack(&self) {
+        let mut process = self.process.borrow_mut();
+        process.assertions.pop();
+        process.pending_commands.push("(pop 1)".to_string());
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn get_as_smt_predicate(&self, mirai_expression: &Expression) -> SmtLibExpressionType {
+        self.get_as_bool_term(mirai_expression)
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn get_model_as_string(&self) -> String {
+        // The model is given in the format of Z3Solver, one "name -> value" line per constant,
+        // so that BodyVisitor::get_counterexample can read it. Only the constants that
+        // represent parameters and locals are of interest, and the values of all the others
+        // can be large.
+        let symbols: Vec<String> = self
+            .process
+            .borrow()
+            .declared_symbols
+            .iter()
+            .filter(|(_, name)| {
+                (name.starts_with("param_") || name.starts_with("local_")) && !name.contains("[..]")
+            })
+            .map(|(symbol, _)| symbol.clone())
+            .collect();
+        if symbols.is_empty() {
+            return String::new();
+        }
+        let command = format!("(get-value ({}))", symbols.join(" "));
+        match self.send_and_receive(&command) {
+            Ok((_, values)) => {
+                let process = self.process.borrow();
+                let mut lines: Vec<String> = list_elements(&values)
+                    .iter()
+                    .filter_map(|pair| {
+                        let pair = list_elements(pair);
+                        // The solver need not quote the symbol in the same way.
+                        let symbol = format!("|{}|", pair.get(0)?.trim_matches('|'));
+                        let name = process.declared_symbols.get(&symbol)?;
+                        let value = pair
+                            .get(1)?
+                            .split_whitespace()
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                        Some(format!("{} -> {}", name, value))
+                    })
+                    .collect();
+                lines.sort();
+                lines.join("\n")
+            }
+            Err(err) => format!("unable to get the model from {}: {}", self.command, err),
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn get_solver_state_as_string(&self) -> String {
+        let process = self.process.borrow();
+        let mut lines: Vec<&str> = process.declarations.iter().map(|d| d.as_str()).collect();
+        for context in process.assertions.iter() {
+            lines.extend(context.iter().map(|a| a.as_str()));
+        }
+        lines.join("\n")
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn set_backtrack_position(&self) {
+        let mut process = self.process.borrow_mut();
+        process.assertions.push(Vec::new());
+        process.pending_commands.push("(push 1)".to_string());
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn solve(&self) -> SmtResult {
+        match self.send_and_receive("(check-sat)") {
+            Ok((errors, response)) => {
+                if !errors.is_empty() {
+                    debug!("solver errors: {:?}", errors);
+                    return SmtResult::Undefined;
+                }
+                match response.as_str() {
+                    "sat" => SmtResult::Satisfiable,
+                    "unsat" => SmtResult::Unsatisfiable,
+                    _ => SmtResult::Undefined,
+                }
+            }
+            Err(err) => {
+                warn!("unable to communicate with {}: {}", self.command, err);
+                self.restart();
+                SmtResult::Undefined
+            }
+        }
+    }
+}
+
+impl SmtLibSolver {
+    #[logfn_inputs(TRACE)]
+    fn get_as_term(&self, expression: &Expression) -> String {
+        match expression {
+            Expression::HeapBlock { .. } => {
+                let path = Path::get_as_path(AbstractValue::make_from(expression.clone(), 1));
+                self.general_variable(&path, &expression.infer_type())
+            }
+            Expression::Add { .. }
+            | Expression::AddOverflows { .. }
+            | Expression::Div { .. }
+            | Expression::IntrinsicBinary { .. }
+            | Expression::IntrinsicFloatingPointUnary { .. }
+            | Expression::Mul { .. }
+            | Expression::MulOverflows { .. }
+            | Expression::Rem { .. }
+            | Expression::Sub { .. }
+            | Expression::SubOverflows { .. } => self.get_as_numeric_term(expression).1,
+            Expression::And { left, right } => self.general_boolean_op(left, right, "and"),
+            Expression::BitAnd { .. } | Expression::BitOr { .. } | Expression::BitXor { .. } => {
+                self.get_as_bv_term(expression, 128)
+            }
+            Expression::BitNot { result_type, .. } => {
+                self.get_as_bv_term(expression, u32::from(result_type.bit_length()))
+            }
+            Expression::Cast {
+                operand,
+                target_type,
+            } => self.general_cast(&operand, target_type),
+            Expression::CompileTimeConstant(const_domain) => {
+                self.get_constant_as_term(const_domain)
+            }
+            Expression::ConditionalExpression {
+                condition,
+                consequent,
+                alternate,
+            } => self.general_conditional(condition, consequent, alternate),
+            Expression::Equals { left, right } => {
+                self.general_relational(left, right, "fp.eq", "=", "=", "=")
+            }
+            Expression::GreaterOrEqual { left, right } => {
+                self.general_relational(left, right, "fp.geq", ">=", "bvsge", "bvuge")
+            }
+            Expression::GreaterThan { left, right } => {
+                self.general_relational(left, right, "fp.gt", ">", "bvsgt", "bvugt")
+            }
+            Expression::IntrinsicBitVectorUnary { bit_length, .. } => {
+                self.get_as_bv_term(expression, u32::from(*bit_length))
+            }
+            Expression::LessOrEqual { left, right } => {
+                self.general_relational(left, right, "fp.leq", "<=", "bvsle", "bvule")
+            }
+            Expression::LessThan { left, right } => {
+                self.general_relational(left, right, "fp.lt", "<", "bvslt", "bvult")
+            }
+            Expression::LogicalNot { operand } => self.general_logical_not(operand),
+            Expression::Ne { left, right } => self.general_ne(left, right),
+            Expression::Neg { operand } => self.general_negation(operand),
+            Expression::Offset { .. } => {
+                let sort = self.get_sort_for(&expression.infer_type());
+                self.fresh_constant(&sort)
+            }
+            Expression::Or { left, right } => self.general_boolean_op(left, right, "or"),
+            Expression::Quantifier {
+                kind,
+                variable,
+                lower_bound,
+                upper_bound,
+                condition,
+            } => self.general_quantifier(*kind, variable, lower_bound, upper_bound, condition),
+            Expression::Reference(path) => self.general_reference(path),
+            Expression::Shl { left, right } => self.bv_binary(128, left, right, "bvshl"),
+            Expression::Shr {
+                left,
+                right,
+                result_type,
+            } => self.general_shr(left, right, result_type),
+            Expression::ShlOverflows {
+                right, result_type, ..
+            }
+            | Expression::ShrOverflows {
+                right, result_type, ..
+            } => self.general_shift_overflows(right, result_type),
+            Expression::Top | Expression::Bottom => self.general_fresh_const(),
+            Expression::UninterpretedCall {
+                result_type: var_type,
+                path,
+                ..
+            }
+            | Expression::Variable { path, var_type } => self.general_variable(path, &var_type),
+            Expression::HasTaint { .. } => self.general_has_taint(expression),
+            Expression::Sanitized { operand, .. } | Expression::Tainted { operand, .. } => {
+                self.get_as_term(&operand.expression)
+            }
+            Expression::Widen { path, operand } => {
+                self.get_term_for_widened(path, operand, operand.expression.infer_type())
+            }
+            Expression::Join { path, .. } | Expression::UnknownModelField { path, .. } => {
+                self.general_join(expression, path)
+            }
+            _ => {
+                debug!("uninterpreted expression: {:?}", expression);
+                let sort = self.get_sort_for(&expression.infer_type());
+                self.fresh_constant(&sort)
+            }
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn general_boolean_op(
+        &self,
+        left: &Rc<AbstractValue>,
+        right: &Rc<AbstractValue>,
+        operation: &str,
+    ) -> String {
+        let left_term = self.get_as_bool_term(&(**left).expression);
+        let right_term = self.get_as_bool_term(&(**right).expression);
+        format!("({} {} {})", operation, left_term, right_term)
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn general_cast(&self, operand: &Rc<AbstractValue>, target_type: &ExpressionType) -> String {
+        if *target_type == ExpressionType::NonPrimitive || *target_type == ExpressionType::Reference
+        {
+            self.get_as_term(&operand.expression)
+        } else if *target_type == ExpressionType::Bool {
+            self.get_as_bool_term(&operand.expression)
+        } else {
+            self.get_as_numeric_term(&operand.expression).1
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn general_conditional(
+        &self,
+        condition: &Rc<AbstractValue>,
+        consequent: &Rc<AbstractValue>,
+        alternate: &Rc<AbstractValue>,
+    ) -> String {
+        let condition_term = self.get_as_bool_term(&(**condition).expression);
+        let consequent_term = self.get_as_term(&(**consequent).expression);
+        let alternate_term = self.get_as_term(&(**alternate).expression);
+        format!(
+            "(ite {} {} {})",
+            condition_term, consequent_term, alternate_term
+        )
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn general_relational(
+        &self,
+        left: &Rc<AbstractValue>,
+        right: &Rc<AbstractValue>,
+        float_op: &str,
+        int_op: &str,
+        signed_bit_op: &str,
+        unsigned_bit_op: &str,
+    ) -> String {
+        let (signed, num_bits) = if left.expression.is_bit_vector() {
+            let ty = left.expression.infer_type();
+            (ty.is_signed_integer(), u32::from(ty.bit_length()))
+        } else if right.expression.is_bit_vector() {
+            let ty = right.expression.infer_type();
+            (ty.is_signed_integer(), u32::from(ty.bit_length()))
+        } else {
+            (false, 0)
+        };
+        if num_bits > 0 {
+            let left_term = self.get_as_bv_term(&(**left).expression, num_bits);
+            let right_term = self.get_as_bv_term(&(**right).expression, num_bits);
+            let op = if signed {
+                signed_bit_op
+            } else {
+                unsigned_bit_op
+            };
+            format!("({} {} {})", op, left_term, right_term)
+        } else {
+            let (lf, left_term) = self.get_as_numeric_term(&(**left).expression);
+            let (rf, right_term) = self.get_as_numeric_term(&(**right).expression);
+            checked_assume_eq!(lf, rf);
+            let op = if lf { float_op } else { int_op };
+            format!("({} {} {})", op, left_term, right_term)
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn general_ne(&self, left: &Rc<AbstractValue>, right: &Rc<AbstractValue>) -> String {
+        let num_bits = if left.expression.is_bit_vector() {
+            u32::from(left.expression.infer_type().bit_length())
+        } else if right.expression.is_bit_vector() {
+            u32::from(right.expression.infer_type().bit_length())
+        } else {
+            0
+        };
+        if num_bits > 0 {
+            let left_term = self.get_as_bv_term(&(**left).expression, num_bits);
+            let right_term = self.get_as_bv_term(&(**right).expression, num_bits);
+            format!("(not (= {} {}))", left_term, right_term)
+        } else {
+            let (lf, left_term) = self.get_as_numeric_term(&(**left).expression);
+            let (rf, right_term) = self.get_as_numeric_term(&(**right).expression);
+            checked_assume_eq!(lf, rf);
+            if lf {
+                format!(
+                    "(or (fp.isNaN {l}) (fp.isNaN {r}) (not (fp.eq {l} {r})))",
+                    l = left_term,
+                    r = right_term
+                )
+            } else {
+                format!("(not (= {} {}))", left_term, right_term)
+            }
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn general_negation(&self, operand: &Rc<AbstractValue>) -> String {
+        let (is_float, operand_term) = self.get_as_numeric_term(&(**operand).expression);
+        if is_float {
+            format!("(fp.neg {})", operand_term)
+        } else {
+            format!("(- {})", operand_term)
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn general_logical_not(&self, operand: &Rc<AbstractValue>) -> String {
+        let operand_term = self.get_as_bool_term(&(**operand).expression);
+        format!("(not {})", operand_term)
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn general_quantifier(
+        &self,
+        kind: QuantifierKind,
+        variable: &Rc<Path>,
+        lower_bound: &Rc<AbstractValue>,
+        upper_bound: &Rc<AbstractValue>,
+        condition: &Rc<AbstractValue>,
+    ) -> String {
+        let lower_bound_term = self.get_as_numeric_term(&lower_bound.expression).1;
+        let upper_bound_term = self.get_as_numeric_term(&upper_bound.expression).1;
+        let condition_term = self.get_as_bool_term(&condition.expression);
+        // The binding shadows the constant that the occurrences of the bound variable in the
+        // condition are translated to.
+        let bound_variable = self.constant(&format!("{:?}", variable), INT_SORT);
+        let is_in_range = format!(
+            "(and (<= {} {}) (< {} {}))",
+            lower_bound_term, bound_variable, bound_variable, upper_bound_term
+        );
+        match kind {
+            QuantifierKind::ForAll => format!(
+                "(forall (({} {})) (=> {} {}))",
+                bound_variable, INT_SORT, is_in_range, condition_term
+            ),
+            QuantifierKind::Exists => format!(
+                "(exists (({} {})) (and {} {}))",
+                bound_variable, INT_SORT, is_in_range, condition_term
+            ),
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn general_reference(&self, path: &Rc<Path>) -> String {
+        self.constant(&format!("&{:?}", path), ANY_SORT)
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn general_shr(
+        &self,
+        left: &Rc<AbstractValue>,
+        right: &Rc<AbstractValue>,
+        result_type: &ExpressionType,
+    ) -> String {
+        let num_bits = u32::from(result_type.bit_length());
+        let left_term = self.get_as_bv_term(&(**left).expression, num_bits);
+        let right_term = self.get_as_bv_term(&(**right).expression, num_bits);
+        if result_type.is_signed_integer() {
+            format!("(bvashr {} {})", left_term, right_term)
+        } else {
+            format!("(bvlshr {} {})", left_term, right_term)
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn general_shift_overflows(
+        &self,
+        right: &Rc<AbstractValue>,
+        result_type: &ExpressionType,
+    ) -> String {
+        let (f, right_term) = self.get_as_numeric_term(&(**right).expression);
+        checked_assume!(!f);
+        format!("(>= {} {})", right_term, result_type.bit_length())
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn general_fresh_const(&self) -> String {
+        //todo: why a bool_sort?
+        self.fresh_constant(BOOL_SORT)
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn general_variable(&self, path: &Rc<Path>, var_type: &ExpressionType) -> String {
+        let sort = self.get_sort_for(var_type);
+        let term = self.get_term_for_path(path, &sort);
+        if var_type.is_integer() {
+            let min_term = self.get_constant_as_term(&var_type.min_value());
+            let max_term = self.get_constant_as_term(&var_type.max_value());
+            // This is a lazy declaration of the range of the variable, as in Z3Solver.
+            self.assert_term(self.get_range_check(&term, &min_term, &max_term));
+        }
+        term
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn general_has_taint(&self, expression: &Expression) -> String {
+        // The solver knows nothing about taint that depends on the caller, but every occurrence
+        // of the same unknown has to be the same constant.
+        self.constant(&format!("{:?}", expression), BOOL_SORT)
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn general_join(&self, expression: &Expression, path: &Rc<Path>) -> String {
+        let sort = self.get_sort_for(&expression.infer_type());
+        self.constant(&format!("{:?}", path), &sort)
+    }
+
+    /// Returns the constant that represents the value at path, unless path selects an element
+    /// of a collection using an integer index, in which case the term selects the element from an
+    /// array that represents all of the elements of the collection, as in Z3Solver.
+    #[logfn_inputs(TRACE)]
+    fn get_term_for_path(&self, path: &Rc<Path>, sort: &str) -> String {
+        if let PathEnum::QualifiedPath {
+            qualifier,
+            selector,
+            ..
+        } = &path.value
+        {
+            if let PathSelector::Index(index) = selector.as_ref() {
+                if index.expression.infer_type().is_integer() {
+                    let index_term = self.get_as_numeric_term(&index.expression).1;
+                    let array_sort = format!("(Array {} {})", INT_SORT, sort);
+                    let elements = self.constant(&format!("{:?}[..]", qualifier), &array_sort);
+                    return format!("(select {} {})", elements, index_term);
+                }
+            }
+        }
+        self.constant(&format!("{:?}", path), sort)
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn get_sort_for(&self, var_type: &ExpressionType) -> String {
+        use self::ExpressionType::*;
+        match var_type {
+            Bool => BOOL_SORT,
+            Char | I8 | I16 | I32 | I64 | I128 | Isize | U8 | U16 | U32 | U64 | U128 | Usize => {
+                INT_SORT
+            }
+            F32 => F32_SORT,
+            F64 => F64_SORT,
+            NonPrimitive | Reference => ANY_SORT,
+        }
+        .to_string()
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn get_term_for_widened(
+        &self,
+        path: &Rc<Path>,
+        operand: &Rc<AbstractValue>,
+        target_type: ExpressionType,
+    ) -> String {
+        let sort = self.get_sort_for(&target_type);
+        let term = self.constant(&format!("{:?}", path), &sort);
+        if target_type.is_integer() {
+            let domain = AbstractValue::make_from(
+                Expression::Widen {
+                    path: path.clone(),
+                    operand: operand.clone(),
+                },
+                1,
+            );
+            let interval = domain.get_as_interval();
+            if !interval.is_bottom() {
+                // An interval is kind of like a type, so its bounds are asserted like
+                // the range of a variable.
+                if let Some(lower_bound) = interval.lower_bound() {
+                    let lb = self.get_constant_as_term(&ConstantDomain::I128(lower_bound));
+                    self.assert_term(format!("(>= {} {})", term, lb));
+                }
+                if let Some(upper_bound) = interval.upper_bound() {
+                    let ub = self.get_constant_as_term(&ConstantDomain::I128(upper_bound));
+                    self.assert_term(format!("(<= {} {})", term, ub));
+                }
+            }
+        }
+        term
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn get_constant_as_term(&self, const_domain: &ConstantDomain) -> String {
+        match const_domain {
+            ConstantDomain::Char(v) => format!("{}", *v as u16),
+            ConstantDomain::False => "false".to_string(),
+            ConstantDomain::I128(v) => int_numeral(*v),
+            ConstantDomain::F32(v) => format!("((_ to_fp 8 24) #x{:08x})", *v),
+            ConstantDomain::F64(v) => format!("((_ to_fp 11 53) #x{:016x})", *v),
+            ConstantDomain::U128(v) => format!("{}", *v),
+            ConstantDomain::True => "true".to_string(),
+            _ => self.fresh_constant(ANY_SORT),
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn get_range_check(&self, operand_term: &str, min_term: &str, max_term: &str) -> String {
+        format!(
+            "(and (<= {min} {x}) (>= {max} {x}))",
+            min = min_term,
+            max = max_term,
+            x = operand_term
+        )
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn get_as_numeric_term(&self, expression: &Expression) -> (bool, String) {
+        match expression {
+            Expression::HeapBlock { .. } => {
+                let path = Path::get_as_path(AbstractValue::make_from(expression.clone(), 1));
+                self.numeric_variable(expression, &path, &expression.infer_type())
+            }
+            Expression::Add { left, right } => self.numeric_binary(left, right, "fp.add", "+"),
+            Expression::AddOverflows {
+                left,
+                right,
+                result_type,
+            } => self.numeric_binary_overflow(left, right, result_type, "+"),
+            Expression::Div { left, right } => self.numeric_binary(left, right, "fp.div", "div"),
+            Expression::Join { path, .. } | Expression::UnknownModelField { path, .. } => {
+                self.numeric_join(&expression, path)
+            }
+            Expression::Mul { left, right } => self.numeric_binary(left, right, "fp.mul", "*"),
+            Expression::MulOverflows {
+                left,
+                right,
+                result_type,
+            } => self.numeric_binary_overflow(left, right, result_type, "*"),
+            Expression::Rem { left, right } => self.numeric_rem(left, right),
+            Expression::Sub { left, right } => self.numeric_binary(left, right, "fp.sub", "-"),
+            Expression::SubOverflows {
+                left,
+                right,
+                result_type,
+            } => self.numeric_binary_overflow(left, right, result_type, "-"),
+            Expression::And { .. }
+            | Expression::Equals { .. }
+            | Expression::GreaterOrEqual { .. }
+            | Expression::GreaterThan { .. }
+            | Expression::LessOrEqual { .. }
+            | Expression::LessThan { .. }
+            | Expression::LogicalNot { .. }
+            | Expression::Ne { .. }
+            | Expression::Or { .. } => self.numeric_boolean_op(expression),
+            Expression::BitAnd { .. } | Expression::BitOr { .. } | Expression::BitXor { .. } => {
+                self.numeric_bitwise_expression(expression)
+            }
+            Expression::BitNot {
+                operand,
+                result_type,
+            } => self.numeric_bitwise_not(operand, result_type),
+            Expression::Cast {
+                operand,
+                target_type,
+            } => self.numeric_cast(&operand.expression, target_type),
+            Expression::CompileTimeConstant(const_domain) => {
+                self.numeric_const(expression, const_domain)
+            }
+            Expression::ConditionalExpression {
+                condition,
+                consequent,
+                alternate,
+            } => self.numeric_conditional(condition, consequent, alternate),
+            Expression::IntrinsicBinary { .. } => {
+                let expression_type = expression.infer_type();
+                let sort = self.get_sort_for(&expression_type);
+                //todo: use the name to select an appropriate floating point function
+                (
+                    expression_type.is_floating_point_number(),
+                    self.fresh_constant(&sort),
+                )
+            }
+            Expression::IntrinsicFloatingPointUnary { .. } => {
+                let sort = self.get_sort_for(&expression.infer_type());
+                //todo: use the name to select an appropriate floating point function
+                (true, self.fresh_constant(&sort))
+            }
+            Expression::Neg { operand } => self.numeric_neg(operand),
+            Expression::Offset { .. } => {
+                use self::ExpressionType::*;
+                let expr_type = expression.infer_type();
+                let expr_type = match expr_type {
+                    Bool | Reference | NonPrimitive => ExpressionType::I128,
+                    _ => expr_type,
+                };
+                let is_float = expr_type.is_floating_point_number();
+                let sort = self.get_sort_for(&expr_type);
+                (is_float, self.fresh_constant(&sort))
+            }
+            Expression::Reference(path) => self.numeric_reference(path),
+            Expression::Shl { left, right } => self.numeric_shl(left, right),
+            Expression::Shr { left, right, .. } => self.numeric_shr(left, right),
+            Expression::Top | Expression::Bottom => self.numeric_fresh_const(),
+            Expression::UninterpretedCall {
+                result_type: var_type,
+                path,
+                ..
+            }
+            | Expression::Variable { path, var_type } => {
+                self.numeric_variable(expression, path, var_type)
+            }
+            Expression::Sanitized { operand, .. } | Expression::Tainted { operand, .. } => {
+                self.get_as_numeric_term(&operand.expression)
+            }
+            Expression::Widen { path, operand } => self.numeric_widen(path, operand),
+            _ => (false, self.get_as_term(expression)),
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn numeric_binary(
+        &self,
+        left: &Rc<AbstractValue>,
+        right: &Rc<AbstractValue>,
+        float_op: &str,
+        int_op: &str,
+    ) -> (bool, String) {
+        let (lf, left_term) = self.get_as_numeric_term(&(**left).expression);
+        let (rf, right_term) = self.get_as_numeric_term(&(**right).expression);
+        checked_assume_eq!(lf, rf);
+        if lf {
+            (
+                true,
+                format!(
+                    "({} {} {} {})",
+                    float_op, NEAREST_EVEN, left_term, right_term
+                ),
+            )
+        } else {
+            (false, format!("({} {} {})", int_op, left_term, right_term))
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn numeric_binary_overflow(
+        &self,
+        left: &Rc<AbstractValue>,
+        right: &Rc<AbstractValue>,
+        result_type: &ExpressionType,
+        int_op: &str,
+    ) -> (bool, String) {
+        let (lf, left_term) = self.get_as_numeric_term(&(**left).expression);
+        let (rf, right_term) = self.get_as_numeric_term(&(**right).expression);
+        checked_assume!(!(lf || rf));
+        let result = format!("({} {} {})", int_op, left_term, right_term);
+        let min_term = self.get_constant_as_term(&result_type.min_value());
+        let max_term = self.get_constant_as_term(&result_type.max_value());
+        let result_overflows = format!(
+            "(or (> {min} {r}) (< {max} {r}))",
+            min = min_term,
+            max = max_term,
+            r = result
+        );
+        let left_in_range = self.get_range_check(&left_term, &min_term, &max_term);
+        let right_in_range = self.get_range_check(&right_term, &min_term, &max_term);
+        (
+            false,
+            format!(
+                "(and {} {} {})",
+                left_in_range, right_in_range, result_overflows
+            ),
+        )
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn numeric_rem(&self, left: &Rc<AbstractValue>, right: &Rc<AbstractValue>) -> (bool, String) {
+        let (lf, left_term) = self.get_as_numeric_term(&(**left).expression);
+        let (rf, right_term) = self.get_as_numeric_term(&(**right).expression);
+        checked_assume_eq!(lf, rf);
+        if lf {
+            (true, format!("(fp.rem {} {})", left_term, right_term))
+        } else {
+            // This is how Z3 defines rem, which is not part of the SMT-LIB2 standard.
+            (
+                false,
+                format!(
+                    "(let ((d {r})) (ite (>= d 0) (mod {l} d) (- (mod {l} d))))",
+                    l = left_term,
+                    r = right_term
+                ),
+            )
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn numeric_join(&self, expression: &Expression, path: &Rc<Path>) -> (bool, String) {
+        let mut var_type = &expression.infer_type();
+        if !(var_type.is_integer() || var_type.is_floating_point_number()) {
+            var_type = &ExpressionType::I128
+        };
+        let sort = self.get_sort_for(var_type);
+        (
+            var_type.is_floating_point_number(),
+            self.constant(&format!("{:?}", path), &sort),
+        )
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn numeric_boolean_op(&self, expression: &Expression) -> (bool, String) {
+        let term = self.get_as_term(expression);
+        (false, format!("(ite {} 1 0)", term))
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn numeric_bitwise_expression(&self, expression: &Expression) -> (bool, String) {
+        let term = self.get_as_bv_term(expression, 128);
+        (false, format!("(bv2nat {})", term))
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn numeric_bitwise_not(
+        &self,
+        operand: &Rc<AbstractValue>,
+        result_type: &ExpressionType,
+    ) -> (bool, String) {
+        if result_type.is_signed_integer() {
+            let (fp, neg) = self.numeric_neg(operand);
+            checked_assume!(!fp); // The Rust type system should prevent this
+            (false, format!("(- {} 1)", neg))
+        } else {
+            let (fp, term) = self.get_as_numeric_term(&operand.expression);
+            checked_assume!(!fp); // The Rust type system should prevent this
+            let max_term = self.get_constant_as_term(&result_type.max_value());
+            (false, format!("(- {} {})", max_term, term))
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn numeric_cast(
+        &self,
+        expression: &Expression,
+        target_type: &ExpressionType,
+    ) -> (bool, String) {
+        let name = format!("{:?}", expression);
+        match target_type {
+            ExpressionType::F32 => (true, self.constant(&name, F32_SORT)),
+            ExpressionType::F64 => (true, self.constant(&name, F64_SORT)),
+            _ => {
+                if target_type.is_integer() {
+                    if expression.infer_type() == *target_type {
+                        self.get_as_numeric_term(expression)
+                    } else {
+                        let bv_cast = self.bv_cast(expression, target_type, 128);
+                        if target_type.is_signed_integer() {
+                            (
+                                false,
+                                format!(
+                                    "(let ((b {})) (ite (bvslt b (_ bv0 128)) (- (bv2nat b) {}) (bv2nat b)))",
+                                    bv_cast, TWO_TO_THE_128
+                                ),
+                            )
+                        } else {
+                            (false, format!("(bv2nat {})", bv_cast))
+                        }
+                    }
+                } else {
+                    (false, self.constant(&name, INT_SORT))
+                }
+            }
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn numeric_const(
+        &self,
+        expression: &Expression,
+        const_domain: &ConstantDomain,
+    ) -> (bool, String) {
+        match const_domain {
+            ConstantDomain::False => (false, "0".to_string()),
+            ConstantDomain::True => (false, "1".to_string()),
+            ConstantDomain::F32(..) | ConstantDomain::F64(..) => {
+                (true, self.get_as_term(expression))
+            }
+            _ => (false, self.get_as_term(expression)),
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn numeric_conditional(
+        &self,
+        condition: &Rc<AbstractValue>,
+        consequent: &Rc<AbstractValue>,
+        alternate: &Rc<AbstractValue>,
+    ) -> (bool, String) {
+        let condition_term = self.get_as_bool_term(&(**condition).expression);
+        let (cf, consequent_term) = self.get_as_numeric_term(&(**consequent).expression);
+        let (af, alternate_term) = self.get_as_numeric_term(&(**alternate).expression);
+        checked_assume_eq!(cf, af);
+        (
+            cf,
+            format!(
+                "(ite {} {} {})",
+                condition_term, consequent_term, alternate_term
+            ),
+        )
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn numeric_neg(&self, operand: &Rc<AbstractValue>) -> (bool, String) {
+        let (is_float, operand_term) = self.get_as_numeric_term(&(**operand).expression);
+        if is_float {
+            (true, format!("(fp.neg {})", operand_term))
+        } else {
+            (false, format!("(- {})", operand_term))
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn numeric_reference(&self, path: &Rc<Path>) -> (bool, String) {
+        (false, self.constant(&format!("{:?}", path), INT_SORT))
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn numeric_shl(&self, left: &Rc<AbstractValue>, right: &Rc<AbstractValue>) -> (bool, String) {
+        let (lf, left_term) = self.get_as_numeric_term(&(**left).expression);
+        let (rf, right_term) = self.get_as_numeric_term(&(**right).expression);
+        checked_assume!(!(lf || rf));
+        (false, format!("(* {} (^ 2 {}))", left_term, right_term))
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn numeric_shr(&self, left: &Rc<AbstractValue>, right: &Rc<AbstractValue>) -> (bool, String) {
+        let (lf, left_term) = self.get_as_numeric_term(&(**left).expression);
+        let (rf, right_term) = self.get_as_numeric_term(&(**right).expression);
+        checked_assume!(!(lf || rf));
+        (false, format!("(div {} (^ 2 {}))", left_term, right_term))
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn numeric_fresh_const(&self) -> (bool, String) {
+        (false, self.fresh_constant(INT_SORT))
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn numeric_variable(
+        &self,
+        expression: &Expression,
+        path: &Rc<Path>,
+        var_type: &ExpressionType,
+    ) -> (bool, String) {
+        use self::ExpressionType::*;
+        match var_type {
+            Bool | Reference | NonPrimitive => {
+                (false, self.constant(&format!("{:?}", path), INT_SORT))
+            }
+            F32 | F64 => (true, self.get_as_term(expression)),
+            _ => (false, self.get_as_term(expression)),
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn numeric_widen(&self, path: &Rc<Path>, operand: &Rc<AbstractValue>) -> (bool, String) {
+        use self::ExpressionType::*;
+        let expr_type = operand.expression.infer_type();
+        let expr_type = match expr_type {
+            Bool | Reference | NonPrimitive => ExpressionType::I128,
+            _ => expr_type,
+        };
+        let is_float = expr_type.is_floating_point_number();
+        let term = self.get_term_for_widened(path, operand, expr_type);
+        (is_float, term)
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn get_as_bool_term(&self, expression: &Expression) -> String {
+        match expression {
+            Expression::BitAnd { .. } | Expression::BitOr { .. } | Expression::BitXor { .. } => {
+                //todo: get operands as booleans and treat this operands as logical
+                let bv = self.get_as_bv_term(expression, 128);
+                format!("(not (= (bv2nat {}) 0))", bv)
+            }
+            Expression::CompileTimeConstant(const_domain) => match const_domain {
+                ConstantDomain::False => "false".to_string(),
+                ConstantDomain::True => "true".to_string(),
+                ConstantDomain::U128(val) => {
+                    if *val == 0 {
+                        "false".to_string()
+                    } else {
+                        "true".to_string()
+                    }
+                }
+                _ => self.get_as_term(expression),
+            },
+            Expression::ConditionalExpression {
+                condition,
+                consequent,
+                alternate,
+            } => {
+                let condition_term = self.get_as_bool_term(&(**condition).expression);
+                let consequent_term = self.get_as_bool_term(&(**consequent).expression);
+                let alternate_term = self.get_as_bool_term(&(**alternate).expression);
+                format!(
+                    "(ite {} {} {})",
+                    condition_term, consequent_term, alternate_term
+                )
+            }
+            Expression::Reference(path) => self.constant(&format!("&{:?}", path), BOOL_SORT),
+            Expression::Top | Expression::Bottom => self.fresh_constant(BOOL_SORT),
+            Expression::UninterpretedCall {
+                result_type: var_type,
+                path,
+                ..
+            }
+            | Expression::Variable { path, var_type } => {
+                if *var_type != ExpressionType::Bool {
+                    debug!("path {:?}, type {:?}", path, var_type);
+                }
+                self.get_term_for_path(path, BOOL_SORT)
+            }
+            Expression::Sanitized { operand, .. } | Expression::Tainted { operand, .. } => {
+                self.get_as_bool_term(&operand.expression)
+            }
+            Expression::Widen { path, operand } => {
+                self.get_term_for_widened(path, operand, ExpressionType::Bool)
+            }
+            _ => self.get_as_term(expression),
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn get_as_bv_term(&self, expression: &Expression, num_bits: u32) -> String {
+        match expression {
+            Expression::HeapBlock { .. } => {
+                let path = Path::get_as_path(AbstractValue::make_from(expression.clone(), 1));
+                self.bv_variable(&path, &expression.infer_type(), num_bits)
+            }
+            Expression::Add { left, right } => self.bv_binary(num_bits, left, right, "bvadd"),
+            Expression::AddOverflows {
+                left,
+                right,
+                result_type,
+            } => self.bv_add_overflows(left, right, result_type),
+            Expression::Div { left, right } => self.bv_binary(num_bits, left, right, "bvsdiv"),
+            Expression::Mul { left, right } => self.bv_binary(num_bits, left, right, "bvmul"),
+            Expression::MulOverflows {
+                left,
+                right,
+                result_type,
+            } => self.bv_mul_overflows(left, right, result_type),
+            Expression::Rem { left, right } => self.bv_binary(num_bits, left, right, "bvsrem"),
+            Expression::Sub { left, right } => self.bv_binary(num_bits, left, right, "bvsub"),
+            Expression::SubOverflows {
+                left,
+                right,
+                result_type,
+            } => self.bv_sub_overflows(left, right, result_type),
+            Expression::And { .. }
+            | Expression::Equals { .. }
+            | Expression::GreaterOrEqual { .. }
+            | Expression::GreaterThan { .. }
+            | Expression::LessOrEqual { .. }
+            | Expression::LessThan { .. }
+            | Expression::LogicalNot { .. }
+            | Expression::Ne { .. }
+            | Expression::Or { .. } => self.bv_boolean_op(expression, num_bits),
+            Expression::BitAnd { left, right } => self.bv_binary(num_bits, left, right, "bvand"),
+            Expression::BitNot { operand, .. } => self.bv_not(num_bits, operand),
+            Expression::BitOr { left, right } => self.bv_binary(num_bits, left, right, "bvor"),
+            Expression::BitXor { left, right } => self.bv_binary(num_bits, left, right, "bvxor"),
+            Expression::Cast { target_type, .. } => self.bv_cast(expression, target_type, num_bits),
+            Expression::CompileTimeConstant(const_domain) => {
+                self.bv_constant(num_bits, const_domain)
+            }
+            Expression::ConditionalExpression {
+                condition,
+                consequent,
+                alternate,
+            } => self.bv_conditional(num_bits, condition, consequent, alternate),
+            Expression::IntrinsicBitVectorUnary { name, .. } => match name {
+                KnownNames::StdIntrinsicsCtlz
+                | KnownNames::StdIntrinsicsCtlzNonzero
+                | KnownNames::StdIntrinsicsCtpop
+                | KnownNames::StdIntrinsicsCttz
+                | KnownNames::StdIntrinsicsCttzNonzero => self.numeric_fresh_const().1,
+                _ => self.bv_fresh_const(num_bits),
+            },
+            Expression::Join { path, .. } => self.bv_join(num_bits, path),
+            Expression::Neg { operand } => self.bv_neg(num_bits, operand),
+            Expression::Reference(path) => self.bv_reference(num_bits, path),
+            Expression::Shl { left, right } => self.bv_binary(num_bits, left, right, "bvshl"),
+            Expression::Shr {
+                left,
+                right,
+                result_type,
+            } => self.bv_shr_by(num_bits, left, right, result_type),
+            Expression::Top | Expression::Bottom => self.bv_fresh_const(num_bits),
+            Expression::UninterpretedCall {
+                result_type: var_type,
+                path,
+                ..
+            }
+            | Expression::Variable { path, var_type } => self.bv_variable(path, var_type, num_bits),
+            Expression::Sanitized { operand, .. } | Expression::Tainted { operand, .. } => {
+                self.get_as_bv_term(&operand.expression, num_bits)
+            }
+            Expression::Widen { path, operand } => self.bv_widen(path, operand, num_bits),
+            _ => self.get_as_term(expression),
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn bv_binary(
+        &self,
+        num_bits: u32,
+        left: &Rc<AbstractValue>,
+        right: &Rc<AbstractValue>,
+        operation: &str,
+    ) -> String {
+        let left_term = self.get_as_bv_term(&(**left).expression, num_bits);
+        let right_term = self.get_as_bv_term(&(**right).expression, num_bits);
+        format!("({} {} {})", operation, left_term, right_term)
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn bv_neg(&self, num_bits: u32, operand: &Rc<AbstractValue>) -> String {
+        let term = self.get_as_bv_term(&(**operand).expression, num_bits);
+        format!("(bvneg {})", term)
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn bv_not(&self, num_bits: u32, operand: &Rc<AbstractValue>) -> String {
+        let term = self.get_as_bv_term(&(**operand).expression, num_bits);
+        format!("(bvnot {})", term)
+    }
+
+    /// The SMT-LIB2 equivalent of negating Z3_mk_bvadd_no_overflow and Z3_mk_bvadd_no_underflow.
+    #[logfn_inputs(TRACE)]
+    fn bv_add_overflows(
+        &self,
+        left: &Rc<AbstractValue>,
+        right: &Rc<AbstractValue>,
+        result_type: &ExpressionType,
+    ) -> String {
+        let num_bits = u32::from(result_type.bit_length());
+        let l = self.get_as_bv_term(&(**left).expression, num_bits);
+        let r = self.get_as_bv_term(&(**right).expression, num_bits);
+        if result_type.is_signed_integer() {
+            format!(
+                "(let ((l {l}) (r {r}) (z (_ bv0 {n}))) (or \
+                 (and (bvsgt l z) (bvsgt r z) (bvslt (bvadd l r) z)) \
+                 (and (bvslt l z) (bvslt r z) (bvsge (bvadd l r) z))))",
+                l = l,
+                r = r,
+                n = num_bits
+            )
+        } else {
+            format!(
+                "(= ((_ extract {n} {n}) (bvadd ((_ zero_extend 1) {l}) ((_ zero_extend 1) {r}))) #b1)",
+                l = l,
+                r = r,
+                n = num_bits
+            )
+        }
+    }
+
+    /// The SMT-LIB2 equivalent of negating Z3_mk_bvsub_no_underflow and Z3_mk_bvsub_no_overflow.
+    #[logfn_inputs(TRACE)]
+    fn bv_sub_overflows(
+        &self,
+        left: &Rc<AbstractValue>,
+        right: &Rc<AbstractValue>,
+        result_type: &ExpressionType,
+    ) -> String {
+        let num_bits = u32::from(result_type.bit_length());
+        let l = self.get_as_bv_term(&(**left).expression, num_bits);
+        let r = self.get_as_bv_term(&(**right).expression, num_bits);
+        if result_type.is_signed_integer() {
+            format!(
+                "(let ((l {l}) (r {r}) (z (_ bv0 {n}))) (or \
+                 (and (bvsge l z) (bvslt r z) (bvslt (bvsub l r) z)) \
+                 (and (bvslt l z) (bvsgt r z) (bvsgt (bvsub l r) z))))",
+                l = l,
+                r = r,
+                n = num_bits
+            )
+        } else {
+            format!("(bvult {} {})", l, r)
+        }
+    }
+
+    /// The SMT-LIB2 equivalent of negating Z3_mk_bvmul_no_overflow and Z3_mk_bvmul_no_underflow.
+    #[logfn_inputs(TRACE)]
+    fn bv_mul_overflows(
+        &self,
+        left: &Rc<AbstractValue>,
+        right: &Rc<AbstractValue>,
+        result_type: &ExpressionType,
+    ) -> String {
+        let num_bits = u32::from(result_type.bit_length());
+        let l = self.get_as_bv_term(&(**left).expression, num_bits);
+        let r = self.get_as_bv_term(&(**right).expression, num_bits);
+        if result_type.is_signed_integer() {
+            format!(
+                "(let ((p (bvmul ((_ sign_extend {n}) {l}) ((_ sign_extend {n}) {r})))) \
+                 (not (= ((_ sign_extend {n}) ((_ extract {m} 0) p)) p)))",
+                l = l,
+                r = r,
+                n = num_bits,
+                m = num_bits - 1
+            )
+        } else {
+            format!(
+                "(not (= ((_ extract {h} {n}) (bvmul ((_ zero_extend {n}) {l}) ((_ zero_extend {n}) {r}))) (_ bv0 {n})))",
+                l = l,
+                r = r,
+                n = num_bits,
+                h = 2 * num_bits - 1
+            )
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn bv_boolean_op(&self, expression: &Expression, num_bits: u32) -> String {
+        let term = self.get_as_term(expression);
+        // term results in a boolean, but we want a bit vector.
+        format!("(ite {} (_ bv1 {n}) (_ bv0 {n}))", term, n = num_bits)
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn bv_cast(
+        &self,
+        expression: &Expression,
+        target_type: &ExpressionType,
+        num_bits: u32,
+    ) -> String {
+        let mask = self.bv_constant(num_bits, &target_type.max_value());
+        let sort = format!("(_ BitVec {})", num_bits);
+        let term = self.constant(&format!("{:?}", expression), &sort);
+        format!("(bvand {} {})", mask, term)
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn bv_constant(&self, num_bits: u32, const_domain: &ConstantDomain) -> String {
+        match const_domain {
+            ConstantDomain::Char(v) => bv_numeral(*v as u128, num_bits),
+            ConstantDomain::False => bv_numeral(0, num_bits),
+            ConstantDomain::F32(..) | ConstantDomain::F64(..) => {
+                self.get_constant_as_term(const_domain)
+            }
+            ConstantDomain::I128(v) => bv_numeral(*v as u128, num_bits),
+            ConstantDomain::U128(v) => bv_numeral(*v, num_bits),
+            ConstantDomain::True => bv_numeral(1, num_bits),
+            _ => self.bv_fresh_const(num_bits),
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn bv_conditional(
+        &self,
+        num_bits: u32,
+        condition: &Rc<AbstractValue>,
+        consequent: &Rc<AbstractValue>,
+        alternate: &Rc<AbstractValue>,
+    ) -> String {
+        let condition_term = self.get_as_bool_term(&(**condition).expression);
+        let consequent_term = self.get_as_bv_term(&(**consequent).expression, num_bits);
+        let alternate_term = self.get_as_bv_term(&(**alternate).expression, num_bits);
+        format!(
+            "(ite {} {} {})",
+            condition_term, consequent_term, alternate_term
+        )
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn bv_join(&self, num_bits: u32, path: &Rc<Path>) -> String {
+        let sort = format!("(_ BitVec {})", num_bits);
+        self.constant(&format!("{:?}", path), &sort)
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn bv_reference(&self, num_bits: u32, path: &Rc<Path>) -> String {
+        let sort = format!("(_ BitVec {})", num_bits);
+        self.constant(&format!("{:?}", path), &sort)
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn bv_shr_by(
+        &self,
+        num_bits: u32,
+        left: &Rc<AbstractValue>,
+        right: &Rc<AbstractValue>,
+        result_type: &ExpressionType,
+    ) -> String {
+        let left_term = self.get_as_bv_term(&(**left).expression, num_bits);
+        let right_term = self.get_as_bv_term(&(**right).expression, num_bits);
+        if result_type.is_signed_integer() {
+            format!("(bvashr {} {})", left_term, right_term)
+        } else {
+            format!("(bvlshr {} {})", left_term, right_term)
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn bv_fresh_const(&self, num_bits: u32) -> String {
+        self.fresh_constant(&format!("(_ BitVec {})", num_bits))
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn bv_variable(&self, path: &Rc<Path>, var_type: &ExpressionType, num_bits: u32) -> String {
+        use self::ExpressionType::*;
+        let name = format!("{:?}", path);
+        match var_type {
+            Bool | Char | I8 | I16 | I32 | I64 | I128 | Isize | U8 | U16 | U32 | U64 | U128
+            | Usize | Reference => self.constant(&name, &format!("(_ BitVec {})", num_bits)),
+            F32 => self.constant(&name, F32_SORT),
+            F64 => self.constant(&name, F64_SORT),
+            NonPrimitive => self.bv_fresh_const(num_bits),
+        }
+    }
+
+    #[logfn_inputs(TRACE)]
+    fn bv_widen(&self, path: &Rc<Path>, operand: &Rc<AbstractValue>, num_bits: u32) -> String {
+        self.bv_variable(path, &operand.expression.infer_type(), num_bits)
+    }
+}
+
+/// An SMT-LIB2 integer literal for the given value.
+fn int_numeral(value: i128) -> String {
+    if value < 0 {
+        format!("(- {})", (value as u128).wrapping_neg())
+    } else {
+        format!("{}", value)
+    }
+}
+
+/// An SMT-LIB2 bit vector literal of the given length for the given value, truncated to fit,
+/// as Z3_mk_numeral does.
+fn bv_numeral(value: u128, num_bits: u32) -> String {
+    let value = if num_bits < 128 {
+        value & ((1u128 << num_bits) - 1)
+    } else {
+        value
+    };
+    format!("(_ bv{} {})", value, num_bits)
+}
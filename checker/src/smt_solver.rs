@@ -7,6 +7,8 @@ use crate::expression::Expression;
 
 use mirai_annotations::{get_model_field, precondition, set_model_field};
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
 /// The result of using the solver to solve an expression.
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Hash)]
@@ -19,6 +21,82 @@ pub enum SmtResult {
     Undefined,
 }
 
+/// Limits how much effort the solver may spend on a single query.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum SolverLimit {
+    /// A number of milliseconds, so whether a query is decided can depend on the machine.
+    Time(u64),
+    /// A number of Z3 resource units (rlimit), which are counted the same way on every machine.
+    Resources(u64),
+}
+
+/// The result of an earlier query, as kept in the SMT result cache of the summary store.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct CachedSmtResult {
+    pub result: SmtResult,
+    /// The solver limit in effect when the result was obtained.
+    pub limit: SolverLimit,
+}
+
+impl CachedSmtResult {
+    /// True if a query with the given solver limit can use this result. An Undefined result is
+    /// only reused by queries that do not allow the solver more effort. If the limit is a
+    /// resource limit, the results must also be the ones the solver would have produced, so that
+    /// the analysis stays reproducible, which rules out results obtained with a time limit and
+    /// decided results obtained with more resources than are allowed now.
+    pub fn can_be_reused_with(&self, limit: SolverLimit) -> bool {
+        let undefined = self.result == SmtResult::Undefined;
+        match (self.limit, limit) {
+            (SolverLimit::Time(cached), SolverLimit::Time(current)) => {
+                !undefined || cached >= current
+            }
+            (SolverLimit::Resources(_), SolverLimit::Time(_)) => !undefined,
+            (SolverLimit::Time(_), SolverLimit::Resources(_)) => false,
+            (SolverLimit::Resources(cached), SolverLimit::Resources(current)) => {
+                if undefined {
+                    cached >= current
+                } else {
+                    cached <= current
+                }
+            }
+        }
+    }
+}
+
+/// The version of the translation of MIRAI expressions to solver terms, by Z3Solver as well as
+/// by SmtLibSolver. Increment this whenever the translation changes, so that results cached for
+/// the earlier translation are no longer used.
+pub const SMT_ENCODING_VERSION: u32 = 1;
+
+/// Returns the key of the SMT result cache entry for the query whether the given condition
+/// can be true when the given path condition is true, when asked of the given solver backend.
+/// The key is a 128 bit hash of the serialized form of the two expressions, together with the
+/// backend, the version of MIRAI and the version of the encoding of expressions, so structurally
+/// identical queries made by different functions, or by different builds, share an entry only if
+/// they are answered by the same solver in the same way.
+pub fn smt_result_cache_key(
+    backend: &str,
+    path_condition: &Expression,
+    condition: &Expression,
+) -> Vec<u8> {
+    let serialized_query = bincode::serialize(&(
+        backend,
+        env!("CARGO_PKG_VERSION"),
+        SMT_ENCODING_VERSION,
+        path_condition,
+        condition,
+    ))
+    .unwrap();
+    let mut key = Vec::with_capacity(16);
+    for seed in 0u8..2 {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        serialized_query.hash(&mut hasher);
+        key.extend_from_slice(&hasher.finish().to_be_bytes());
+    }
+    key
+}
+
 /// The functionality that a solver must expose in order for MIRAI to use it.
 pub trait SmtSolver<SmtExpressionType> {
     /// Returns a string representation of the given expression for use in debugging.
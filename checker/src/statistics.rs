@@ -0,0 +1,165 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Support for reporting how much work went into the analysis of each function and how much of it
+// was actually checked. In relaxed mode, a function that times out or calls a function that cannot
+// be summarized is silently assumed to be correct, so without this report there is no way to tell
+// how much code MIRAI really covers.
+
+use crate::body_visitor::BodyVisitor;
+use crate::expression::Expression;
+use crate::k_limits;
+use crate::smt_solver::{SmtResult, SmtSolver};
+
+use serde::Serialize;
+use std::cell::Cell;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::Duration;
+
+/// The reason why the analysis of a function gave up and assumed the function to be correct.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum AngelicReason {
+    /// The analysis took longer than the max_analysis_time_for_body k-limit.
+    Timeout,
+    /// The analysis took more than the max_fuel_for_body k-limit number of steps.
+    OutOfFuel,
+    /// A called function, identified by its summary key, could not be summarized.
+    MissingSummary { callee: String },
+    /// The function to call could not be determined.
+    UnknownCallee,
+    /// The function contains inline assembly.
+    InlineAssembly,
+}
+
+/// The statistics for the analysis of a single function as an analysis root.
+#[derive(Debug, Serialize)]
+pub struct FunctionStatistics {
+    pub summary_key: String,
+    /// The generic arguments, if this is an instance of a generic function.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generic_arguments: Option<String>,
+    pub analysis_time_in_milliseconds: u128,
+    pub blocks: usize,
+    pub fixpoint_iterations: usize,
+    /// True if the fixed point loop was stopped by the max_fixpoint_iterations k-limit.
+    pub fixpoint_limit_reached: bool,
+    pub solver_queries: SolverStatistics,
+    pub angelic: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub angelic_reason: Option<AngelicReason>,
+}
+
+/// The number of queries made to the SMT solver, by outcome.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct SolverStatistics {
+    pub satisfiable: usize,
+    pub unsatisfiable: usize,
+    pub undefined: usize,
+}
+
+/// The statistics for all of the functions analyzed in a crate.
+#[derive(Debug, Serialize)]
+pub struct CrateStatistics<'a> {
+    #[serde(rename = "crate")]
+    pub crate_name: String,
+    pub functions: &'a [FunctionStatistics],
+}
+
+impl FunctionStatistics {
+    /// Collects the statistics kept by a body visitor that has just visited an analysis root.
+    /// The analysis time and solver queries are filled in by the caller.
+    pub fn for_body<E>(body_visitor: &BodyVisitor<'_, '_, '_, E>) -> FunctionStatistics {
+        FunctionStatistics {
+            summary_key: body_visitor.function_name.to_string(),
+            generic_arguments: None,
+            analysis_time_in_milliseconds: 0,
+            blocks: body_visitor.mir.basic_blocks().len(),
+            fixpoint_iterations: body_visitor.fixpoint_iterations,
+            fixpoint_limit_reached: body_visitor.fixpoint_iterations
+                > k_limits::max_fixpoint_iterations(),
+            solver_queries: SolverStatistics::default(),
+            angelic: body_visitor.assume_function_is_angelic,
+            angelic_reason: body_visitor.angelic_reason.clone(),
+        }
+    }
+
+    pub fn set_analysis_time(&mut self, duration: Duration) {
+        self.analysis_time_in_milliseconds = duration.as_millis();
+    }
+}
+
+impl<'a> CrateStatistics<'a> {
+    /// Writes the statistics as JSON to the file at the given path, replacing any existing content.
+    pub fn write_to(&self, path: &Path) -> std::io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+}
+
+/// An SMT solver that counts the outcomes of the queries it passes on to another solver.
+pub struct CountingSolver<'a, E> {
+    solver: &'a mut dyn SmtSolver<E>,
+    statistics: Cell<SolverStatistics>,
+}
+
+impl<'a, E> CountingSolver<'a, E> {
+    pub fn new(solver: &'a mut dyn SmtSolver<E>) -> CountingSolver<'a, E> {
+        CountingSolver {
+            solver,
+            statistics: Cell::new(SolverStatistics::default()),
+        }
+    }
+
+    /// The number of queries made so far, by outcome.
+    pub fn statistics(&self) -> SolverStatistics {
+        self.statistics.get()
+    }
+}
+
+impl<'a, E> SmtSolver<E> for CountingSolver<'a, E> {
+    fn as_debug_string(&self, expression: &E) -> String {
+        self.solver.as_debug_string(expression)
+    }
+
+    fn assert(&self, expression: &E) {
+        self.solver.assert(expression)
+    }
+
+    fn backtrack(&self) {
+        self.solver.backtrack()
+    }
+
+    fn get_as_smt_predicate(&self, mirai_expression: &Expression) -> E {
+        self.solver.get_as_smt_predicate(mirai_expression)
+    }
+
+    fn get_model_as_string(&self) -> String {
+        self.solver.get_model_as_string()
+    }
+
+    fn get_solver_state_as_string(&self) -> String {
+        self.solver.get_solver_state_as_string()
+    }
+
+    fn set_backtrack_position(&self) {
+        self.solver.set_backtrack_position()
+    }
+
+    fn solve(&self) -> SmtResult {
+        let result = self.solver.solve();
+        let mut statistics = self.statistics.get();
+        match result {
+            SmtResult::Satisfiable => statistics.satisfiable += 1,
+            SmtResult::Unsatisfiable => statistics.unsatisfiable += 1,
+            SmtResult::Undefined => statistics.undefined += 1,
+        }
+        self.statistics.set(statistics);
+        result
+    }
+}
@@ -8,7 +8,9 @@ use crate::abstract_value::AbstractValueTrait;
 use crate::constant_domain::FunctionReference;
 use crate::environment::Environment;
 use crate::expression::Expression;
-use crate::path::Path;
+use crate::incremental::BodyRecord;
+use crate::path::{Path, PathEnum};
+use crate::smt_solver::CachedSmtResult;
 use crate::utils;
 
 use log_derive::{logfn, logfn_inputs};
@@ -84,8 +86,11 @@ pub struct Summary {
     pub side_effects: Vec<(Rc<Path>, Rc<AbstractValue>)>,
 
     // A condition that should hold subsequent to a call that completes normally.
+    // The condition is expressed in terms of the state on entry to the function, which is also
+    // how the values of old!(..) expressions are represented.
     // Callers should substitute parameter values with argument values and simplify the results
-    // under the current path condition.
+    // under the path condition and the state prior to the call, except for the result of the
+    // call, which is only known after the call.
     // The resulting value should be conjoined to the current path condition.
     pub post_condition: Option<Rc<AbstractValue>>,
 
@@ -103,6 +108,14 @@ pub struct Summary {
     // under the current path condition. They should then update their current state to reflect the
     // side-effects of the call for the unwind control paths, following the call.
     pub unwind_side_effects: Vec<(Rc<Path>, Rc<AbstractValue>)>,
+
+    // Locations that a contract function declares (via modifies!) to be modified by the function
+    // it stands in for. The contract does not say what the new values are, so callers should
+    // substitute parameter values with argument values and forget what they know about the
+    // values at these locations, before updating their state with the side-effects of the call.
+    // This is empty for summaries computed from the body of the actual function, since their
+    // side-effects are precise.
+    pub modified_paths: Vec<Rc<Path>>,
 }
 
 /// Bundles together the condition of a precondition with the provenance (place where defined) of
@@ -210,6 +223,7 @@ impl Summary {
             post_condition: other.post_condition.clone(),
             unwind_condition: None,
             unwind_side_effects,
+            modified_paths: other.modified_paths.clone(),
         }
     }
 
@@ -259,27 +273,35 @@ pub fn summarize(
     post_condition: &Option<Rc<AbstractValue>>,
     unwind_condition: Option<Rc<AbstractValue>>,
     unwind_environment: &Environment,
+    modified_paths: &[Rc<Path>],
     tcx: TyCtxt<'_>,
 ) -> Summary {
     trace!(
-        "summarize env {:?} pre {:?} post {:?} unwind cond {:?} unwind env {:?}",
+        "summarize env {:?} pre {:?} post {:?} unwind cond {:?} unwind env {:?} modified {:?}",
         exit_environment,
         preconditions,
         post_condition,
         unwind_condition,
-        unwind_environment
+        unwind_environment,
+        modified_paths
     );
     let mut preconditions: Vec<Precondition> = add_provenance(preconditions, tcx);
     let mut side_effects = if let Some(exit_environment) = exit_environment {
-        extract_side_effects(exit_environment, argument_count)
+        let mut side_effects = extract_side_effects(exit_environment, argument_count);
+        side_effects.extend(extract_ghost_static_side_effects(exit_environment, tcx));
+        side_effects
     } else {
         vec![]
     };
     let mut unwind_side_effects = extract_side_effects(unwind_environment, argument_count);
 
+    let mut modified_paths = modified_paths.to_vec();
+
     preconditions.sort();
     side_effects.sort();
     unwind_side_effects.sort();
+    modified_paths.sort();
+    modified_paths.dedup();
 
     Summary {
         is_computed: true,
@@ -289,6 +311,7 @@ pub fn summarize(
         post_condition: post_condition.clone(),
         unwind_condition,
         unwind_side_effects,
+        modified_paths,
     }
 }
 
@@ -351,6 +374,34 @@ fn extract_side_effects(
     result
 }
 
+/// Returns a list of (path, value) pairs where each path is rooted by a ghost static (see the
+/// ghost_static! macro) and the value is an update of the static, rather than just the value
+/// that it had on entry to the function.
+#[logfn_inputs(TRACE)]
+fn extract_ghost_static_side_effects(
+    env: &Environment,
+    tcx: TyCtxt<'_>,
+) -> Vec<(Rc<Path>, Rc<AbstractValue>)> {
+    fn is_rooted_by_ghost_static(path: &Rc<Path>, tcx: TyCtxt<'_>) -> bool {
+        match &path.value {
+            PathEnum::QualifiedPath { qualifier, .. } => is_rooted_by_ghost_static(qualifier, tcx),
+            PathEnum::StaticVariable {
+                def_id: Some(def_id),
+                ..
+            } => utils::is_ghost_static(tcx, *def_id),
+            _ => false,
+        }
+    }
+    env.value_map
+        .iter()
+        .filter(|(path, value)| {
+            is_rooted_by_ghost_static(path, tcx)
+                && !matches!(&value.expression, Expression::Variable { path: vpath, .. } if vpath == *path)
+        })
+        .map(|(path, value)| (path.clone(), value.clone()))
+        .collect()
+}
+
 /// Adds roots for all new heap allocated objects that are reachable by the caller.
 #[logfn_inputs(TRACE)]
 fn extract_reachable_heap_allocations(
@@ -379,6 +430,106 @@ fn extract_reachable_heap_allocations(
     }
 }
 
+/// The name of the Sled tree that holds the records used by incremental analysis.
+const BODY_RECORDS_TREE: &str = "body_records";
+const SMT_RESULTS_TREE: &str = "smt_results";
+
+/// The key under which a summary store records the version of the format of its entries.
+/// The # ensures that this cannot be the summary key of a function.
+const FORMAT_VERSION_KEY: &str = "#format_version";
+
+/// The version of the (bincode) format of the entries in a summary store. Increment this
+/// whenever the serialized form of Summary, BodyRecord, CachedSmtResult or any of the types
+/// they contain (such as Expression and Path) changes, so that stores written by older builds
+/// of MIRAI are discarded rather than misread. If the change can be undone while reading, extend
+/// bring_up_to_date so that the summaries of such stores, and notably of the seed store in
+/// binaries/summary_store.tar, are converted rather than discarded.
+///
+/// Stores without a version, such as the seed store, lack Summary::modified_paths. Version 2
+/// changed the encoding of cached SMT results and version 3 restored the encoding that stores
+/// without a version use for KnownNames, so stores of versions 1 and 2 cannot be converted.
+pub const FORMAT_VERSION: u32 = 3;
+
+/// Returns the format version recorded in the given summary store, if any.
+fn format_version_of(db: &Db) -> Option<u32> {
+    let value = db.get(FORMAT_VERSION_KEY).ok()??;
+    let mut bytes = [0u8; 4];
+    if value.len() != bytes.len() {
+        return None;
+    }
+    bytes.copy_from_slice(value.as_ref());
+    Some(u32::from_le_bytes(bytes))
+}
+
+/// The form in which summaries were persisted before format versions were recorded.
+/// Its fields must be the same, and in the same order, as the leading fields of Summary.
+#[derive(Deserialize)]
+struct SummaryWithoutModifiedPaths {
+    is_computed: bool,
+    is_angelic: bool,
+    preconditions: Vec<Precondition>,
+    side_effects: Vec<(Rc<Path>, Rc<AbstractValue>)>,
+    post_condition: Option<Rc<AbstractValue>>,
+    unwind_condition: Option<Rc<AbstractValue>>,
+    unwind_side_effects: Vec<(Rc<Path>, Rc<AbstractValue>)>,
+}
+
+impl From<SummaryWithoutModifiedPaths> for Summary {
+    fn from(legacy: SummaryWithoutModifiedPaths) -> Summary {
+        Summary {
+            is_computed: legacy.is_computed,
+            is_angelic: legacy.is_angelic,
+            preconditions: legacy.preconditions,
+            side_effects: legacy.side_effects,
+            post_condition: legacy.post_condition,
+            unwind_condition: legacy.unwind_condition,
+            unwind_side_effects: legacy.unwind_side_effects,
+            modified_paths: Vec::new(),
+        }
+    }
+}
+
+/// Makes the entries of the given store readable with the current format and marks the store
+/// as having the current format. The summaries of a store without a recorded format are
+/// converted, so that the summaries of the seed store survive. Summaries that cannot be
+/// converted, the summaries of stores written with some other format, the body records and
+/// the cached SMT results are all discarded, so that they are recomputed rather than misread.
+#[logfn_inputs(TRACE)]
+fn bring_up_to_date(db: &Db) -> sled::Result<()> {
+    match format_version_of(db) {
+        Some(FORMAT_VERSION) => return Ok(()),
+        None if db.iter().next().is_none() => {}
+        None => {
+            info!("converting the summaries of a summary store without a format version");
+            let entries = db.iter().collect::<sled::Result<Vec<_>>>()?;
+            for (key, value) in entries {
+                match bincode::deserialize::<SummaryWithoutModifiedPaths>(value.deref()) {
+                    Ok(legacy) => {
+                        let summary: Summary = legacy.into();
+                        db.insert(key, bincode::serialize(&summary).unwrap())?;
+                    }
+                    Err(err) => {
+                        warn!(
+                            "discarding the summary of {} since it cannot be converted: {}",
+                            String::from_utf8_lossy(key.as_ref()),
+                            err
+                        );
+                        db.remove(key)?;
+                    }
+                }
+            }
+        }
+        Some(_) => {
+            info!("discarding the entries of a summary store with an old format");
+            db.clear()?;
+        }
+    }
+    db.drop_tree(BODY_RECORDS_TREE)?;
+    db.drop_tree(SMT_RESULTS_TREE)?;
+    db.insert(FORMAT_VERSION_KEY, &FORMAT_VERSION.to_le_bytes()[..])?;
+    Ok(())
+}
+
 /// A persistent map from summary key to Summary, along with a transient cache from DefId to
 /// Summary. The latter is cleared after every outer fixed point loop iteration.
 /// Also tracks which definitions depend on (use) any particular Summary.
@@ -429,6 +580,9 @@ impl<'a, 'tcx: 'a> PersistentSummaryCache<'tcx> {
             debug!("{} ", err);
             assume_unreachable!();
         });
+        if let Err(err) = bring_up_to_date(&db) {
+            println!("unable to bring the summary database up to date: {:?}", err);
+        }
         PersistentSummaryCache {
             db,
             def_id_cache: HashMap::new(),
@@ -473,6 +627,18 @@ impl<'a, 'tcx: 'a> PersistentSummaryCache<'tcx> {
         store_path
     }
 
+    /// Forgets all summaries that were obtained or computed during the current run, other than
+    /// those in the persistent store, so that they are obtained or computed again when next
+    /// needed. This is necessary when summaries in the store have changed since they were used.
+    #[logfn_inputs(TRACE)]
+    pub fn clear_transient_caches(&mut self) {
+        self.def_id_cache.clear();
+        self.typed_cache.clear();
+        self.typed_cache_table.clear();
+        self.reference_cache.clear();
+        self.typed_reference_cache.clear();
+    }
+
     /// Returns (and caches) a string that uniquely identifies a definition to serve as a key to
     /// the summary cache, which is a key value store. The string will always be the same as
     /// long as the definition does not change its name or location, so it can be used to
@@ -611,10 +777,17 @@ impl<'a, 'tcx: 'a> PersistentSummaryCache<'tcx> {
     }
 
     /// Helper for get_summary_for and get_persistent_summary_for.
+    /// An entry that cannot be decoded is treated as missing, so that it gets recomputed.
     #[logfn(TRACE)]
     fn get_persistent_summary_for_db(db: &Db, persistent_key: &str) -> Option<Summary> {
         if let Ok(Some(pinned_value)) = db.get(persistent_key.as_bytes()) {
-            Some(bincode::deserialize(pinned_value.deref()).unwrap())
+            match bincode::deserialize(pinned_value.deref()) {
+                Ok(summary) => Some(summary),
+                Err(err) => {
+                    warn!("cannot decode the summary of {}: {}", persistent_key, err);
+                    None
+                }
+            }
         } else {
             None
         }
@@ -646,6 +819,56 @@ impl<'a, 'tcx: 'a> PersistentSummaryCache<'tcx> {
         }
     }
 
+    /// Returns the record of the most recent analysis of the function body with the given
+    /// summary key, if there is one.
+    #[logfn_inputs(TRACE)]
+    pub fn get_body_record(&self, persistent_key: &str) -> Option<BodyRecord> {
+        let tree = self.db.open_tree(BODY_RECORDS_TREE).ok()?;
+        if let Ok(Some(pinned_value)) = tree.get(persistent_key.as_bytes()) {
+            bincode::deserialize(pinned_value.deref()).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Replaces the record of the most recent analysis of the function body with the given
+    /// summary key.
+    #[logfn_inputs(TRACE)]
+    pub fn set_body_record(&self, persistent_key: &str, record: &BodyRecord) {
+        let serialized_record = bincode::serialize(record).unwrap();
+        let result = self
+            .db
+            .open_tree(BODY_RECORDS_TREE)
+            .and_then(|tree| tree.insert(persistent_key.as_bytes(), serialized_record));
+        if result.is_err() {
+            println!("unable to set key in body records database: {:?}", result);
+        }
+    }
+
+    /// Returns the cached result of the SMT query with the given key, if any.
+    /// See smt_solver::smt_result_cache_key.
+    pub fn get_smt_result(&self, key: &[u8]) -> Option<CachedSmtResult> {
+        let tree = self.db.open_tree(SMT_RESULTS_TREE).ok()?;
+        if let Ok(Some(pinned_value)) = tree.get(key) {
+            bincode::deserialize(pinned_value.deref()).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Caches the result of the SMT query with the given key, replacing any earlier result.
+    #[logfn_inputs(TRACE)]
+    pub fn set_smt_result(&self, key: &[u8], result: &CachedSmtResult) {
+        let serialized_result = bincode::serialize(result).unwrap();
+        let result = self
+            .db
+            .open_tree(SMT_RESULTS_TREE)
+            .and_then(|tree| tree.insert(key, serialized_result));
+        if result.is_err() {
+            println!("unable to set key in SMT results database: {:?}", result);
+        }
+    }
+
     /// Sets or updates the DefId cache so that from now on def_id maps to the given summary.
     #[logfn_inputs(TRACE)]
     pub fn set_summary_for(&mut self, def_id: DefId, summary: Summary) -> Option<Summary> {
@@ -660,3 +883,86 @@ impl<'a, 'tcx: 'a> PersistentSummaryCache<'tcx> {
         self.def_id_cache.insert(def_id, summary)
     }
 }
+
+/// Provides access to the contents of a summary store without the need for a type context.
+/// This is used by tools that inspect the store or move summaries between stores.
+pub struct SummaryStore {
+    db: Db,
+}
+
+impl Debug for SummaryStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        "SummaryStore".fmt(f)
+    }
+}
+
+impl SummaryStore {
+    /// Opens (or creates) the Sled database at the given path, which is typically a directory
+    /// named .summary_store.sled. The entries of the store are first brought up to date with
+    /// the current format (see bring_up_to_date).
+    #[logfn_inputs(TRACE)]
+    pub fn open(store_path: &std::path::Path) -> sled::Result<SummaryStore> {
+        let db = Config::default().path(store_path).open()?;
+        bring_up_to_date(&db)?;
+        Ok(SummaryStore { db })
+    }
+
+    /// Returns the version of the format of the entries of this store, if it is recorded.
+    /// Entries can only be decoded if this is FORMAT_VERSION.
+    pub fn format_version(&self) -> Option<u32> {
+        format_version_of(&self.db)
+    }
+
+    /// Returns the keys of all summaries in the store that start with the given prefix, in order.
+    #[logfn_inputs(TRACE)]
+    pub fn keys_with_prefix(&self, prefix: &str) -> sled::Result<Vec<String>> {
+        let mut keys = Vec::new();
+        for entry in self.db.scan_prefix(prefix.as_bytes()) {
+            let (key, _) = entry?;
+            if key.as_ref() == FORMAT_VERSION_KEY.as_bytes() {
+                continue;
+            }
+            keys.push(String::from_utf8_lossy(key.as_ref()).into_owned());
+        }
+        Ok(keys)
+    }
+
+    /// Returns the summary stored for the given key, if any, or a description of why the stored
+    /// summary cannot be read.
+    #[logfn_inputs(TRACE)]
+    pub fn get(&self, key: &str) -> std::result::Result<Option<Summary>, String> {
+        let value = self
+            .db
+            .get(key.as_bytes())
+            .map_err(|e| format!("cannot read the summary of {}: {}", key, e))?;
+        match value {
+            Some(pinned_value) => bincode::deserialize(pinned_value.deref())
+                .map(Some)
+                .map_err(|e| {
+                    let mut message = format!("cannot decode the summary of {}: {}", key, e);
+                    if self.format_version() != Some(FORMAT_VERSION) {
+                        message.push_str(
+                            " (the store was written by a version of MIRAI with another format)",
+                        );
+                    }
+                    message
+                }),
+            None => Ok(None),
+        }
+    }
+
+    /// Stores the given summary under the given key, replacing any existing summary.
+    #[logfn_inputs(TRACE)]
+    pub fn insert(&self, key: &str, summary: &Summary) -> sled::Result<()> {
+        let serialized_summary = bincode::serialize(summary).unwrap();
+        self.db.insert(key.as_bytes(), serialized_summary)?;
+        Ok(())
+    }
+
+    /// Makes sure that all changes have been written to disk.
+    #[logfn_inputs(TRACE)]
+    pub fn flush(&self) -> sled::Result<()> {
+        self.db.flush()?;
+        Ok(())
+    }
+}
@@ -6,7 +6,8 @@
 use log::debug;
 use log_derive::{logfn, logfn_inputs};
 use mirai_annotations::assume_unreachable;
-use rustc_hir::def_id::DefId;
+use rustc_ast::ast;
+use rustc_hir::def_id::{DefId, LOCAL_CRATE};
 use rustc_hir::definitions::{DefPathData, DisambiguatedDefPathData};
 use rustc_hir::{ItemKind, Node};
 use rustc_middle::ty;
@@ -363,6 +364,86 @@ fn push_component_name(component_data: DefPathData, target: &mut String) {
     };
 }
 
+/// Returns the DefId of the function that the invariant! macro generates for the struct, enum or
+/// union identified by adt_def_id, if the type has an invariant.
+pub fn get_invariant_function(tcx: TyCtxt<'_>, adt_def_id: DefId) -> Option<DefId> {
+    tcx.inherent_impls(adt_def_id)
+        .iter()
+        .flat_map(|impl_def_id| tcx.associated_item_def_ids(*impl_def_id).iter())
+        .find(|def_id| tcx.item_name(**def_id).as_str() == "mirai_invariant")
+        .copied()
+}
+
+/// Returns the DefId of the function that the typestate! macro generates for the method with the
+/// given name of the struct, enum or union identified by adt_def_id, if the type has a typestate
+/// protocol with a transition for the method.
+pub fn get_typestate_transition_function(
+    tcx: TyCtxt<'_>,
+    adt_def_id: DefId,
+    method_name: &str,
+) -> Option<DefId> {
+    tcx.all_traits(LOCAL_CRATE)
+        .iter()
+        .filter(|trait_def_id| tcx.item_name(**trait_def_id).as_str() == "MiraiTypestate")
+        .flat_map(|trait_def_id| tcx.all_impls(*trait_def_id))
+        .filter(|impl_def_id| {
+            matches!(&tcx.type_of(*impl_def_id).kind, TyKind::Adt(def, _) if def.did == adt_def_id)
+        })
+        .flat_map(|impl_def_id| tcx.associated_item_def_ids(impl_def_id).iter())
+        .find(|def_id| tcx.item_name(**def_id).as_str() == method_name)
+        .copied()
+}
+
+/// Returns the MIRAI tool attributes with the given name, such as #[mirai::skip] for "skip",
+/// that apply to the item identified by def_id. Attributes of enclosing items, such as an impl,
+/// a module or the crate, also apply to the item.
+pub fn get_mirai_attributes<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    def_id: DefId,
+    name: &str,
+) -> Vec<&'tcx ast::Attribute> {
+    let mut result = Vec::new();
+    let mut item_def_id = Some(def_id);
+    while let Some(def_id) = item_def_id {
+        result.extend(tcx.get_attrs(def_id).iter().filter(|attr| {
+            if let ast::AttrKind::Normal(item) = &attr.kind {
+                let segments = &item.path.segments;
+                segments.len() == 2
+                    && segments[0].ident.as_str() == "mirai"
+                    && segments[1].ident.as_str() == name
+            } else {
+                false
+            }
+        }));
+        item_def_id = tcx.parent(def_id);
+    }
+    result
+}
+
+/// Returns true if the item identified by def_id, or an item that encloses it, has the MIRAI
+/// tool attribute with the given name, for example #[mirai::skip].
+pub fn has_mirai_attribute(tcx: TyCtxt<'_>, def_id: DefId, name: &str) -> bool {
+    !get_mirai_attributes(tcx, def_id, name).is_empty()
+}
+
+/// Returns true if the static identified by def_id is a ghost static, i.e. one that is declared
+/// with the ghost_static! macro, which marks it with #[mirai::ghost].
+pub fn is_ghost_static(tcx: TyCtxt<'_>, def_id: DefId) -> bool {
+    has_mirai_attribute(tcx, def_id, "ghost")
+}
+
+/// Returns the kinds of diagnostic, i.e. the SARIF rule ids, that are listed by the
+/// #[mirai::allow(..)] attributes that apply to the item identified by def_id.
+pub fn get_allowed_diagnostic_kinds(tcx: TyCtxt<'_>, def_id: DefId) -> Vec<String> {
+    get_mirai_attributes(tcx, def_id, "allow")
+        .into_iter()
+        .filter_map(|attr| attr.meta_item_list())
+        .flatten()
+        .filter_map(|nested_item| nested_item.ident())
+        .map(|ident| ident.to_string())
+        .collect()
+}
+
 /// Returns a readable display name for a DefId. This name may not be unique.
 pub fn def_id_display_name(tcx: TyCtxt<'_>, def_id: DefId) -> String {
     struct PrettyDefId<'tcx>(DefId, TyCtxt<'tcx>);
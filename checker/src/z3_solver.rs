@@ -7,11 +7,13 @@
 use crate::abstract_value::AbstractValue;
 use crate::abstract_value::AbstractValueTrait;
 use crate::constant_domain::ConstantDomain;
-use crate::expression::{Expression, ExpressionType};
+use crate::expression::{Expression, ExpressionType, QuantifierKind};
+use crate::k_limits;
 use crate::known_names::KnownNames;
-use crate::path::Path;
+use crate::path::{Path, PathEnum, PathSelector};
 use crate::smt_solver::SmtResult;
 use crate::smt_solver::SmtSolver;
+use crate::smt_solver::SolverLimit;
 
 use lazy_static::lazy_static;
 use log::debug;
@@ -52,13 +54,19 @@ impl Debug for Z3Solver {
 
 impl Z3Solver {
     #[logfn_inputs(TRACE)]
-    pub fn new() -> Z3Solver {
+    pub fn new(limit: SolverLimit) -> Z3Solver {
         unsafe {
             let _guard = Z3_MUTEX.lock().unwrap();
             let z3_sys_cfg = z3_sys::Z3_mk_config();
-            let time_out = CString::new("timeout").unwrap().into_raw();
-            let ms = CString::new("100").unwrap().into_raw();
-            z3_sys::Z3_set_param_value(z3_sys_cfg, time_out, ms);
+            // Z3 applies both parameters to each check, rather than to the context as a whole.
+            // Resource units are counted deterministically, unlike time.
+            let (param, value) = match limit {
+                SolverLimit::Time(milliseconds) => ("timeout", milliseconds),
+                SolverLimit::Resources(resources) => ("rlimit", resources),
+            };
+            let param = CString::new(param).unwrap().into_raw();
+            let value = CString::new(value.to_string()).unwrap().into_raw();
+            z3_sys::Z3_set_param_value(z3_sys_cfg, param, value);
 
             let z3_context = z3_sys::Z3_mk_context(z3_sys_cfg);
             let z3_solver = z3_sys::Z3_mk_solver(z3_context);
@@ -96,7 +104,9 @@ impl Z3Solver {
 impl Default for Z3Solver {
     #[logfn_inputs(TRACE)]
     fn default() -> Self {
-        Z3Solver::new()
+        Z3Solver::new(SolverLimit::Time(
+            k_limits::max_solver_time_in_milliseconds(),
+        ))
     }
 }
 
@@ -269,6 +279,13 @@ impl Z3Solver {
             Expression::Or { left, right } => {
                 self.general_boolean_op(left, right, z3_sys::Z3_mk_or)
             }
+            Expression::Quantifier {
+                kind,
+                variable,
+                lower_bound,
+                upper_bound,
+                condition,
+            } => self.general_quantifier(*kind, variable, lower_bound, upper_bound, condition),
             Expression::Reference(path) => self.general_reference(path),
             Expression::Shl { left, right } => {
                 self.bv_binary(128, left, right, z3_sys::Z3_mk_bvshl)
@@ -291,6 +308,10 @@ impl Z3Solver {
                 ..
             }
             | Expression::Variable { path, var_type } => self.general_variable(path, &var_type),
+            Expression::HasTaint { .. } => self.general_has_taint(expression),
+            Expression::Sanitized { operand, .. } | Expression::Tainted { operand, .. } => {
+                self.get_as_z3_ast(&operand.expression)
+            }
             Expression::Widen { path, operand } => {
                 self.get_ast_for_widened(path, operand, operand.expression.infer_type())
             }
@@ -526,13 +547,68 @@ impl Z3Solver {
         unsafe { z3_sys::Z3_mk_fresh_const(self.z3_context, self.empty_str, self.bool_sort) }
     }
 
+    #[logfn_inputs(TRACE)]
+    fn general_quantifier(
+        &self,
+        kind: QuantifierKind,
+        variable: &Rc<Path>,
+        lower_bound: &Rc<AbstractValue>,
+        upper_bound: &Rc<AbstractValue>,
+        condition: &Rc<AbstractValue>,
+    ) -> z3_sys::Z3_ast {
+        let lower_bound_ast = self.get_as_numeric_z3_ast(&lower_bound.expression).1;
+        let upper_bound_ast = self.get_as_numeric_z3_ast(&upper_bound.expression).1;
+        let condition_ast = self.get_as_bool_z3_ast(&condition.expression);
+        unsafe {
+            // The occurrences of the bound variable in the condition are translated to this
+            // constant, which the quantifier then binds.
+            let bound_variable = z3_sys::Z3_mk_const(
+                self.z3_context,
+                self.get_symbol_for(variable),
+                self.int_sort,
+            );
+            let bound_app = z3_sys::Z3_to_app(self.z3_context, bound_variable);
+            let is_ge_lower_bound =
+                z3_sys::Z3_mk_ge(self.z3_context, bound_variable, lower_bound_ast);
+            let is_lt_upper_bound =
+                z3_sys::Z3_mk_lt(self.z3_context, bound_variable, upper_bound_ast);
+            let tmp = vec![is_ge_lower_bound, is_lt_upper_bound];
+            let is_in_range = z3_sys::Z3_mk_and(self.z3_context, 2, tmp.as_ptr());
+            match kind {
+                QuantifierKind::ForAll => {
+                    let body = z3_sys::Z3_mk_implies(self.z3_context, is_in_range, condition_ast);
+                    z3_sys::Z3_mk_forall_const(
+                        self.z3_context,
+                        0,
+                        1,
+                        &bound_app,
+                        0,
+                        std::ptr::null(),
+                        body,
+                    )
+                }
+                QuantifierKind::Exists => {
+                    let tmp = vec![is_in_range, condition_ast];
+                    let body = z3_sys::Z3_mk_and(self.z3_context, 2, tmp.as_ptr());
+                    z3_sys::Z3_mk_exists_const(
+                        self.z3_context,
+                        0,
+                        1,
+                        &bound_app,
+                        0,
+                        std::ptr::null(),
+                        body,
+                    )
+                }
+            }
+        }
+    }
+
     #[logfn_inputs(TRACE)]
     fn general_variable(&self, path: &Rc<Path>, var_type: &ExpressionType) -> z3_sys::Z3_ast {
-        let path_str = CString::new(format!("{:?}", path)).unwrap();
         unsafe {
-            let path_symbol = z3_sys::Z3_mk_string_symbol(self.z3_context, path_str.into_raw());
             let sort = self.get_sort_for(var_type);
-            let ast = z3_sys::Z3_mk_const(self.z3_context, path_symbol, sort);
+            let ast = self.get_ast_for_path(path, sort);
             if var_type.is_integer() {
                 let min_ast = self.get_constant_as_ast(&var_type.min_value());
                 let max_ast = self.get_constant_as_ast(&var_type.max_value());
@@ -547,6 +623,18 @@ impl Z3Solver {
         }
     }
 
+    #[logfn_inputs(TRACE)]
+    fn general_has_taint(&self, expression: &Expression) -> z3_sys::Z3_ast {
+        // The solver knows nothing about taint that depends on the caller, but every occurrence
+        // of the same unknown has to be the same constant.
+        let expression_str = CString::new(format!("{:?}", expression)).unwrap();
+        unsafe {
+            let expression_symbol =
+                z3_sys::Z3_mk_string_symbol(self.z3_context, expression_str.into_raw());
+            z3_sys::Z3_mk_const(self.z3_context, expression_symbol, self.bool_sort)
+        }
+    }
+
     #[logfn_inputs(TRACE)]
     fn general_join(&self, expression: &Expression, path: &Rc<Path>) -> z3_sys::Z3_ast {
         let path_str = CString::new(format!("{:?}", path)).unwrap();
@@ -557,6 +645,37 @@ impl Z3Solver {
         }
     }
 
+    /// Returns the constant that represents the value at path, unless path selects an element
+    /// of a collection using an integer index. In that case the value is the element at the index
+    /// of a Z3 array that represents all of the elements of the collection, so that the values of
+    /// elements at different (possibly quantified) indices are related to each other.
+    #[logfn_inputs(TRACE)]
+    fn get_ast_for_path(&self, path: &Rc<Path>, sort: z3_sys::Z3_sort) -> z3_sys::Z3_ast {
+        if let PathEnum::QualifiedPath {
+            qualifier,
+            selector,
+            ..
+        } = &path.value
+        {
+            if let PathSelector::Index(index) = selector.as_ref() {
+                if index.expression.infer_type().is_integer() {
+                    let index_ast = self.get_as_numeric_z3_ast(&index.expression).1;
+                    let elements_str = CString::new(format!("{:?}[..]", qualifier)).unwrap();
+                    unsafe {
+                        let elements_symbol =
+                            z3_sys::Z3_mk_string_symbol(self.z3_context, elements_str.into_raw());
+                        let array_sort =
+                            z3_sys::Z3_mk_array_sort(self.z3_context, self.int_sort, sort);
+                        let elements =
+                            z3_sys::Z3_mk_const(self.z3_context, elements_symbol, array_sort);
+                        return z3_sys::Z3_mk_select(self.z3_context, elements, index_ast);
+                    }
+                }
+            }
+        }
+        unsafe { z3_sys::Z3_mk_const(self.z3_context, self.get_symbol_for(path), sort) }
+    }
+
     #[logfn_inputs(TRACE)]
     fn get_symbol_for(&self, path: &Rc<Path>) -> z3_sys::Z3_symbol {
         let path_str = CString::new(format!("{:?}", path)).unwrap();
@@ -787,6 +906,9 @@ impl Z3Solver {
             | Expression::Variable { path, var_type } => {
                 self.numeric_variable(expression, path, var_type)
             }
+            Expression::Sanitized { operand, .. } | Expression::Tainted { operand, .. } => {
+                self.get_as_numeric_z3_ast(&operand.expression)
+            }
             Expression::Widen { path, operand } => self.numeric_widen(path, operand),
             _ => (false, self.get_as_z3_ast(expression)),
         }
@@ -1225,12 +1347,10 @@ impl Z3Solver {
                 if *var_type != ExpressionType::Bool {
                     debug!("path {:?}, type {:?}", path, var_type);
                 }
-                let path_str = CString::new(format!("{:?}", path)).unwrap();
-                unsafe {
-                    let path_symbol =
-                        z3_sys::Z3_mk_string_symbol(self.z3_context, path_str.into_raw());
-                    z3_sys::Z3_mk_const(self.z3_context, path_symbol, self.bool_sort)
-                }
+                self.get_ast_for_path(path, self.bool_sort)
+            }
+            Expression::Sanitized { operand, .. } | Expression::Tainted { operand, .. } => {
+                self.get_as_bool_z3_ast(&operand.expression)
             }
             Expression::Widen { path, operand } => {
                 self.get_ast_for_widened(path, operand, ExpressionType::Bool)
@@ -1351,6 +1471,9 @@ impl Z3Solver {
                 ..
             }
             | Expression::Variable { path, var_type } => self.bv_variable(path, var_type, num_bits),
+            Expression::Sanitized { operand, .. } | Expression::Tainted { operand, .. } => {
+                self.get_as_bv_z3_ast(&operand.expression, num_bits)
+            }
             Expression::Widen { path, operand } => self.bv_widen(path, operand, num_bits),
             _ => self.get_as_z3_ast(expression),
         }
@@ -0,0 +1,51 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+// Helpers for the tests that run MIRAI on a small crate in a directory of their own.
+
+use mirai::callbacks;
+use mirai::options::Options;
+use mirai::utils;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+// Writes the source to lib.rs in the given directory and compiles it with MIRAI, using the
+// given options, with the directory as the output directory.
+// Returns false if the compilation failed.
+pub fn run_mirai(directory: &Path, source: &str, options: Options) -> bool {
+    let file_name = directory.join("lib.rs");
+    fs::write(&file_name, source).expect("failed to write the test source");
+    let command_line_arguments: Vec<String> = vec![
+        String::from("--crate-name mirai"),
+        file_name.to_str().expect("valid string").to_string(),
+        String::from("--crate-type"),
+        String::from("lib"),
+        String::from("--edition=2018"),
+        String::from("--out-dir"),
+        directory.to_str().expect("valid string").to_string(),
+        String::from("--sysroot"),
+        utils::find_sysroot(),
+        String::from("-Z"),
+        String::from("span_free_formats"),
+        String::from("-Z"),
+        String::from("mir-emit-retag"),
+    ];
+    let mut call_backs = callbacks::MiraiCallbacks::new(options);
+    let result = std::panic::catch_unwind(move || {
+        rustc_driver::run_compiler(
+            &command_line_arguments,
+            &mut call_backs,
+            None, // use default file loader
+            None, // emit output to default destination
+        )
+    });
+    matches!(result, Ok(Ok(())))
+}
+
+pub fn read_json(path: &Path) -> Value {
+    let content = fs::read_to_string(path).expect("failed to read output");
+    serde_json::from_str(&content).expect("output is not valid JSON")
+}
@@ -0,0 +1,74 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+// Tests of the mirai.toml configuration files that are shipped with MIRAI.
+
+extern crate mirai;
+
+use mirai::config::Config;
+use std::path::PathBuf;
+
+// The crates that MIRAI used to skip when analyzing Libra with --test_only.
+const LIBRA_TEST_EXCLUSIONS: &[&str] = &["storage/storage-service/src", "client/cli/src"];
+
+// The crates that MIRAI used to skip when analyzing Libra without --test_only.
+const LIBRA_EXCLUSIONS: &[&str] = &[
+    "admission-control/admission-control-proto/src",
+    "crypto/crypto/src",
+    "crypto/crypto-derive/src",
+    "common/bitvec/src",
+    "common/bounded-executor/src",
+    "common/debug-interface/src",
+    "common/futures-semaphore/src",
+    "common/metrics/src",
+    "language/bytecode-verifier/src",
+    "language/compiler/bytecode-source-map/src",
+    "language/compiler/ir-to-bytecode/syntax/src",
+    "language/stdlib/src",
+    "language/move-lang/src",
+    "language/move-vm/state/src",
+    "language/vm/src",
+    "network/src",
+    "client/cli/src",
+    "client/libra_wallet/src",
+    "state-synchronizer/src",
+    "storage/jellyfish-merkle/src",
+    "storage/libradb/src",
+    "storage/schemadb/src",
+    "storage/scratchpad/src",
+    "types/src",
+];
+
+#[test]
+fn libra_config_excludes_what_used_to_be_built_in() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("../configs/libra/mirai.toml");
+    let config = Config::read_from(&path).unwrap_or_else(|msg| panic!("{}", msg));
+    let is_excluded = |file_name: &str, test_only: bool| {
+        config
+            .exclude
+            .iter()
+            .any(|e| e.excludes_crate(file_name, test_only))
+    };
+    for test_only in &[true, false] {
+        let expected = if *test_only {
+            LIBRA_TEST_EXCLUSIONS
+        } else {
+            LIBRA_EXCLUSIONS
+        };
+        for crate_path in LIBRA_EXCLUSIONS.iter().chain(LIBRA_TEST_EXCLUSIONS) {
+            let file_name = format!("/libra/{}/lib.rs", crate_path);
+            assert_eq!(
+                is_excluded(&file_name, *test_only),
+                expected.contains(crate_path),
+                "{} with test_only = {}",
+                file_name,
+                test_only
+            );
+        }
+        assert!(!is_excluded("/libra/mempool/src/lib.rs", *test_only));
+    }
+    assert_eq!(config.k_limits.max_analysis_time_for_body, 20);
+}
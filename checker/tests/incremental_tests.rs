@@ -0,0 +1,96 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+// Tests of incremental analysis, which is used when the summary store is shared between runs.
+// These are kept apart from the other tests that run MIRAI outside of the test runner, because
+// sharing the summary store is enabled for the whole process via an environment variable.
+#![feature(rustc_private)]
+
+extern crate mirai;
+extern crate rustc_driver;
+extern crate tempdir;
+
+mod common;
+
+use common::{read_json, run_mirai};
+use mirai::options::Options;
+use serde_json::Value;
+use std::path::Path;
+use tempdir::TempDir;
+
+const CALLEE_WITHOUT_PRECONDITION: &str = "fn check(x: u8) -> u8 {
+    x / 2
+}
+";
+
+const CALLEE_WITH_PRECONDITION: &str = "fn check(x: u8) -> u8 {
+    x + 1
+}
+";
+
+const CALLER: &str = "
+pub fn caller(x: u8) -> u8 {
+    check(x)
+}
+";
+
+// Runs MIRAI, with the same options every time, and returns the statistics and the SARIF log.
+fn run_incrementally(directory: &Path, source: &str) -> (Value, Value) {
+    let statistics_path = directory.join("statistics.json");
+    let sarif_path = directory.join("diagnostics.sarif");
+    let mut options = Options::default();
+    options.statistics_file = Some(statistics_path.to_str().expect("valid string").to_string());
+    options.sarif_file = Some(sarif_path.to_str().expect("valid string").to_string());
+    assert!(run_mirai(directory, source, options));
+    (read_json(&statistics_path), read_json(&sarif_path))
+}
+
+fn analyzed_functions(statistics: &Value) -> Vec<String> {
+    statistics["functions"]
+        .as_array()
+        .expect("functions")
+        .iter()
+        .map(|f| f["summary_key"].as_str().expect("summary key").to_string())
+        .collect()
+}
+
+#[test]
+fn editing_a_callee_updates_the_diagnostics_of_its_caller() {
+    std::env::set_var("MIRAI_SHARE_PERSISTENT_STORE", "true");
+    std::env::remove_var("MIRAI_START_FRESH");
+    let temp_dir = TempDir::new("miraiTest").expect("failed to create a temp dir");
+    let directory = temp_dir.path();
+
+    let source = format!("{}{}", CALLEE_WITHOUT_PRECONDITION, CALLER);
+    let (statistics, log) = run_incrementally(directory, &source);
+    let analyzed = analyzed_functions(&statistics);
+    assert_eq!(analyzed.len(), 1);
+    assert!(analyzed[0].ends_with("caller"));
+    assert_eq!(log["runs"][0]["results"], Value::Array(vec![]));
+
+    // An edit that only moves the functions does not cause them to be analyzed again.
+    let source = format!(
+        "// An unrelated edit\n{}{}",
+        CALLEE_WITHOUT_PRECONDITION, CALLER
+    );
+    let (statistics, log) = run_incrementally(directory, &source);
+    assert!(analyzed_functions(&statistics).is_empty());
+    assert_eq!(log["runs"][0]["results"], Value::Array(vec![]));
+
+    // Changing the callee gives it a precondition that the caller does not satisfy.
+    let source = format!("{}{}", CALLEE_WITH_PRECONDITION, CALLER);
+    let (statistics, log) = run_incrementally(directory, &source);
+    assert_eq!(analyzed_functions(&statistics), analyzed);
+    let results = log["runs"][0]["results"].as_array().expect("results");
+    assert_eq!(results.len(), 1);
+    assert!(results[0]["message"]["text"]
+        .as_str()
+        .expect("message")
+        .ends_with("attempt to add with overflow"));
+    assert_eq!(
+        results[0]["locations"][0]["physicalLocation"]["region"]["startLine"],
+        6
+    );
+}
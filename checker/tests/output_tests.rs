@@ -0,0 +1,252 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+// Tests of the files that MIRAI writes when it is not run as a test runner, such as SARIF logs,
+// baselines and statistics reports. Each test compiles a small crate with MIRAI and then checks
+// the content of the output.
+#![feature(rustc_private)]
+
+extern crate mirai;
+extern crate rustc_driver;
+extern crate tempdir;
+
+mod common;
+
+use common::{read_json, run_mirai};
+use mirai::options::{AnalysisBudget, DiagLevel, Options};
+use mirai::sarif::{Rule, RULES};
+use std::fs;
+use tempdir::TempDir;
+
+const ADD_ONE: &str = "pub fn add_one(x: u8) -> u8 {
+    x + 1
+}
+";
+
+#[test]
+fn sarif_rules_are_in_the_order_of_rule() {
+    let rules = [
+        (Rule::AnalysisTimeout, "analysis_timeout"),
+        (Rule::ConditionalPrecondition, "conditional_precondition"),
+        (Rule::DeallocatedMemory, "deallocated_memory"),
+        (
+            Rule::FalseVerificationCondition,
+            "false_verification_condition",
+        ),
+        (Rule::InconsistentLayout, "inconsistent_layout"),
+        (
+            Rule::InconsistentPostConditions,
+            "inconsistent_post_conditions",
+        ),
+        (Rule::InlineAssembly, "inline_assembly"),
+        (Rule::InvalidAnnotation, "invalid_annotation"),
+        (Rule::MissingSummary, "missing_summary"),
+        (Rule::OffsetOutOfRange, "offset_out_of_range"),
+        (Rule::PossibleError, "possible_error"),
+        (Rule::RedundantAssumption, "redundant_assumption"),
+        (Rule::UnreachableAnnotation, "unreachable_annotation"),
+        (Rule::DefiniteError, "definite_error"),
+    ];
+    assert_eq!(rules.len(), RULES.len());
+    for (rule, id) in rules.iter() {
+        assert_eq!(rule.id(), *id);
+    }
+}
+
+#[test]
+fn sarif_log() {
+    let temp_dir = TempDir::new("miraiTest").expect("failed to create a temp dir");
+    let directory = temp_dir.path();
+    let sarif_path = directory.join("diagnostics.sarif");
+    let mut options = Options::default();
+    options.sarif_file = Some(sarif_path.to_str().expect("valid string").to_string());
+    assert!(run_mirai(directory, ADD_ONE, options));
+
+    let log = read_json(&sarif_path);
+    assert_eq!(log["version"], "2.1.0");
+    let run = &log["runs"][0];
+    assert_eq!(run["tool"]["driver"]["name"], "MIRAI");
+    let rules = run["tool"]["driver"]["rules"].as_array().expect("rules");
+    assert_eq!(rules.len(), RULES.len());
+    let results = run["results"].as_array().expect("results");
+    assert_eq!(results.len(), 1);
+    let result = &results[0];
+    assert_eq!(result["ruleId"], "possible_error");
+    let rule_index = result["ruleIndex"].as_u64().expect("rule index") as usize;
+    assert_eq!(rules[rule_index]["id"], "possible_error");
+    assert_eq!(result["level"], "warning");
+    assert_eq!(
+        result["message"]["text"],
+        "possible attempt to add with overflow"
+    );
+    let location = &result["locations"][0];
+    let physical_location = &location["physicalLocation"];
+    assert!(physical_location["artifactLocation"]["uri"]
+        .as_str()
+        .expect("uri")
+        .ends_with("lib.rs"));
+    assert_eq!(physical_location["region"]["startLine"], 2);
+    assert_eq!(location["logicalLocations"][0]["kind"], "function");
+}
+
+#[test]
+fn baseline_suppresses_recorded_diagnostics() {
+    let temp_dir = TempDir::new("miraiTest").expect("failed to create a temp dir");
+    let directory = temp_dir.path();
+    let baseline_path = directory.join("baseline.json");
+    let baseline_file = baseline_path.to_str().expect("valid string").to_string();
+
+    // Record the diagnostics of the first version of the crate.
+    let mut options = Options::default();
+    options.baseline_file = Some(baseline_file.clone());
+    options.write_baseline = true;
+    assert!(run_mirai(directory, ADD_ONE, options));
+    let baseline = read_json(&baseline_path);
+    let fingerprints = baseline.as_array().expect("fingerprints");
+    assert_eq!(fingerprints.len(), 1);
+    assert_eq!(
+        fingerprints[0]["message"],
+        "possible attempt to add with overflow"
+    );
+    assert_eq!(fingerprints[0]["span_text"], "x + 1");
+
+    // Add a function with a new diagnostic before the old one, which moves the old diagnostic to
+    // another line. Only the new diagnostic should be reported.
+    let sarif_path = directory.join("diagnostics.sarif");
+    let mut options = Options::default();
+    options.baseline_file = Some(baseline_file);
+    options.sarif_file = Some(sarif_path.to_str().expect("valid string").to_string());
+    let source = format!(
+        "pub fn subtract_one(x: u8) -> u8 {{\n    x - 1\n}}\n\n{}",
+        ADD_ONE
+    );
+    assert!(run_mirai(directory, &source, options));
+    let log = read_json(&sarif_path);
+    let results = log["runs"][0]["results"].as_array().expect("results");
+    assert_eq!(results.len(), 1);
+    assert_eq!(
+        results[0]["message"]["text"],
+        "possible attempt to subtract with overflow"
+    );
+}
+
+#[test]
+fn statistics_report() {
+    let temp_dir = TempDir::new("miraiTest").expect("failed to create a temp dir");
+    let directory = temp_dir.path();
+    let statistics_path = directory.join("statistics.json");
+    let mut options = Options::default();
+    options.statistics_file = Some(statistics_path.to_str().expect("valid string").to_string());
+    assert!(run_mirai(directory, ADD_ONE, options));
+
+    let statistics = read_json(&statistics_path);
+    assert_eq!(statistics["crate"], "lib");
+    let functions = statistics["functions"].as_array().expect("functions");
+    assert_eq!(functions.len(), 1);
+    let function = functions[0].as_object().expect("function");
+    assert!(function["summary_key"]
+        .as_str()
+        .expect("summary key")
+        .ends_with("add_one"));
+    // The generic arguments and the reason for being angelic are only present if they apply.
+    let mut fields: Vec<&str> = function.keys().map(String::as_str).collect();
+    fields.sort();
+    assert_eq!(
+        fields,
+        [
+            "analysis_time_in_milliseconds",
+            "angelic",
+            "blocks",
+            "fixpoint_iterations",
+            "fixpoint_limit_reached",
+            "solver_queries",
+        ]
+    );
+    assert!(function["analysis_time_in_milliseconds"].is_u64());
+    // One block does the checked addition and the other returns its result.
+    assert_eq!(function["blocks"], 2);
+    let fixpoint_iterations = function["fixpoint_iterations"].as_u64();
+    assert!(fixpoint_iterations.expect("iterations") >= 1);
+    assert_eq!(function["fixpoint_limit_reached"], false);
+    assert_eq!(function["angelic"], false);
+    let solver_queries = function["solver_queries"].as_object().expect("queries");
+    let mut outcomes: Vec<&str> = solver_queries.keys().map(String::as_str).collect();
+    outcomes.sort();
+    assert_eq!(outcomes, ["satisfiable", "undefined", "unsatisfiable"]);
+    assert!(solver_queries.values().all(|count| count.is_u64()));
+}
+
+#[test]
+fn running_out_of_fuel() {
+    let temp_dir = TempDir::new("miraiTest").expect("failed to create a temp dir");
+    let directory = temp_dir.path();
+    let config_path = directory.join("mirai.toml");
+    fs::write(&config_path, "[k_limits]\nmax_fuel_for_body = 20\n").expect("config written");
+    let sarif_path = directory.join("diagnostics.sarif");
+    let statistics_path = directory.join("statistics.json");
+    let mut options = Options::default();
+    options.budget = AnalysisBudget::Fuel;
+    // With relaxed diagnostics, a function that could not be analyzed is not reported.
+    options.diag_level = DiagLevel::STRICT;
+    options.config_file = Some(config_path.to_str().expect("valid string").to_string());
+    options.sarif_file = Some(sarif_path.to_str().expect("valid string").to_string());
+    options.statistics_file = Some(statistics_path.to_str().expect("valid string").to_string());
+    let source = "pub fn sum(n: u64) -> u64 {
+    let mut result = 0;
+    for i in 0..n {
+        result += i;
+    }
+    result
+}
+";
+    assert!(run_mirai(directory, source, options));
+
+    let log = read_json(&sarif_path);
+    let results = log["runs"][0]["results"].as_array().expect("results");
+    assert_eq!(results.len(), 1);
+    let result = &results[0];
+    assert_eq!(result["ruleId"], "analysis_timeout");
+    assert_eq!(
+        result["message"]["text"],
+        "The analysis of this function ran out of fuel"
+    );
+    let statistics = read_json(&statistics_path);
+    let function = &statistics["functions"][0];
+    assert_eq!(function["angelic"], true);
+    assert_eq!(function["angelic_reason"]["kind"], "out_of_fuel");
+}
+
+#[test]
+fn smt_dump_marks_cached_results() {
+    // The two functions make the same queries, so the second gets its results from the cache,
+    // unless the cache is disabled.
+    let source = format!(
+        "{}\npub fn add_one_again(x: u8) -> u8 {{\n    x + 1\n}}\n",
+        ADD_ONE
+    );
+    for no_smt_cache in [false, true].iter() {
+        let temp_dir = TempDir::new("miraiTest").expect("failed to create a temp dir");
+        let directory = temp_dir.path();
+        let dump_dir = directory.join("smt");
+        let mut options = Options::default();
+        options.smt_dump_dir = Some(dump_dir.to_str().expect("valid string").to_string());
+        options.no_smt_cache = *no_smt_cache;
+        assert!(run_mirai(directory, &source, options));
+
+        let mut checks = 0;
+        let mut cached_checks = 0;
+        for entry in fs::read_dir(&dump_dir).expect("dump dir") {
+            let content = fs::read_to_string(entry.expect("entry").path()).expect("dump");
+            checks += content.matches("; check: ").count();
+            cached_checks += content.matches(" (cached)").count();
+        }
+        assert!(checks > 0);
+        if *no_smt_cache {
+            assert_eq!(cached_checks, 0);
+        } else {
+            assert!(cached_checks > 0);
+        }
+    }
+}
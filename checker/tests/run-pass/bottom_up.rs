@@ -0,0 +1,61 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+
+// A test that checks that callers can use the summaries of callees that were analyzed before them,
+// and that these summaries still have the preconditions that the callers must satisfy.
+
+// MIRAI_FLAGS --bottom_up
+
+#[macro_use]
+extern crate mirai_annotations;
+
+pub fn caller() -> i32 {
+    let r = callee(2);
+    verify!(r == 1);
+    r
+}
+
+pub fn callee(x: i32) -> i32 {
+    precondition!(x > 0);
+    x - 1
+}
+
+pub fn bad_caller() -> i32 {
+    checked_decrement(0) //~ unsatisfied precondition: x should be positive
+}
+
+pub fn checked_decrement(x: i32) -> i32 {
+    precondition!(x > 0, "x should be positive"); //~ related location
+    x - 1
+}
+
+pub fn overflowing_caller() -> u8 {
+    increment(255) //~ attempt to add with overflow
+}
+
+pub fn increment(x: u8) -> u8 {
+    // The analysis of increment as a root reports this, but its callers must still check it.
+    x + 1 //~ possible attempt to add with overflow
+          //~ related location
+}
+
+pub fn is_even(n: u32) -> bool {
+    if n == 0 {
+        true
+    } else {
+        is_odd(n - 1)
+    }
+}
+
+pub fn is_odd(n: u32) -> bool {
+    if n == 0 {
+        false
+    } else {
+        is_even(n - 1)
+    }
+}
+
+pub fn main() {}
@@ -0,0 +1,19 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+
+// A test that checks that possible failures are reported along with values that cause them.
+
+// MIRAI_FLAGS --counterexamples
+
+#[macro_use]
+extern crate mirai_annotations;
+
+pub fn foo(x: i32) {
+    verify!(x != 3); //~ possible false verification condition
+                     //~ counterexample: x = 3
+}
+
+pub fn main() {}
@@ -0,0 +1,94 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+
+// A test that checks the measures of loops and recursive functions.
+
+#[macro_use]
+extern crate mirai_annotations;
+
+pub fn count_up(n: usize) {
+    let mut i = 0;
+    while i < n {
+        decreases!(n - i);
+        i += 1;
+    }
+}
+
+pub fn count_down(n: u64) {
+    let mut i = n;
+    while i > 0 {
+        decreases!(i);
+        i -= 1;
+    }
+}
+
+pub fn no_progress(n: usize) {
+    let mut i = 0;
+    while i < n {
+        decreases!(n - i); //~ possible non-termination, the decreases measure may not decrease
+        if i % 2 == 0 {
+            i += 1;
+        }
+    }
+}
+
+pub fn may_become_negative(n: i64) {
+    let mut i = n;
+    while i > -10 {
+        decreases!(i); //~ possible non-termination, the decreases measure may become negative
+        i -= 1;
+    }
+}
+
+pub fn depth(n: u64) -> u64 {
+    decreases!(n);
+    if n == 0 {
+        0
+    } else {
+        depth(n - 1)
+    }
+}
+
+pub fn no_progress_recursion(n: u64) -> u64 {
+    decreases!(n);
+    if n == 0 {
+        0
+    } else {
+        no_progress_recursion(n) //~ possible non-termination, the decreases measure may not decrease
+    }
+}
+
+pub fn negative_recursion(n: i64) -> i64 {
+    decreases!(n);
+    if n < -5 {
+        0
+    } else {
+        negative_recursion(n - 1) //~ possible non-termination, the decreases measure may become negative
+    }
+}
+
+pub fn is_even(n: u64) -> bool {
+    decreases!(n);
+    if n == 0 {
+        true
+    } else {
+        is_odd(n - 1) //~ possible non-termination, the decreases measure is not checked for mutually recursive calls
+    }
+}
+
+pub fn is_odd(n: u64) -> bool {
+    if n == 0 {
+        false
+    } else {
+        is_even(n - 1)
+    }
+}
+
+pub fn not_an_integer(b: bool) {
+    decreases!(b); //~ the measure of decreases! should be an integer
+}
+
+pub fn main() {}
@@ -0,0 +1,29 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+
+// A test that checks that the analysis works the same when its budget is fuel rather than time.
+
+// MIRAI_FLAGS --budget=fuel
+
+#[macro_use]
+extern crate mirai_annotations;
+
+pub fn abs(x: i32) -> i32 {
+    precondition!(x > i32::MIN);
+    if x < 0 {
+        -x
+    } else {
+        x
+    }
+}
+
+pub fn get(a: &[i32], i: usize) -> i32 {
+    a[i] //~ possible index out of bounds
+}
+
+pub fn main() {
+    verify!(abs(-3) == 3);
+}
@@ -0,0 +1,24 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+
+// A test that checks that generic functions are analyzed via their concrete instances.
+
+// MIRAI_FLAGS --generic_instances
+
+pub fn first<T: Copy>(v: &[T]) -> T {
+    v[0] //~ possible index out of bounds
+         //~ instantiated with T = i32
+}
+
+#[mirai::skip]
+pub fn skipped_first<T: Copy>(v: &[T]) -> T {
+    v[0]
+}
+
+pub fn main() {
+    let _ = first(&[1, 2]);
+    let _ = skipped_first(&[1, 2]);
+}
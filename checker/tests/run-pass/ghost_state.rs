@@ -0,0 +1,60 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+
+// A test that tracks a ghost static across calls.
+
+#[macro_use]
+extern crate mirai_annotations;
+
+ghost_static! {
+    static LOCKED: bool = false;
+}
+
+pub fn lock() {
+    ghost! {
+        precondition!(!LOCKED, "already locked"); //~ related location
+        //~ related location
+        LOCKED = true;
+    }
+}
+
+pub fn unlock() {
+    ghost! {
+        precondition!(LOCKED, "not locked"); //~ related location
+        LOCKED = false;
+    }
+}
+
+fn lock_indirectly() {
+    lock(); //~ related location
+}
+
+pub fn lock_and_unlock() {
+    ghost! { LOCKED = false; }
+    lock();
+    unlock();
+    lock();
+    unlock();
+}
+
+pub fn lock_twice() {
+    ghost! { LOCKED = false; }
+    lock();
+    lock(); //~ unsatisfied precondition: already locked
+}
+
+pub fn unlock_without_lock() {
+    ghost! { LOCKED = false; }
+    unlock(); //~ unsatisfied precondition: not locked
+}
+
+pub fn lock_then_lock_indirectly() {
+    ghost! { LOCKED = false; }
+    lock();
+    lock_indirectly(); //~ unsatisfied precondition: already locked
+}
+
+pub fn main() {}
@@ -0,0 +1,40 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+
+// A test that checks loop invariants on entry to a loop and after an iteration.
+
+#[macro_use]
+extern crate mirai_annotations;
+
+pub fn valid(n: usize) {
+    let mut i = 0;
+    while i < n {
+        loop_invariant!(i < n);
+        i += 1;
+    }
+}
+
+pub fn fails_on_entry(n: usize) {
+    let mut i = n;
+    while i > 0 {
+        loop_invariant!(i <= 100); //~ possible violation of the loop invariant on entry to the loop
+        i -= 1;
+    }
+}
+
+pub fn not_inductive(n: usize) {
+    let mut i = 0;
+    while i < n {
+        loop_invariant!(i < 10); //~ possible violation of the loop invariant after an iteration, it may not be inductive
+        i += 1;
+    }
+}
+
+pub fn not_in_a_loop(n: usize) {
+    loop_invariant!(n > 0); //~ a loop invariant must be at the start of a loop body
+}
+
+pub fn main() {}
@@ -0,0 +1,44 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+
+// A test that uses attributes to exclude functions from analysis or to suppress their diagnostics.
+
+#[mirai::skip]
+pub fn skipped(x: u8) -> u8 {
+    x + 1
+}
+
+pub fn call_skipped() -> u8 {
+    skipped(255)
+}
+
+#[mirai::trusted]
+pub fn trusted(x: u8) -> u8 {
+    x + 1 //~ related location
+}
+
+pub fn call_trusted() -> u8 {
+    trusted(255) //~ attempt to add with overflow
+}
+
+#[mirai::allow(possible_error)]
+pub fn allowed(x: u8) -> u8 {
+    x + 1
+}
+
+#[mirai::allow(possible_error)]
+pub mod allowed_module {
+    pub fn increment(x: u8) -> u8 {
+        x + 1
+    }
+}
+
+#[mirai::allow(redundant_assumption)]
+pub fn other_kind_allowed(x: u8) -> u8 {
+    x + 1 //~ possible attempt to add with overflow
+}
+
+pub fn main() {}
@@ -0,0 +1,52 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+
+// A test that declares the places that functions may modify.
+
+#[macro_use]
+extern crate mirai_annotations;
+
+pub struct Point {
+    x: u64,
+    y: u64,
+}
+
+impl Point {
+    pub fn move_right(&mut self) {
+        modifies!(self.x);
+        if self.x < 100 {
+            self.x += 1;
+        }
+    }
+
+    pub fn move_diagonally(&mut self) {
+        //~ the function may modify places that are not listed by modifies!
+        modifies!(self.x);
+        if self.x < 100 && self.y < 100 {
+            self.x += 1;
+            self.y += 1;
+        }
+    }
+}
+
+pub trait Reset {
+    // A contract for implementations that do not say what they reset the value to.
+    fn reset(value: &mut u64) {
+        modifies!(*value);
+    }
+}
+
+pub struct Resetter {}
+
+impl Reset for Resetter {}
+
+pub fn test_reset() {
+    let mut v = 1;
+    Resetter::reset(&mut v);
+    verify!(v == 1); //~ possible false verification condition
+}
+
+pub fn main() {}
@@ -0,0 +1,52 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+
+// A test that uses old! to relate the state on return from a function to its state on entry.
+
+#[macro_use]
+extern crate mirai_annotations;
+
+pub struct Counter {
+    count: u64,
+}
+
+impl Counter {
+    pub fn increment(&mut self) {
+        precondition!(self.count < std::u64::MAX);
+        self.count += 1;
+        postcondition!(self.count == old!(self.count) + 1);
+    }
+}
+
+pub fn swap(x: &mut u64, y: &mut u64) {
+    let t = *x;
+    *x = *y;
+    *y = t;
+    postcondition!(*x == old!(*y) && *y == old!(*x));
+}
+
+pub fn not_a_parameter() -> u64 {
+    let x = 1;
+    //~ the argument of old! should be a place rooted in a parameter
+    postcondition!(old!(x) == 1);
+    x
+}
+
+pub fn test_increment(c: &mut Counter) {
+    precondition!(c.count < 10);
+    let before = c.count;
+    c.increment();
+    verify!(c.count == before + 1);
+}
+
+pub fn test_swap() {
+    let mut a = 1;
+    let mut b = 2;
+    swap(&mut a, &mut b);
+    verify!(a == 2 && b == 1);
+}
+
+pub fn main() {}
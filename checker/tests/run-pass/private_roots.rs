@@ -0,0 +1,18 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+
+// A test that checks that private functions can be analyzed as roots.
+
+// MIRAI_FLAGS --private_roots
+
+fn increment(a: u8) -> u8 {
+    a + 1 //~ possible attempt to add with overflow
+          //~ would be a precondition of the callers of this private function
+}
+
+pub fn main() {
+    increment(1);
+}
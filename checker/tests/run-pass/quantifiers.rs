@@ -0,0 +1,35 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+
+// A test that uses forall! and exists! to state conditions on the elements of slices.
+
+#[macro_use]
+extern crate mirai_annotations;
+
+pub fn divide_by_first(v: &[u32], x: u32) -> u32 {
+    precondition!(v.len() > 0);
+    precondition!(forall!(i in 0..v.len(), v[i] != 0));
+    x / v[0]
+}
+
+pub fn all_in_range(n: usize) {
+    verify!(forall!(i in 0..n, i < n));
+}
+
+pub fn some_in_range(n: usize) {
+    precondition!(n > 0);
+    verify!(exists!(i in 0..n, i == 0));
+}
+
+pub fn maybe_empty_range(n: usize) {
+    verify!(exists!(i in 0..n, i == 0)); //~ possible false verification condition
+}
+
+pub fn empty_range() {
+    verify!(exists!(i in 0..0, i == 0)); //~ provably false verification condition
+}
+
+pub fn main() {}
@@ -0,0 +1,21 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+
+// A test that registers the mirai tool itself, which MIRAI then must not do again.
+
+#![feature(register_tool)]
+#![register_tool(mirai)]
+
+#[mirai::skip]
+pub fn skipped(x: u8) -> u8 {
+    x + 1
+}
+
+pub fn call_skipped() -> u8 {
+    skipped(255)
+}
+
+pub fn main() {}
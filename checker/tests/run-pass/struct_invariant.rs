@@ -0,0 +1,58 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+
+// A test that declares an invariant for a struct and relies on it.
+
+#[macro_use]
+extern crate mirai_annotations;
+
+pub struct Counter {
+    count: u32,
+    limit: u32,
+}
+
+invariant!(Counter, counter => counter.count <= counter.limit);
+
+impl Counter {
+    pub fn new(limit: u32) -> Counter {
+        Counter { count: 0, limit }
+    }
+
+    pub fn with_count(count: u32, limit: u32) -> Counter {
+        //~ possible violation of the invariant of Counter
+        Counter { count, limit }
+    }
+
+    pub fn increment(&mut self) {
+        if self.count < self.limit {
+            self.count += 1;
+        }
+    }
+
+    pub fn reset_limit(&mut self) {
+        //~ possible violation of the invariant of Counter
+        self.limit = 0;
+    }
+
+    pub fn remaining(&self) -> u32 {
+        self.limit - self.count // no overflow, because of the invariant
+    }
+}
+
+pub fn increment_twice(counter: &mut Counter) {
+    counter.increment();
+    counter.increment();
+    verify!(counter.count <= counter.limit);
+}
+
+pub fn remaining_after_check(counter: &Counter) -> u32 {
+    let remaining = counter.remaining();
+    // Methods that take &self also establish the invariant for their callers.
+    verify!(counter.count <= counter.limit);
+    remaining
+}
+
+pub fn main() {}
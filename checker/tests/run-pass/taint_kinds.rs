@@ -0,0 +1,70 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+
+// A test that tracks several kinds of taint from sources, through sanitizers, to sinks.
+
+#[macro_use]
+extern crate mirai_annotations;
+
+taint_kind!(pub UserInput);
+taint_kind!(pub SecretData);
+
+pub struct Request {
+    pub id: u64,
+    pub len: u64,
+}
+
+pub fn read_input() -> u64 {
+    let input = abstract_value!(0u64);
+    add_taint!(input, UserInput);
+    input
+}
+
+pub fn read_secret() -> u64 {
+    let secret = abstract_value!(0u64);
+    add_taint!(secret, SecretData);
+    secret
+}
+
+pub fn escape(value: u64) -> u64 {
+    let escaped = value;
+    remove_taint!(escaped, UserInput);
+    escaped
+}
+
+pub fn run_query(query: u64) {
+    taint_sink!(query, UserInput); //~ related location
+}
+
+pub fn tainted_flow() {
+    let id = read_input();
+    run_query(id + 1); //~ data tainted with UserInput reaches a sink
+}
+
+pub fn tainted_struct_flow() {
+    let request = Request {
+        id: read_input(),
+        len: 0,
+    };
+    verify!(has_taint!(request, UserInput));
+    verify!(!has_taint!(request.len, UserInput));
+    let copy = request;
+    verify!(has_taint!(copy.id, UserInput));
+}
+
+pub fn sanitized_flow() {
+    let id = escape(read_input());
+    verify!(!has_taint!(id, UserInput));
+    run_query(id);
+}
+
+pub fn other_kind_flow() {
+    let secret = escape(read_secret());
+    verify!(has_taint!(secret, SecretData));
+    run_query(secret);
+}
+
+pub fn main() {}
@@ -0,0 +1,76 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+
+// A test that checks calls against a typestate protocol.
+
+#[macro_use]
+extern crate mirai_annotations;
+
+pub struct Connection {
+    port: u16,
+}
+
+typestate!(Connection {
+    states: Closed, Open, Finished;
+    open: Closed -> Open, //~ related location
+    send: Open -> Open, //~ related location
+    close: Open -> Finished, //~ related location
+});
+
+impl Connection {
+    pub fn new(port: u16) -> Connection {
+        Connection { port }
+    }
+
+    pub fn open(&mut self) {}
+
+    pub fn send(&mut self, _data: u64) {}
+
+    pub fn close(&mut self) {}
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+pub fn follows_protocol() {
+    let mut c = Connection::new(80);
+    let _ = c.port();
+    c.open();
+    c.send(1);
+    c.send(2);
+    c.close();
+    let _ = c.port();
+}
+
+pub fn send_before_open() {
+    let mut c = Connection::new(80);
+    c.send(1); //~ protocol violation: send requires Connection to be in state Open
+}
+
+pub fn close_twice() {
+    let mut c = Connection::new(80);
+    c.open();
+    c.close();
+    c.close(); //~ protocol violation: close requires Connection to be in state Open
+}
+
+fn send_on(c: &mut Connection) {
+    c.send(3); //~ related location
+}
+
+pub fn send_on_opened() {
+    let mut c = Connection::new(80);
+    c.open();
+    send_on(&mut c);
+}
+
+pub fn send_on_new() {
+    let mut c = Connection::new(80);
+    send_on(&mut c); //~ protocol violation: send requires Connection to be in state Open
+}
+
+pub fn main() {}
@@ -0,0 +1,87 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+// Tests of the conversation MIRAI has with an external SMT-LIB solver. The solver is a stub
+// script that logs the commands it receives and does not know the answer to any query.
+#![feature(rustc_private)]
+#![cfg(unix)]
+
+extern crate mirai;
+extern crate rustc_driver;
+extern crate tempdir;
+
+mod common;
+
+use common::run_mirai;
+use mirai::options::Options;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use tempdir::TempDir;
+
+const SETUP_COMMANDS: [&str; 4] = [
+    "(set-option :global-declarations true)",
+    "(set-option :produce-models true)",
+    "(set-logic ALL)",
+    "(declare-sort Any 0)",
+];
+
+// Writes a stub solver to the given directory, which appends the commands it receives to the
+// given log, and returns its path.
+fn write_stub_solver(directory: &Path, log: &Path) -> String {
+    let stub_path = directory.join("stub_solver.sh");
+    let script = format!(
+        "#!/bin/sh
+while IFS= read -r line; do
+  echo \"$line\" >> '{}'
+  case \"$line\" in
+    \"(check-sat)\") echo unknown ;;
+    \"(echo \"*) echo '\"mirai-end-of-responses\"' ;;
+  esac
+done
+",
+        log.to_str().expect("valid string")
+    );
+    fs::write(&stub_path, script).expect("stub written");
+    fs::set_permissions(&stub_path, fs::Permissions::from_mode(0o755))
+        .expect("stub made executable");
+    stub_path.to_str().expect("valid string").to_string()
+}
+
+#[test]
+fn the_solver_is_set_up_after_every_reset() {
+    let temp_dir = TempDir::new("miraiTest").expect("failed to create a temp dir");
+    let directory = temp_dir.path();
+    let log_path = directory.join("commands.log");
+    let mut options = Options::default();
+    options.smt_solver = Some(write_stub_solver(directory, &log_path));
+    options.no_smt_cache = true;
+    let source = "pub fn add_one(x: u8) -> u8 {
+    x + 1
+}
+
+pub fn add_two(x: u8) -> u8 {
+    x + 2
+}
+";
+    assert!(run_mirai(directory, source, options));
+
+    let log = fs::read_to_string(&log_path).expect("commands logged");
+    let commands: Vec<&str> = log.lines().collect();
+    assert_eq!(&commands[..SETUP_COMMANDS.len()], &SETUP_COMMANDS[..]);
+    let resets: Vec<usize> = commands
+        .iter()
+        .enumerate()
+        .filter(|(_, command)| **command == "(reset)")
+        .map(|(i, _)| i)
+        .collect();
+    // Every root starts with a reset, which must not discard the setup of its queries.
+    assert!(resets.len() >= 2);
+    for i in resets {
+        let end = (i + 1 + SETUP_COMMANDS.len()).min(commands.len());
+        assert_eq!(&commands[i + 1..end], &SETUP_COMMANDS[..]);
+    }
+    assert!(commands.contains(&"(check-sat)"));
+}
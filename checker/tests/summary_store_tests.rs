@@ -0,0 +1,41 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//
+// Tests of the summary store that do not need to run MIRAI.
+#![feature(rustc_private)]
+
+extern crate mirai;
+extern crate rustc_driver;
+extern crate tar;
+extern crate tempdir;
+
+use mirai::summaries::{SummaryStore, FORMAT_VERSION};
+use tar::Archive;
+use tempdir::TempDir;
+
+// The seed store predates the recording of format versions, so opening it must convert its
+// summaries rather than discard them.
+#[test]
+fn opening_the_seed_store_keeps_the_std_summaries() {
+    let directory = TempDir::new("seed_store").unwrap();
+    let bytes: &[u8] = include_bytes!("../../binaries/summary_store.tar");
+    Archive::new(bytes).unpack(directory.path()).unwrap();
+
+    let store = SummaryStore::open(&directory.path().join(".summary_store.sled")).unwrap();
+    assert_eq!(store.format_version(), Some(FORMAT_VERSION));
+    assert!(!store.keys_with_prefix("core.").unwrap().is_empty());
+    let summary = store
+        .get("core.cmp.max__u32")
+        .unwrap()
+        .expect("the seed store should have a summary for core::cmp::max::<u32>");
+    assert!(summary.is_computed);
+    assert!(!summary.side_effects.is_empty());
+    assert!(summary.modified_paths.is_empty());
+    drop(store);
+
+    // Reopening a converted store leaves its summaries alone.
+    let store = SummaryStore::open(&directory.path().join(".summary_store.sled")).unwrap();
+    assert!(store.get("core.cmp.max__u32").unwrap().is_some());
+}
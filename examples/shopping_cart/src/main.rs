@@ -5,14 +5,12 @@
 //
 
 // This is an example of using pre/post conditions as well as invariants
-// via the contracts crate.
+// via the macros of the mirai_annotations crate.
 //
 // We define a simple shopping cart with an invariant both on items in the cart
 // and the overall cart.
 //
 // TODO(wrwg): this example is not yet fully functional regarding MIRAI verification.
-use contracts::*;
-
 use mirai_annotations::*;
 
 // Items stored in the shopping cart.
@@ -22,18 +20,23 @@ pub struct Item {
     price: u64,
 }
 
+// The invariant is assumed on entry to the public methods of Item that take &self, and
+// established by the public functions that return a new Item.
+invariant!(Item, item => item.is_valid());
+
 impl Item {
-    // Invariant for the Item struct. We define those invariants via functions
-    // so they can easily be referenced from attributes.
-    fn invariant(&self) -> bool {
+    // The condition of the invariant, as a function, so that it can also be used by the
+    // invariant of the cart and by tests.
+    pub fn is_valid(&self) -> bool {
         !self.name.is_empty() && self.price > 0
     }
 
-    // Creates a new Item, satisfying the invariant. Notice we can't use
-    // the `#[invariant]` attribute for new because this requires a `self` argument.
-    #[pre(!name.is_empty() && price > 0)]
-    #[post(ret.invariant())]
-    fn new(name: &'static str, price: u64) -> Item {
+    // Creates a new Item, satisfying the invariant.
+    pub fn new(name: &'static str, price: u64) -> Item {
+        checked_precondition!(
+            !name.is_empty() && price > 0,
+            "an item must have a name and a price"
+        );
         Item { name, price }
     }
 }
@@ -45,60 +48,61 @@ pub struct ShoppingCart {
     total: u64,
 }
 
+// The invariant is assumed on entry to all public methods of the cart that take &self or
+// &mut self, and is verified on return from the ones that take &mut self and from new.
+invariant!(ShoppingCart, cart => cart.is_valid());
+
 impl ShoppingCart {
-    // Invariant for the shopping cart.
-    fn invariant(&self) -> bool {
+    // The condition of the invariant of the cart.
+    pub fn is_valid(&self) -> bool {
         // TODO(wrwg): this invariant is currently not handled by MIRAI:
         // function DefId(2:1711 ~ core[5a18]::ops[0]::deref[0]::Deref[0]::deref[0])
         // Comment the code out to see other issues with this code.
-        self.items.iter().all(|x| x.invariant())
+        self.items.iter().all(|x| x.is_valid())
             && self.items.iter().map(|x| x.price).sum::<u64>() == self.total
     }
 
-    #[post(ret.invariant())]
-    fn new() -> ShoppingCart {
+    pub fn new() -> ShoppingCart {
         ShoppingCart {
             items: vec![],
             total: 0,
         }
     }
-}
 
-// Implements methods of the shopping cart. By attaching the `#[invariant]` attribute
-// all methods in the impl which work on `self` get automatically injected
-// the invariant both as pre and post condition, in addition to what they state
-// individually.
-// TODO(wrwg): having both an invariant here and a post below is currently not supported
-// by MIRAI: "only one post condition is supported"
-#[invariant(self.invariant())]
-impl ShoppingCart {
-    #[pre(self.total <= std::u64::MAX - item.price && self.items.len() < std::usize::MAX)]
-    #[post(self.items.len() == old(self.items.len()) + 1)]
-    fn add(&mut self, item: Item) {
+    pub fn add(&mut self, item: Item) {
+        checked_precondition!(
+            self.total <= std::u64::MAX - item.price && self.items.len() < std::usize::MAX
+        );
         self.total += item.price;
         self.items.push(item);
+        postcondition!(self.items.len() == old!(self.items.len()) + 1);
     }
 
-    // TODO(wrwg): This seems to generate a false positive:
-    // "provably false verification condition" (after warning for
-    // integer overflow, which might be the cause).
-    #[post(self.items.len() == old(self.items.len()) + 1)]
-    fn add_broken_invariant(&mut self, item: Item) {
+    // MIRAI reports that this method does not maintain the invariant of the cart,
+    // since the total is not updated.
+    pub fn add_broken_invariant(&mut self, item: Item) {
         //self.total += item.price;
         self.items.push(item);
+        postcondition!(self.items.len() == old!(self.items.len()) + 1);
     }
 
     // TODO(wrwg): This currently can not be handled by MIRAI:
     // DefId(2:1739 ~ core[5a18]::ops[0]::function[0]::FnMut[0]::call_mut[0])
-    #[post(ret == old(self.total))]
-    fn checkout(&mut self) -> u64 {
+    pub fn checkout(&mut self) -> u64 {
         let bill = self.total;
         self.total = 0;
         self.items.clear();
+        postcondition!(bill == old!(self.total));
         bill
     }
 }
 
+impl Default for ShoppingCart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // A main entry point which violates conditions.
 pub fn main() {
     let mut cart = ShoppingCart::new();
@@ -118,11 +122,12 @@ mod tests {
         let mut cart = ShoppingCart::new();
         cart.add(Item::new("ipad pro", 899));
         cart.add(Item::new("ipad folio", 169));
+        checked_verify!(cart.is_valid());
         checked_verify_eq!(cart.checkout(), 899 + 169);
     }
 
     #[test]
-    #[should_panic(expected = "Pre-condition of new violated")]
+    #[should_panic(expected = "an item must have a name and a price")]
     fn fail_item_new() {
         let mut cart = ShoppingCart::new();
         // Below violates precondition of Item::new
@@ -130,11 +135,11 @@ mod tests {
         checked_verify_eq!(cart.checkout(), 0);
     }
 
+    // Invariants are only checked by MIRAI, so this checks the condition explicitly.
     #[test]
-    #[should_panic(expected = "Invariant of add_broken_invariant violated")]
     fn fail_add_invariant() {
         let mut cart = ShoppingCart::new();
         cart.add_broken_invariant(Item::new("ipad pro", 899));
-        checked_verify_eq!(cart.checkout(), 899);
+        checked_verify!(!cart.is_valid());
     }
 }